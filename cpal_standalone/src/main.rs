@@ -15,6 +15,10 @@ fn main() {
     let (matrix, node_exec) = init_hexosynth();
     let matrix = Arc::new(Mutex::new(matrix));
 
+    // Plays the pattern chains of the TSeq nodes, also without the GUI:
+    let _song_sequencer =
+        hexosynth::pattern_bank::SongSequencer::start(matrix.clone());
+
     start_backend(node_exec, move || {
         open_hexosynth(None, matrix.clone());
     });
@@ -33,6 +37,7 @@ where
     let channels    = config.channels as usize;
 
     node_exec.set_sample_rate(sample_rate);
+    hexosynth::audio_tap::set_sample_rate(sample_rate);
 
     let input_bufs = [[0.0; hexodsp::dsp::MAX_BLOCK_SIZE]; 2];
     let mut outputbufs = [[0.0; hexodsp::dsp::MAX_BLOCK_SIZE]; 2];
@@ -78,6 +83,7 @@ where
                 context.output[1].fill(0.0);
 
                 node_exec.process(&mut context);
+                hexosynth::audio_tap::feed(context.input, &*context.output);
 
                 // This copy loop is a bit inefficient, it's likely you can
                 // pass the right array slices directly into node_exec.process()
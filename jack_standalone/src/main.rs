@@ -39,6 +39,7 @@ impl jack::NotificationHandler for Notifications {
         println!("JACK: sample rate changed to {}", srate);
         let mut ne = self.node_exec.lock().unwrap();
         ne.set_sample_rate(srate as f32);
+        hexosynth::audio_tap::set_sample_rate(srate as f32);
         jack::Control::Continue
     }
 
@@ -140,6 +141,9 @@ fn start_backend<F: FnMut()>(node_exec: NodeExecutor, mut f: F) {
     let mut out_b =
         client.register_port("hexosynth_out2", jack::AudioOut::default())
             .unwrap();
+    let midi_in =
+        client.register_port("hexosynth_midi_in", jack::MidiIn::default())
+            .unwrap();
 
     let ne        = Arc::new(Mutex::new(node_exec));
     let ne2       = ne.clone();
@@ -166,6 +170,10 @@ fn start_backend<F: FnMut()>(node_exec: NodeExecutor, mut f: F) {
             first = false;
         }
 
+        for ev in midi_in.iter(ps) {
+            hexosynth::midi_input::feed_midi(ev.bytes);
+        }
+
         let nframes = out_a_p.len();
 
         let mut node_exec = ne.lock().unwrap();
@@ -211,6 +219,8 @@ fn start_backend<F: FnMut()>(node_exec: NodeExecutor, mut f: F) {
                 node_exec.process(&mut context);
             }
 
+            hexosynth::audio_tap::feed(context.input, &*context.output);
+
             offs += cur_nframes;
         }
 
@@ -235,6 +245,10 @@ fn main() {
     let (matrix, node_exec) = init_hexosynth();
     let matrix = Arc::new(Mutex::new(matrix));
 
+    // Plays the pattern chains of the TSeq nodes, also without the GUI:
+    let _song_sequencer =
+        hexosynth::pattern_bank::SongSequencer::start(matrix.clone());
+
     start_backend(node_exec, move || {
         open_hexosynth(None, matrix.clone());
     });
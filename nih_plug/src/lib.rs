@@ -12,6 +12,8 @@ pub struct HexoSynthPlug {
     matrix:     Arc<Mutex<Matrix>>,
     node_exec:  Box<NodeExecutor>,
     proc_log:   bool,
+    /// Plays the pattern chains of the TSeq nodes, also without the GUI.
+    _song_sequencer: hexosynth::pattern_bank::SongSequencer,
 }
 
 #[derive(Params)]
@@ -47,9 +49,14 @@ impl Default for HexoSynthPlug {
 
         log(|w| write!(w, "INIT").unwrap());
 
+        let matrix = Arc::new(Mutex::new(matrix));
+        let song_sequencer =
+            hexosynth::pattern_bank::SongSequencer::start(matrix.clone());
+
         Self {
-            matrix:    Arc::new(Mutex::new(matrix)),
+            matrix,
             node_exec: Box::new(node_exec),
+            _song_sequencer: song_sequencer,
 
             params: Arc::new(HexoSynthPlugParams::default()),
             proc_log: false,
@@ -99,6 +106,8 @@ impl Plugin for HexoSynthPlug {
     const DEFAULT_NUM_INPUTS: u32 = 2;
     const DEFAULT_NUM_OUTPUTS: u32 = 2;
 
+    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
 
     fn params(&self) -> Arc<dyn Params> {
@@ -131,6 +140,7 @@ impl Plugin for HexoSynthPlug {
         hexodsp::log::init_thread_logger("proc_init");
         log(|w| write!(w, "PROC INIT").unwrap());
         self.node_exec.set_sample_rate(buffer_config.sample_rate);
+        hexosynth::audio_tap::set_sample_rate(buffer_config.sample_rate);
         true
     }
 
@@ -138,11 +148,17 @@ impl Plugin for HexoSynthPlug {
         &mut self,
         buffer: &mut Buffer,
         _aux: &mut AuxiliaryBuffers,
-        _context: &mut impl ProcessContext,
+        context: &mut impl ProcessContext,
     ) -> ProcessStatus {
         use std::io::Write;
         use hexodsp::log::log;
 
+        while let Some(event) = context.next_event() {
+            if let NoteEvent::NoteOn { note, .. } = event {
+                hexosynth::midi_input::feed_note_on(note);
+            }
+        }
+
         if !self.proc_log {
 //            hexodsp::log::init_thread_logger("proc");
             self.proc_log = true;
@@ -209,6 +225,7 @@ impl Plugin for HexoSynthPlug {
             context.output[1].fill(0.0);
 
             self.node_exec.process(&mut context);
+            hexosynth::audio_tap::feed(context.input, &*context.output);
 
 //            if oversample_simulation {
 //                node_exec.process(&mut context);
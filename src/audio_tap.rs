@@ -0,0 +1,191 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+//! Taps the audio rate signals of the audio device channels.
+//!
+//! The audio thread calls [feed] with the input and output buffers of
+//! every processed block. Every [AudioTap] owns a lock free ring buffer,
+//! which receives the interleaved samples of its sources. The owner of
+//! the tap pops them with [AudioTap::pop], so the audio thread never waits
+//! for the UI or the disk.
+
+use ringbuf::{RingBuffer, Producer, Consumer};
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+static SAMPLE_RATE : AtomicU32   = AtomicU32::new(44100);
+static NEXT_TAP_ID : AtomicUsize = AtomicUsize::new(1);
+/// Frames of the blocks, that the audio thread could not feed to the taps,
+/// because [TAPS] was locked. These blocks are missing in every tap.
+static SKIPPED_FRAMES : AtomicUsize = AtomicUsize::new(0);
+
+/// The audio thread only uses `try_lock` on this, it is only locked
+/// by the other threads when a tap is created or dropped.
+static TAPS : Mutex<Vec<TapFeed>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TapSource {
+    /// An output channel of the audio device, these carry the signals
+    /// of the `Out` node. 0 is the left and 1 the right channel.
+    Output(usize),
+    /// An input channel of the audio device.
+    Input(usize),
+}
+
+impl TapSource {
+    pub fn from_str(kind: &str, channel: usize) -> Option<Self> {
+        match kind {
+            "out" => Some(TapSource::Output(channel)),
+            "in"  => Some(TapSource::Input(channel)),
+            _     => None,
+        }
+    }
+
+    pub fn kind_str(&self) -> &'static str {
+        match self {
+            TapSource::Output(_) => "out",
+            TapSource::Input(_)  => "in",
+        }
+    }
+
+    pub fn channel(&self) -> usize {
+        match self {
+            TapSource::Output(ch) => *ch,
+            TapSource::Input(ch)  => *ch,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            TapSource::Output(0)  => "Out L".to_string(),
+            TapSource::Output(1)  => "Out R".to_string(),
+            TapSource::Output(ch) => format!("Out {}", ch + 1),
+            TapSource::Input(0)   => "In L".to_string(),
+            TapSource::Input(1)   => "In R".to_string(),
+            TapSource::Input(ch)  => format!("In {}", ch + 1),
+        }
+    }
+}
+
+/// The audio thread side of an [AudioTap].
+struct TapFeed {
+    id:      usize,
+    sources: Vec<TapSource>,
+    prod:    Producer<f32>,
+    dropped: Arc<AtomicUsize>,
+}
+
+/// Receives the interleaved samples of one or more [TapSource]
+/// from the audio thread. The tap stops receiving samples when
+/// it is dropped.
+pub struct AudioTap {
+    id:      usize,
+    sources: Vec<TapSource>,
+    cons:    Consumer<f32>,
+    dropped: Arc<AtomicUsize>,
+    /// [SKIPPED_FRAMES] when the tap was created.
+    skipped_base: usize,
+}
+
+impl AudioTap {
+    /// Creates a tap with a ring buffer for `frames` frames of `sources`.
+    pub fn new(sources: Vec<TapSource>, frames: usize) -> Self {
+        let rb = RingBuffer::<f32>::new((frames * sources.len()).max(1));
+        let (prod, cons) = rb.split();
+
+        let id      = NEXT_TAP_ID.fetch_add(1, Ordering::Relaxed);
+        let dropped = Arc::new(AtomicUsize::new(0));
+
+        let mut skipped_base = SKIPPED_FRAMES.load(Ordering::Relaxed);
+
+        if let Ok(mut taps) = TAPS.lock() {
+            taps.push(TapFeed {
+                id,
+                sources: sources.clone(),
+                prod,
+                dropped: dropped.clone(),
+            });
+
+            // Blocks skipped from now on are missing in this tap:
+            skipped_base = SKIPPED_FRAMES.load(Ordering::Relaxed);
+        }
+
+        Self { id, sources, cons, dropped, skipped_base }
+    }
+
+    pub fn sources(&self) -> &[TapSource] { &self.sources[..] }
+
+    /// Pops the interleaved samples into `buf`.
+    /// Returns the number of samples that were written to `buf`.
+    pub fn pop(&mut self, buf: &mut [f32]) -> usize {
+        self.cons.pop_slice(buf)
+    }
+
+    /// Returns the number of samples that were dropped, because the
+    /// ring buffer was full or the audio thread could not feed the taps.
+    pub fn dropped_samples(&self) -> usize {
+        let skipped =
+            SKIPPED_FRAMES.load(Ordering::Relaxed)
+                .wrapping_sub(self.skipped_base);
+
+        self.dropped.load(Ordering::Relaxed)
+        + skipped * self.sources.len()
+    }
+}
+
+impl Drop for AudioTap {
+    fn drop(&mut self) {
+        if let Ok(mut taps) = TAPS.lock() {
+            taps.retain(|tap| tap.id != self.id);
+        }
+    }
+}
+
+/// Called by the audio backends with the sample rate of the audio device.
+pub fn set_sample_rate(sample_rate: f32) {
+    SAMPLE_RATE.store(sample_rate as u32, Ordering::Relaxed);
+}
+
+/// Returns the sample rate of the audio device.
+pub fn sample_rate() -> u32 {
+    SAMPLE_RATE.load(Ordering::Relaxed)
+}
+
+/// Called by the audio thread with the input and output buffers after
+/// `NodeExecutor::process`. Does nothing if there are no taps.
+pub fn feed(input: &[&[f32]], output: &[&mut [f32]]) {
+    let nframes = output.get(0).map(|buf| buf.len()).unwrap_or(0);
+
+    // Never block the audio thread, if a tap is created or dropped
+    // right now, this block is not tapped and counted as dropped:
+    let mut taps =
+        if let Ok(taps) = TAPS.try_lock() { taps }
+        else {
+            SKIPPED_FRAMES.fetch_add(nframes, Ordering::Relaxed);
+            return;
+        };
+
+    for tap in taps.iter_mut() {
+        // Blocks are dropped as a whole, to keep the channels of
+        // the interleaved samples aligned:
+        let len = nframes * tap.sources.len();
+        if tap.prod.remaining() < len {
+            tap.dropped.fetch_add(len, Ordering::Relaxed);
+            continue;
+        }
+
+        for i in 0..nframes {
+            for src in tap.sources.iter() {
+                let sample =
+                    match src {
+                        TapSource::Output(ch) => output.get(*ch).and_then(|buf| buf.get(i)),
+                        TapSource::Input(ch)  => input.get(*ch).and_then(|buf| buf.get(i)),
+                    };
+
+                let _ = tap.prod.push(sample.copied().unwrap_or(0.0));
+            }
+        }
+    }
+}
@@ -0,0 +1,322 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use hexodsp::{Matrix, Cell, CellDir, NodeId};
+
+/// The edges a signal can leave a cell through. A route can only
+/// ever walk along these directions, because every step needs an
+/// output edge on one side and an input edge on the other.
+const OUTPUT_DIRS : [CellDir; 3] = [CellDir::TR, CellDir::BR, CellDir::B];
+
+/// The instance of a [NodeId] is stored in an `u8`, so there can't be
+/// more instances of a node type.
+pub const MAX_NODE_INSTANCES : usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RouteMode {
+    /// Only route through a chain of pass through (`Amp`) nodes.
+    Pass,
+    /// Only route by a `FbWr`/`FbRd` pair.
+    Feedback,
+    /// Try [RouteMode::Pass] first and fall back to [RouteMode::Feedback]
+    /// if no free path was found.
+    Auto,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RouteError {
+    EmptyCell((usize, usize)),
+    UnknownOutput(String),
+    UnknownInput(String),
+    InputAlreadyUsed(String),
+    /// All instances of the node type, that the route needs, are used.
+    TooManyInstances(String),
+    NoRoute,
+}
+
+fn pos_is_free(m: &Matrix, pos: (usize, usize)) -> bool {
+    let (w, h) = m.size();
+    if pos.0 >= w || pos.1 >= h {
+        return false;
+    }
+
+    m.get(pos.0, pos.1).map(|c| c.is_empty()).unwrap_or(false)
+}
+
+fn used_node_ids(m: &Matrix) -> HashSet<NodeId> {
+    let mut used = HashSet::new();
+    m.for_each(|_x, _y, cell| {
+        if !cell.is_empty() {
+            used.insert(cell.node_id());
+        }
+    });
+    used
+}
+
+/// Returns the first instance of `node_id` from instance `from` on,
+/// that is not used in the matrix.
+fn unused_instance(used: &HashSet<NodeId>, node_id: NodeId, from: usize)
+    -> Result<NodeId, RouteError>
+{
+    (from..MAX_NODE_INSTANCES)
+        .map(|inst| node_id.to_instance(inst))
+        .find(|nid| !used.contains(nid))
+        .ok_or_else(|| RouteError::TooManyInstances(node_id.name().to_string()))
+}
+
+/// An output edge of the source cell can be used if it's not assigned
+/// yet, or already carries the output we want to route.
+fn src_edge_usable(src: &Cell, dir: CellDir, out_idx: u8) -> bool {
+    match src.local_port_idx(dir) {
+        None    => true,
+        Some(i) => i == out_idx,
+    }
+}
+
+/// Finds a way to connect an output of one node with an input of
+/// a distant node by placing intermediate nodes on free cells.
+///
+/// Nothing is placed into the matrix by [AutoRoute::route], it only
+/// returns the cells (including the modified source and destination cells).
+/// That way the caller can wrap the change into a snapshot/check/restore
+/// cycle.
+pub struct AutoRoute {
+    src:        Cell,
+    dst:        Cell,
+    out_idx:    u8,
+    in_idx:     u8,
+}
+
+impl AutoRoute {
+    /// Sets up a route from output `out_name` of the node at `src` to the
+    /// input `in_name` of the node at `dst`. If `out_name` is `None` the
+    /// first output is used, if `in_name` is `None` the first unused input
+    /// is used.
+    pub fn new(
+        m: &Matrix,
+        src: (usize, usize), out_name: Option<&str>,
+        dst: (usize, usize), in_name: Option<&str>
+    ) -> Result<Self, RouteError>
+    {
+        let src_cell = m.get_copy(src.0, src.1).filter(|c| !c.is_empty());
+        let src_cell = src_cell.ok_or(RouteError::EmptyCell(src))?;
+        let dst_cell = m.get_copy(dst.0, dst.1).filter(|c| !c.is_empty());
+        let dst_cell = dst_cell.ok_or(RouteError::EmptyCell(dst))?;
+
+        let src_id = src_cell.node_id();
+        let dst_id = dst_cell.node_id();
+
+        let out_idx =
+            if let Some(name) = out_name {
+                src_id.out(name)
+                    .ok_or_else(|| RouteError::UnknownOutput(name.to_string()))?
+            } else {
+                src_id.out_name_by_idx(0)
+                    .map(|_| 0)
+                    .ok_or_else(|| RouteError::UnknownOutput("".to_string()))?
+            };
+
+        let in_idx =
+            if let Some(name) = in_name {
+                let param =
+                    dst_id.inp_param(name)
+                        .ok_or_else(|| RouteError::UnknownInput(name.to_string()))?;
+
+                if m.param_input_is_used(param) {
+                    return Err(RouteError::InputAlreadyUsed(name.to_string()));
+                }
+
+                param.inp()
+            } else {
+                let mut i = 0;
+                loop {
+                    let param =
+                        dst_id.inp_param_by_idx(i)
+                            .ok_or_else(|| RouteError::UnknownInput("".to_string()))?;
+
+                    if !m.param_input_is_used(param) {
+                        break param.inp();
+                    }
+
+                    i += 1;
+                }
+            };
+
+        Ok(Self {
+            src: src_cell,
+            dst: dst_cell,
+            out_idx,
+            in_idx,
+        })
+    }
+
+    pub fn route(&self, m: &Matrix, mode: RouteMode) -> Result<Vec<Cell>, RouteError> {
+        match mode {
+            RouteMode::Pass     => self.route_pass(m),
+            RouteMode::Feedback => self.route_feedback(m),
+            RouteMode::Auto     => {
+                match self.route_pass(m) {
+                    Err(RouteError::NoRoute) => self.route_feedback(m),
+                    res                      => res,
+                }
+            },
+        }
+    }
+
+    /// Returns the output edge of a cell at `pos`, that leads into a
+    /// free input edge of the destination cell.
+    fn dir_into_dst(&self, pos: (usize, usize)) -> Option<CellDir> {
+        for dir in OUTPUT_DIRS {
+            if dir.offs_pos(pos) == Some(self.dst.pos())
+               && self.dst.local_port_idx(dir.flip()).is_none()
+            {
+                return Some(dir);
+            }
+        }
+
+        None
+    }
+
+    /// Breadth first search over free cells, walking only along
+    /// output directions. Each visited cell becomes an `Amp` node with
+    /// it's input on the side it was entered from.
+    fn route_pass(&self, m: &Matrix) -> Result<Vec<Cell>, RouteError> {
+        let src_pos = self.src.pos();
+
+        let mut src = self.src;
+        let mut dst = self.dst;
+
+        if let Some(dir) = CellDir::are_adjacent(src_pos, dst.pos()) {
+            if dir.is_output()
+               && src_edge_usable(&src, dir, self.out_idx)
+               && dst.local_port_idx(dir.flip()).is_none()
+            {
+                src.set_io_dir(dir, self.out_idx as usize);
+                dst.set_io_dir(dir.flip(), self.in_idx as usize);
+                return Ok(vec![src, dst]);
+            }
+        }
+
+        let mut came_from : HashMap<(usize, usize), ((usize, usize), CellDir)> =
+            HashMap::new();
+        let mut queue = VecDeque::new();
+
+        for dir in OUTPUT_DIRS {
+            if !src_edge_usable(&src, dir, self.out_idx) {
+                continue;
+            }
+
+            if let Some(pos) = dir.offs_pos(src_pos) {
+                if pos_is_free(m, pos) && !came_from.contains_key(&pos) {
+                    came_from.insert(pos, (src_pos, dir));
+                    queue.push_back(pos);
+                }
+            }
+        }
+
+        let mut end = None;
+
+        while let Some(pos) = queue.pop_front() {
+            if let Some(dir) = self.dir_into_dst(pos) {
+                end = Some((pos, dir));
+                break;
+            }
+
+            for dir in OUTPUT_DIRS {
+                if let Some(next) = dir.offs_pos(pos) {
+                    if pos_is_free(m, next) && !came_from.contains_key(&next) {
+                        came_from.insert(next, (pos, dir));
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+
+        let (mut pos, mut out_dir) = end.ok_or(RouteError::NoRoute)?;
+
+        dst.set_io_dir(out_dir.flip(), self.in_idx as usize);
+
+        let mut path = vec![];
+        while pos != src_pos {
+            let (prev, in_dir) = came_from[&pos];
+            path.push((pos, in_dir.flip(), out_dir));
+            out_dir = in_dir;
+            pos     = prev;
+        }
+        path.reverse();
+
+        src.set_io_dir(out_dir, self.out_idx as usize);
+
+        let used    = used_node_ids(m);
+        let amp_out = NodeId::Amp(0).out("sig").unwrap_or(0) as usize;
+        let amp_in  = NodeId::Amp(0).inp("inp").unwrap_or(0) as usize;
+
+        let mut cells     = vec![src];
+        let mut next_inst = 0;
+
+        for (pos, in_dir, out_dir) in path {
+            let nid = unused_instance(&used, NodeId::Amp(0), next_inst)?;
+            next_inst = nid.instance() + 1;
+
+            let mut cell = Cell::empty_at(nid, pos.0 as u8, pos.1 as u8);
+            cell.set_io_dir(in_dir,  amp_in);
+            cell.set_io_dir(out_dir, amp_out);
+            cells.push(cell);
+        }
+
+        cells.push(dst);
+
+        Ok(cells)
+    }
+
+    /// Places a `FbWr` next to the source and a `FbRd` with the same
+    /// instance next to the destination. This does not need a free path,
+    /// but introduces the feedback delay of the `FbWr`/`FbRd` pair.
+    fn route_feedback(&self, m: &Matrix) -> Result<Vec<Cell>, RouteError> {
+        let mut src = self.src;
+        let mut dst = self.dst;
+
+        let wr =
+            OUTPUT_DIRS.iter()
+                .filter(|dir| src_edge_usable(&src, **dir, self.out_idx))
+                .filter_map(|dir| dir.offs_pos(src.pos()).map(|p| (p, *dir)))
+                .find(|(p, _)| pos_is_free(m, *p));
+
+        let rd =
+            OUTPUT_DIRS.iter()
+                .filter(|dir| dst.local_port_idx(dir.flip()).is_none())
+                .filter_map(|dir| dir.flip().offs_pos(dst.pos()).map(|p| (p, *dir)))
+                .find(|(p, _)| pos_is_free(m, *p) && Some(*p) != wr.map(|w| w.0));
+
+        let ((wr_pos, wr_dir), (rd_pos, rd_dir)) =
+            match (wr, rd) {
+                (Some(wr), Some(rd)) => (wr, rd),
+                _ => return Err(RouteError::NoRoute),
+            };
+
+        let used = used_node_ids(m);
+        let inst =
+            (0..MAX_NODE_INSTANCES)
+                .find(|inst|
+                    !used.contains(&NodeId::FbWr(0).to_instance(*inst))
+                    && !used.contains(&NodeId::FbRd(0).to_instance(*inst)))
+                .ok_or_else(|| RouteError::TooManyInstances("fbwr".to_string()))?;
+
+        let wr_id = NodeId::FbWr(0).to_instance(inst);
+        let rd_id = NodeId::FbRd(0).to_instance(inst);
+
+        src.set_io_dir(wr_dir, self.out_idx as usize);
+
+        let mut wr_cell = Cell::empty_at(wr_id, wr_pos.0 as u8, wr_pos.1 as u8);
+        wr_cell.set_io_dir(wr_dir.flip(), wr_id.inp("inp").unwrap_or(0) as usize);
+
+        let mut rd_cell = Cell::empty_at(rd_id, rd_pos.0 as u8, rd_pos.1 as u8);
+        rd_cell.set_io_dir(rd_dir, rd_id.out("sig").unwrap_or(0) as usize);
+
+        dst.set_io_dir(rd_dir.flip(), self.in_idx as usize);
+
+        Ok(vec![src, wr_cell, rd_cell, dst])
+    }
+}
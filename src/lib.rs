@@ -8,6 +8,20 @@ use hexotk::{UI, open_window, Units, Rect, TestScript, HexoTKWindowHandle};
 //pub mod ui;
 //pub mod ui_ctrl;
 mod cluster;
+mod auto_route;
+mod patch_lint;
+mod mod_matrix;
+mod signal_type;
+pub mod audio_tap;
+pub mod midi_input;
+pub mod recorder;
+pub mod sample_file;
+pub mod sample_library;
+pub mod pattern_io;
+pub mod pattern_ops;
+pub mod midi_import;
+pub mod pattern_bank;
+pub mod pattern_history;
 //mod uimsg_queue;
 //mod state;
 //mod actions;
@@ -34,6 +48,9 @@ pub use hexodsp::*;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// The patch that is loaded on startup.
+pub const INIT_PATCH: &str = "init.hxy";
+
 /// Initializes the default [Matrix] setup of HexoSynth.
 ///
 /// This routine is used for example by the tests,
@@ -58,8 +75,23 @@ pub fn init_hexosynth() -> (Matrix, NodeExecutor) {
     let gain_p = NodeId::Amp(0).inp_param("gain").unwrap();
     matrix.set_param(gain_p, gain_p.norm(0.06).into());
 
-    if let Err(e) = load_patch_from_file(&mut matrix, "init.hxy") {
-        println!("Error loading init.hxy: {:?}", e);
+    match load_patch_from_file(&mut matrix, INIT_PATCH) {
+        Ok(_) => {
+            // Load the samples here, so that they are played even if
+            // the GUI is never opened:
+            let missing =
+                sample_library::resolve_samples(
+                    &mut matrix, INIT_PATCH, Some(audio_tap::sample_rate()));
+
+            for (path, reason) in missing.iter() {
+                println!("Missing sample '{}' in {}: {}", path, INIT_PATCH, reason);
+            }
+
+            pattern_bank::songs_changed(&mut matrix);
+        },
+        Err(e) => {
+            println!("Error loading {}: {:?}", INIT_PATCH, e);
+        },
     }
 
     let _ = matrix.sync();
@@ -1138,6 +1170,8 @@ pub fn open_hexosynth_with_config(
                     "wllib/tests.wl", std::fs::read_to_string(env_path.to_string() + "/wllib/tests.wl").unwrap().to_string());
                 lfmr.borrow_mut().preload(
                     "wllib/texts.wl", std::fs::read_to_string(env_path.to_string() + "/wllib/texts.wl").unwrap().to_string());
+                lfmr.borrow_mut().preload(
+                    "wllib/patch_gen.wl", std::fs::read_to_string(env_path.to_string() + "/wllib/patch_gen.wl").unwrap().to_string());
             } else {
                 lfmr.borrow_mut().preload(
                     "main.wl",          include_str!("wlcode_compiletime/main.wl").to_string());
@@ -1149,6 +1183,8 @@ pub fn open_hexosynth_with_config(
                     "wllib/tests.wl",   include_str!("wlcode_compiletime/wllib/tests.wl").to_string());
                 lfmr.borrow_mut().preload(
                     "wllib/texts.wl",   include_str!("wlcode_compiletime/wllib/texts.wl").to_string());
+                lfmr.borrow_mut().preload(
+                    "wllib/patch_gen.wl", include_str!("wlcode_compiletime/wllib/patch_gen.wl").to_string());
             }
             global_env.borrow_mut().set_resolver(lfmr);
 
@@ -1187,6 +1223,16 @@ pub fn open_hexosynth_with_config(
                     Ok(VVal::new_usr(wlapi::VVPatEditFb::new_dummy()))
                 }, Some(0), Some(0), false);
 
+            ui_st.fun(
+                "create_pattern_history", |_env: &mut Env, _argc: usize| {
+                    Ok(wlapi::VVPatHistory::new_vv())
+                }, Some(0), Some(0), false);
+
+            ui_st.fun(
+                "create_note_keys_model", |_env: &mut Env, _argc: usize| {
+                    Ok(VVal::new_usr(wlapi::VOctaveKeysModel::new_note_keys()))
+                }, Some(0), Some(0), false);
+
             ui_st.fun(
                 "create_pattern_data_unconnected", |env: &mut Env, _argc: usize| {
                     Ok(VVal::new_usr(
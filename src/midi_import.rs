@@ -0,0 +1,286 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+//! Imports a track of a Standard MIDI File into the pattern of a `TSeq`.
+//!
+//! The notes are quantized to the rows of the pattern. The note column
+//! gets the MIDI note numbers, the pitch encoding of the note columns.
+//! The gate column is held open with `GATE_HOLD` over the length of
+//! a note. Only if the next note starts right after it, the last row
+//! of the note gets `GATE_RELEASE`, so that the next note is triggered
+//! again.
+
+use hexotk::UIPatternModel;
+
+use crate::pattern_ops::{check_col, MAX_NOTE_VALUE};
+
+/// A gate cell of a `TSeq` has three hex digits, see the help of the
+/// `TSeq` node: 0xF00 is the length of the gate within the row, F keeps
+/// it open for the whole row. 0x0F0 divides the row into that many plus
+/// one gates. 0x00F is the probability of the gate, F plays it always.
+///
+/// One gate over the whole row. Consecutive rows with it are one gate,
+/// so a note sounds over all of its rows.
+const GATE_HOLD    : u16 = 0xF0F;
+/// One gate, that closes in the middle of the row. The last row of a
+/// note gets it, if the next note follows directly.
+const GATE_RELEASE : u16 = 0x80F;
+/// The longest pattern a `TSeq` can play.
+const MAX_IMPORT_ROWS : usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MidiNote {
+    pub start: u64,
+    pub end:   u64,
+    pub key:   u8,
+    pub vel:   u8,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MidiTrack {
+    pub name:  String,
+    pub notes: Vec<MidiNote>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MidiFile {
+    /// Ticks per quarter note.
+    pub division: u16,
+    pub tracks:   Vec<MidiTrack>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos:  usize,
+}
+
+impl<'a> Reader<'a> {
+    fn eof(&self) -> bool { self.pos >= self.data.len() }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.pos + n > self.data.len() {
+            return Err("Unexpected end of MIDI data".to_string());
+        }
+        let b = &self.data[self.pos..(self.pos + n)];
+        self.pos += n;
+        Ok(b)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> { Ok(self.bytes(1)?[0]) }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Reads a variable length quantity.
+    fn vlq(&mut self) -> Result<u32, String> {
+        let mut v : u32 = 0;
+        for _ in 0..4 {
+            let b = self.u8()?;
+            v = (v << 7) | (b & 0x7F) as u32;
+            if b & 0x80 == 0 { return Ok(v); }
+        }
+        Err("Bad variable length value in MIDI data".to_string())
+    }
+}
+
+fn parse_track(data: &[u8]) -> Result<MidiTrack, String> {
+    let mut rd      = Reader { data, pos: 0 };
+    let mut track   = MidiTrack::default();
+    let mut time    : u64 = 0;
+    let mut status  : u8  = 0;
+    // Start time and velocity of the sounding notes, per channel and key:
+    let mut playing : Vec<Option<(u64, u8)>> = vec![None; 16 * 128];
+
+    while !rd.eof() {
+        time += rd.vlq()? as u64;
+
+        let mut b = rd.u8()?;
+        if b & 0x80 != 0 {
+            status = b;
+            if status < 0xF0 { b = rd.u8()?; }
+        } else if status == 0 {
+            return Err("MIDI data without status byte".to_string());
+        }
+
+        match status {
+            0xFF => {
+                let typ = rd.u8()?;
+                let len = rd.vlq()? as usize;
+                let payload = rd.bytes(len)?;
+
+                if typ == 0x03 {
+                    track.name = String::from_utf8_lossy(payload).to_string();
+                }
+                if typ == 0x2F { break; }
+                // Running status is canceled by meta and sysex events:
+                status = 0;
+            },
+            0xF0 | 0xF7 => {
+                let len = rd.vlq()? as usize;
+                rd.bytes(len)?;
+                status = 0;
+            },
+            _ => {
+                let channel = (status & 0x0F) as usize;
+                let data1   = b & 0x7F;
+
+                match status & 0xF0 {
+                    0x80 | 0x90 => {
+                        let vel  = rd.u8()? & 0x7F;
+                        let slot = channel * 128 + data1 as usize;
+
+                        if let Some((start, on_vel)) = playing[slot].take() {
+                            track.notes.push(MidiNote {
+                                start, end: time, key: data1, vel: on_vel
+                            });
+                        }
+
+                        // A note on with velocity 0 is a note off:
+                        if status & 0xF0 == 0x90 && vel > 0 {
+                            playing[slot] = Some((time, vel));
+                        }
+                    },
+                    0xA0 | 0xB0 | 0xE0 => { rd.u8()?; },
+                    // Program change and channel pressure have one data byte.
+                    _ => { },
+                }
+            },
+        }
+    }
+
+    // Notes without note off end with the track:
+    for (slot, p) in playing.iter().enumerate() {
+        if let Some((start, vel)) = p {
+            track.notes.push(MidiNote {
+                start: *start, end: time, key: (slot % 128) as u8, vel: *vel
+            });
+        }
+    }
+
+    track.notes.sort_by(|a, b| a.start.cmp(&b.start).then(b.key.cmp(&a.key)));
+
+    Ok(track)
+}
+
+pub fn parse_midi(data: &[u8]) -> Result<MidiFile, String> {
+    let mut rd = Reader { data, pos: 0 };
+
+    if rd.bytes(4)? != b"MThd" {
+        return Err("Not a Standard MIDI File".to_string());
+    }
+    let hdr_len  = rd.u32()? as usize;
+    let _format  = rd.u16()?;
+    let ntracks  = rd.u16()?;
+    let division = rd.u16()?;
+    rd.bytes(hdr_len.saturating_sub(6))?;
+
+    if division & 0x8000 != 0 {
+        return Err("SMPTE time division in MIDI files is not supported".to_string());
+    }
+
+    let mut tracks = vec![];
+
+    while !rd.eof() && tracks.len() < ntracks as usize {
+        let id  = rd.bytes(4)?;
+        let len = rd.u32()? as usize;
+        let chunk = rd.bytes(len)?;
+
+        // Unknown chunks have to be skipped:
+        if id == b"MTrk" {
+            tracks.push(parse_track(chunk)?);
+        }
+    }
+
+    Ok(MidiFile { division, tracks })
+}
+
+pub fn load_midi_file(path: &str) -> Result<MidiFile, String> {
+    let data =
+        std::fs::read(path).map_err(|e| format!("Can't read '{}': {}", path, e))?;
+    parse_midi(&data).map_err(|e| format!("Can't read '{}': {}", path, e))
+}
+
+/// The result of [import_track].
+#[derive(Debug, Clone, Copy)]
+pub struct MidiImportInfo {
+    pub rows:    usize,
+    pub notes:   usize,
+    /// Notes that were dropped, because another note started
+    /// on the same row. The column of a `TSeq` is monophonic.
+    pub dropped: usize,
+}
+
+/// Quantizes the notes of `track` to rows with the length of
+/// 1/`rows_per_beat` of a quarter note and writes them into the note
+/// column `note_col` and the gate column `gate_col` of the pattern.
+/// The pattern is resized to the length of the track.
+pub fn import_track(
+    pat: &mut dyn UIPatternModel, midi: &MidiFile, track: usize,
+    note_col: usize, gate_col: usize, rows_per_beat: usize)
+    -> Result<MidiImportInfo, String>
+{
+    check_col(pat, note_col)?;
+    check_col(pat, gate_col)?;
+
+    let track =
+        midi.tracks.get(track)
+            .ok_or_else(|| format!(
+                "No track {}, the MIDI file has {} tracks", track, midi.tracks.len()))?;
+
+    let ticks_per_row = (midi.division as f64 / rows_per_beat.max(1) as f64).max(1.0);
+    let tick2row      = |t: u64| (t as f64 / ticks_per_row).round() as usize;
+
+    let len =
+        track.notes.iter().map(|n| tick2row(n.end)).max().unwrap_or(0)
+            .clamp(1, MAX_IMPORT_ROWS);
+
+    pat.set_rows(len);
+    let rows = pat.rows();
+
+    pat.set_col_note_type(note_col);
+    pat.set_col_gate_type(gate_col);
+    for row in 0..rows {
+        pat.clear_cell(row, note_col);
+        pat.clear_cell(row, gate_col);
+    }
+
+    let mut info      = MidiImportInfo { rows, notes: 0, dropped: 0 };
+    let mut next_free = 0;
+    let mut has_prev  = false;
+
+    for note in track.notes.iter() {
+        let start = tick2row(note.start);
+        // Every note is at least one row long:
+        let end   = tick2row(note.end).max(start + 1).min(rows);
+
+        if start >= rows || start < next_free {
+            info.dropped += 1;
+            continue;
+        }
+
+        // Close the gate of the previous note early, if it would
+        // otherwise join this note into one gate:
+        if has_prev && start == next_free {
+            pat.set_cell_value(start - 1, gate_col, GATE_RELEASE);
+        }
+
+        pat.set_cell_value(start, note_col, (note.key as u16).min(MAX_NOTE_VALUE));
+        for row in start..end {
+            pat.set_cell_value(row, gate_col, GATE_HOLD);
+        }
+
+        next_free   = end;
+        has_prev    = true;
+        info.notes += 1;
+    }
+
+    Ok(info)
+}
@@ -0,0 +1,87 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+//! Passes the note-on events of the MIDI input to the UI.
+//!
+//! The audio backends call [feed_midi] or [feed_note_on] from the audio
+//! thread. Every [MidiNoteTap] owns a lock free ring buffer, which receives
+//! the notes, the owner pops them with [MidiNoteTap::pop]. This is built
+//! like the [crate::audio_tap].
+
+use ringbuf::{RingBuffer, Producer, Consumer};
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The number of notes a tap can hold until they are popped.
+const NOTE_BUFFER_LEN : usize = 256;
+
+static NEXT_TAP_ID : AtomicUsize = AtomicUsize::new(1);
+
+/// The audio thread only uses `try_lock` on this, it is only locked
+/// by the other threads when a tap is created or dropped.
+static NOTE_TAPS : Mutex<Vec<NoteFeed>> = Mutex::new(Vec::new());
+
+/// The audio thread side of a [MidiNoteTap].
+struct NoteFeed {
+    id:   usize,
+    prod: Producer<u8>,
+}
+
+/// Receives the MIDI note numbers of the note-on events.
+/// The tap stops receiving notes when it is dropped.
+pub struct MidiNoteTap {
+    id:   usize,
+    cons: Consumer<u8>,
+}
+
+impl MidiNoteTap {
+    pub fn new() -> Self {
+        let (prod, cons) = RingBuffer::<u8>::new(NOTE_BUFFER_LEN).split();
+        let id = NEXT_TAP_ID.fetch_add(1, Ordering::Relaxed);
+
+        if let Ok(mut taps) = NOTE_TAPS.lock() {
+            taps.push(NoteFeed { id, prod });
+        }
+
+        Self { id, cons }
+    }
+
+    /// Returns the next received note, in the order they were played.
+    pub fn pop(&mut self) -> Option<u8> {
+        self.cons.pop()
+    }
+}
+
+impl Drop for MidiNoteTap {
+    fn drop(&mut self) {
+        if let Ok(mut taps) = NOTE_TAPS.lock() {
+            taps.retain(|tap| tap.id != self.id);
+        }
+    }
+}
+
+/// Called by the audio thread with the note of a note-on event.
+/// Notes are dropped if a tap is full or if a tap is created or
+/// dropped right now, the audio thread never waits.
+pub fn feed_note_on(note: u8) {
+    let mut taps =
+        if let Ok(taps) = NOTE_TAPS.try_lock() { taps }
+        else { return; };
+
+    for tap in taps.iter_mut() {
+        let _ = tap.prod.push(note & 0x7F);
+    }
+}
+
+/// Called by the audio thread with the bytes of a raw MIDI message.
+/// Only note-on messages with a velocity above 0 are passed on,
+/// a velocity of 0 is a note-off.
+pub fn feed_midi(bytes: &[u8]) {
+    if let [status, note, velocity, ..] = bytes {
+        if status & 0xF0 == 0x90 && *velocity > 0 {
+            feed_note_on(*note);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+//! Collects the modulated parameters of all nodes in the matrix,
+//! for the overview in the modulation matrix panel.
+
+use hexodsp::{Matrix, NodeId, ParamId, CellDir};
+
+use std::collections::HashMap;
+
+const INPUT_DIRS : [CellDir; 3] = [CellDir::T, CellDir::TL, CellDir::BL];
+
+#[derive(Debug, Clone, Copy)]
+pub struct ModEntry {
+    pub param_id: ParamId,
+    pub amount:   f32,
+    /// The node and output index, that feed the input of the parameter.
+    /// `None` if the input is not connected.
+    pub source:   Option<(NodeId, u8)>,
+}
+
+/// Returns all parameters with a modulation amount, sorted by node.
+pub fn mod_entries(m: &Matrix) -> Vec<ModEntry> {
+    let mut cells = vec![];
+    m.for_each(|_x, _y, cell| {
+        if !cell.is_empty() { cells.push(*cell); }
+    });
+    cells.sort_by(|a, b|
+        a.node_id().name().cmp(b.node_id().name())
+            .then(a.node_id().instance().cmp(&b.node_id().instance())));
+
+    let mut entries = vec![];
+    let mut visited = vec![];
+
+    for cell in cells.iter() {
+        let node_id = cell.node_id();
+        if visited.contains(&node_id) { continue; }
+        visited.push(node_id);
+
+        // The sources of the inputs, from all cells of the node:
+        let mut sources : HashMap<u8, (NodeId, u8)> = HashMap::new();
+        for node_cell in cells.iter().filter(|c| c.node_id() == node_id) {
+            for dir in INPUT_DIRS {
+                let in_idx = match node_cell.local_port_idx(dir) { Some(i) => i, None => continue };
+                let pos    = match dir.offs_pos(node_cell.pos()) { Some(p) => p, None => continue };
+
+                if let Some(src) = m.get_copy(pos.0, pos.1) {
+                    if let Some(out_idx) = src.local_port_idx(dir.flip()) {
+                        sources.insert(in_idx, (src.node_id(), out_idx));
+                    }
+                }
+            }
+        }
+
+        let mut idx = 0;
+        while let Some(param_id) = node_id.param_by_idx(idx) {
+            idx += 1;
+
+            if let Some(amount) = m.get_param_modamt(&param_id) {
+                entries.push(ModEntry {
+                    param_id,
+                    amount,
+                    source: sources.get(&param_id.inp()).copied(),
+                });
+            }
+        }
+    }
+
+    entries
+}
@@ -0,0 +1,283 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use std::collections::{HashMap, HashSet};
+use hexodsp::{Matrix, Cell, CellDir, NodeId};
+use hexodsp::matrix::MatrixError;
+use crate::signal_type::edge_signal;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LintKind {
+    /// The matrix does not pass [Matrix::check].
+    MatrixError,
+    /// The node has outputs, but none of them and none of it's
+    /// inputs are connected.
+    UnusedNode,
+    /// The node has outputs, but none of them is connected.
+    NoOutputConnected,
+    /// An input port is assigned to an edge, but nothing feeds it.
+    DanglingInput,
+    /// The knob of a parameter is disabled, because it's input counts
+    /// as used, but no cell of the node gets a signal on that input.
+    KnobDisabled,
+    /// There is no `Out` node, the patch can't be heard.
+    MissingOut,
+    /// The same node instance is placed in more than one cell.
+    DuplicateInstance,
+    /// A `FbWr` has no `FbRd` with the same instance.
+    FbWrWithoutFbRd,
+    /// A `FbRd` has no `FbWr` with the same instance.
+    FbRdWithoutFbWr,
+    /// An output drives an input of an unsuitable signal type,
+    /// see also [crate::signal_type::SignalType::can_drive].
+    SignalMismatch,
+}
+
+impl LintKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LintKind::MatrixError         => "matrix-error",
+            LintKind::UnusedNode          => "unused-node",
+            LintKind::NoOutputConnected   => "no-output-connected",
+            LintKind::DanglingInput       => "dangling-input",
+            LintKind::KnobDisabled        => "knob-disabled",
+            LintKind::MissingOut          => "missing-out",
+            LintKind::DuplicateInstance   => "duplicate-instance",
+            LintKind::FbWrWithoutFbRd     => "fbwr-without-fbrd",
+            LintKind::FbRdWithoutFbWr     => "fbrd-without-fbwr",
+            LintKind::SignalMismatch      => "signal-mismatch",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LintDiagnostic {
+    pub kind:       LintKind,
+    pub pos:        Option<(usize, usize)>,
+    pub node_id:    Option<NodeId>,
+    pub msg:        String,
+}
+
+impl LintDiagnostic {
+    fn at_cell(kind: LintKind, cell: &Cell, msg: String) -> Self {
+        Self {
+            kind,
+            pos:     Some(cell.pos()),
+            node_id: Some(cell.node_id()),
+            msg,
+        }
+    }
+}
+
+/// The connections of all cells of one node.
+#[derive(Debug, Clone, Default)]
+struct NodeConnections {
+    /// The inputs, that get a signal through any cell of the node.
+    fed:                HashSet<u8>,
+    output_connected:   bool,
+}
+
+const INPUT_DIRS  : [CellDir; 3] = [CellDir::T,  CellDir::TL, CellDir::BL];
+const OUTPUT_DIRS : [CellDir; 3] = [CellDir::TR, CellDir::BR, CellDir::B];
+
+fn matrix_error2msg(err: &MatrixError) -> String {
+    match err {
+        MatrixError::CycleDetected =>
+            "The matrix contains a cycle, use FbWr/FbRd for feedback.".to_string(),
+        MatrixError::PosOutOfRange =>
+            "A cell position is out of range.".to_string(),
+        MatrixError::NonEmptyCell { cell } =>
+            format!("Cell at {:?} is not empty.", cell.pos()),
+        MatrixError::DuplicatedInput { output1, output2 } =>
+            format!("Input fed by two outputs: {}:{} and {}:{}",
+                output1.0, output1.1, output2.0, output2.1),
+    }
+}
+
+/// Returns the outputs that drive an input of an unsuitable signal type.
+/// This is much cheaper than a full [lint_matrix] and done after
+/// every change of the matrix.
+pub fn signal_mismatches(m: &Matrix) -> Vec<LintDiagnostic> {
+    let mut diags = vec![];
+
+    m.for_each(|_x, _y, cell| {
+        if cell.is_empty() { return; }
+
+        for dir in OUTPUT_DIRS {
+            if let Some(sig) = edge_signal(m, cell, dir) {
+                if sig.is_miswired() {
+                    diags.push(LintDiagnostic::at_cell(
+                        LintKind::SignalMismatch, cell,
+                        format!("{} drives a {} input with a {} signal.",
+                            cell.node_id(), sig.input.as_str(), sig.output.as_str())));
+                }
+            }
+        }
+    });
+
+    diags
+}
+
+/// Analyzes the matrix for common patching mistakes and returns
+/// all found diagnostics. In contrast to [Matrix::check] this does
+/// not stop at the first problem.
+pub fn lint_matrix(m: &mut Matrix) -> Vec<LintDiagnostic> {
+    let mut diags = vec![];
+
+    if let Err(e) = m.check() {
+        diags.push(LintDiagnostic {
+            kind:    LintKind::MatrixError,
+            pos:     None,
+            node_id: None,
+            msg:     matrix_error2msg(&e),
+        });
+    }
+
+    let mut cells = vec![];
+    m.for_each(|_x, _y, cell| {
+        if !cell.is_empty() {
+            cells.push(*cell);
+        }
+    });
+
+    let mut instances : HashMap<NodeId, usize> = HashMap::new();
+    for cell in cells.iter() {
+        *instances.entry(cell.node_id()).or_insert(0) += 1;
+    }
+
+    let used : HashSet<NodeId> = instances.keys().copied().collect();
+
+    if !used.iter().any(|nid| nid.eq_variant(&NodeId::Out(0))) {
+        diags.push(LintDiagnostic {
+            kind:    LintKind::MissingOut,
+            pos:     None,
+            node_id: None,
+            msg:     "There is no Out node, the patch is silent.".to_string(),
+        });
+    }
+
+    for cell in cells.iter() {
+        let node_id = cell.node_id();
+
+        if instances.get(&node_id).copied().unwrap_or(0) > 1 {
+            diags.push(LintDiagnostic::at_cell(
+                LintKind::DuplicateInstance, cell,
+                format!("{} is placed more than once.", node_id)));
+        }
+
+        if node_id.eq_variant(&NodeId::FbWr(0))
+           && !used.contains(&NodeId::FbRd(0).to_instance(node_id.instance()))
+        {
+            diags.push(LintDiagnostic::at_cell(
+                LintKind::FbWrWithoutFbRd, cell,
+                format!("{} has no FbRd {} reading it.",
+                    node_id, node_id.instance())));
+        }
+
+        if node_id.eq_variant(&NodeId::FbRd(0))
+           && !used.contains(&NodeId::FbWr(0).to_instance(node_id.instance()))
+        {
+            diags.push(LintDiagnostic::at_cell(
+                LintKind::FbRdWithoutFbWr, cell,
+                format!("{} has no FbWr {} to read from.",
+                    node_id, node_id.instance())));
+        }
+    }
+
+    diags.extend(signal_mismatches(m));
+
+    // A node can be fed through any of it's cells and feed others
+    // from any of it's cells, so the connections are collected per node:
+    let mut conns : HashMap<NodeId, NodeConnections> = HashMap::new();
+    for cell in cells.iter() {
+        let node_conns = conns.entry(cell.node_id()).or_default();
+
+        for dir in INPUT_DIRS {
+            if let Some(idx) = cell.local_port_idx(dir) {
+                if cell.is_port_dir_connected(m, dir).is_some() {
+                    node_conns.fed.insert(idx);
+                }
+            }
+        }
+
+        if OUTPUT_DIRS.iter().any(|dir| cell.is_port_dir_connected(m, *dir).is_some()) {
+            node_conns.output_connected = true;
+        }
+    }
+
+    let mut checked = HashSet::new();
+    for cell in cells.iter() {
+        let node_id    = cell.node_id();
+        let node_conns = &conns[&node_id];
+
+        // Unfed inputs are reported at the cell, that carries them.
+        // If the input disables the knob, that is the more useful hint:
+        for dir in INPUT_DIRS {
+            let idx =
+                match cell.local_port_idx(dir) {
+                    Some(idx) if !node_conns.fed.contains(&idx) => idx,
+                    _ => continue,
+                };
+
+            let param = node_id.inp_param_by_idx(idx as usize);
+            let name =
+                param.map(|p| p.name().to_string())
+                     .unwrap_or_else(|| idx.to_string());
+
+            if param.map(|p| m.param_input_is_used(p)).unwrap_or(false) {
+                diags.push(LintDiagnostic::at_cell(
+                    LintKind::KnobDisabled, cell,
+                    format!("The knob '{}' of {} is disabled, but nothing feeds it's input.",
+                        name, node_id)));
+            } else {
+                diags.push(LintDiagnostic::at_cell(
+                    LintKind::DanglingInput, cell,
+                    format!("Input '{}' of {} is not fed by anything.",
+                        name, node_id)));
+            }
+        }
+
+        // The remaining diagnostics are about the whole node:
+        if !checked.insert(node_id) {
+            continue;
+        }
+
+        // A knob can stay disabled after the edge with it's input was removed:
+        let mut i = 0;
+        while let Some(param) = node_id.inp_param_by_idx(i) {
+            i += 1;
+
+            let carried =
+                cells.iter()
+                    .filter(|c| c.node_id() == node_id)
+                    .any(|c| INPUT_DIRS.iter().any(|dir|
+                        c.local_port_idx(*dir) == Some(param.inp())));
+
+            if !carried && m.param_input_is_used(param)
+               && !node_conns.fed.contains(&param.inp())
+            {
+                diags.push(LintDiagnostic::at_cell(
+                    LintKind::KnobDisabled, cell,
+                    format!("The knob '{}' of {} is disabled, but nothing feeds it's input.",
+                        param.name(), node_id)));
+            }
+        }
+
+        if node_id.out_name_by_idx(0).is_none() || node_conns.output_connected {
+            continue;
+        }
+
+        if node_conns.fed.is_empty() {
+            diags.push(LintDiagnostic::at_cell(
+                LintKind::UnusedNode, cell,
+                format!("{} is not connected at all.", node_id)));
+        } else {
+            diags.push(LintDiagnostic::at_cell(
+                LintKind::NoOutputConnected, cell,
+                format!("No output of {} is connected.", node_id)));
+        }
+    }
+
+    diags
+}
@@ -0,0 +1,485 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+//! A bank of patterns for each `TSeq` and a chain of patterns for
+//! playing whole songs.
+//!
+//! A `TSeq` node only plays one pattern. The bank keeps the other
+//! patterns as text (see [crate::pattern_io]) in the matrix property
+//! "tseq_patterns_<tracker_id>", the chain and the song settings in
+//! "tseq_song_<tracker_id>", so that they are saved with the patch.
+//! Switching to another pattern stores the played pattern in the bank
+//! and copies the new one into the pattern of the `TSeq`.
+//!
+//! In song mode the chain is advanced at the end of the pattern. The
+//! pattern can also be chosen by a control signal: the output of a node
+//! in the range 0.0 to 1.0 is spread over the patterns of the bank.
+//! The [SongSequencer] thread switches the patterns, so that songs
+//! also play while the GUI is closed.
+
+use hexodsp::{Matrix, NodeId, SAtom};
+use hexotk::UIPatternModel;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+
+use crate::pattern_io::{pattern_from_text, pattern_to_text};
+
+/// How often the [SongSequencer] looks at the phase of the `TSeq` nodes.
+const SEQUENCER_INTERVAL_MS : u64 = 1;
+/// The [SongSequencer] switches the pattern, when the `TSeq` is expected
+/// to reach the end of it within this many looks at the phase. This gives
+/// the new pattern about 10ms to reach the audio thread before the first
+/// row of the next pass is played.
+const SWITCH_AHEAD_CALLS : f32 = 10.0;
+
+pub const MAX_BANK_PATTERNS : usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternSong {
+    /// The index of the pattern that is played by the `TSeq`.
+    pub current:   usize,
+    /// The number of patterns in the bank.
+    pub count:     usize,
+    /// The pattern indices, that are played one after another in song mode.
+    pub chain:     Vec<usize>,
+    pub chain_pos: usize,
+    pub song_mode: bool,
+    /// A node output that chooses the pattern.
+    pub control:   Option<(NodeId, u8)>,
+}
+
+impl Default for PatternSong {
+    fn default() -> Self {
+        Self {
+            current:   0,
+            count:     1,
+            chain:     vec![],
+            chain_pos: 0,
+            song_mode: false,
+            control:   None,
+        }
+    }
+}
+
+impl PatternSong {
+    /// Whether the patterns are switched by the [SongSequencer].
+    pub fn is_playing(&self) -> bool {
+        self.song_mode || self.control.is_some()
+    }
+
+    fn to_prop(&self) -> String {
+        let chain : Vec<String> = self.chain.iter().map(|i| i.to_string()).collect();
+        let mut s =
+            format!("current={};count={};chain={};pos={};song={}",
+                self.current, self.count, chain.join(","),
+                self.chain_pos, self.song_mode as u8);
+
+        if let Some((node_id, out)) = self.control {
+            s += &format!(";control={},{},{}", node_id.name(), node_id.instance(), out);
+        }
+
+        s
+    }
+
+    fn from_prop(s: &str) -> Self {
+        let mut song = PatternSong::default();
+
+        for entry in s.split(';') {
+            let mut kv = entry.splitn(2, '=');
+            let key    = kv.next().unwrap_or("");
+            let value  = kv.next().unwrap_or("");
+            let num    = value.parse::<usize>().unwrap_or(0);
+
+            match key {
+                "current" => song.current   = num,
+                "count"   => song.count     = num.max(1),
+                "pos"     => song.chain_pos = num,
+                "song"    => song.song_mode = num != 0,
+                "chain"   => {
+                    song.chain =
+                        value.split(',')
+                            .filter_map(|i| i.parse::<usize>().ok())
+                            .collect();
+                },
+                "control" => {
+                    let parts : Vec<&str> = value.split(',').collect();
+                    if parts.len() == 3 {
+                        let node_id =
+                            NodeId::from_str(parts[0])
+                                .to_instance(parts[1].parse::<usize>().unwrap_or(0));
+                        let out = parts[2].parse::<u8>().unwrap_or(0);
+                        song.control = Some((node_id, out));
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        song
+    }
+}
+
+fn song_prop(tracker_id: usize) -> String { format!("tseq_song_{}", tracker_id) }
+fn patterns_prop(tracker_id: usize) -> String { format!("tseq_patterns_{}", tracker_id) }
+
+pub fn load_song(m: &mut Matrix, tracker_id: usize) -> PatternSong {
+    match m.get_prop(&song_prop(tracker_id)) {
+        Some(SAtom::Str(s)) => PatternSong::from_prop(&s),
+        _                   => PatternSong::default(),
+    }
+}
+
+pub fn save_song(m: &mut Matrix, tracker_id: usize, song: &PatternSong) {
+    m.set_prop(&song_prop(tracker_id), SAtom::str(&song.to_prop()));
+    songs_changed(m);
+}
+
+/// The patterns are separated by lines with "PATTERN <index>".
+fn load_patterns(m: &mut Matrix, tracker_id: usize) -> Vec<String> {
+    let mut patterns : Vec<String> = vec![];
+
+    if let Some(SAtom::Str(s)) = m.get_prop(&patterns_prop(tracker_id)) {
+        for line in s.lines() {
+            if line.starts_with("PATTERN") {
+                patterns.push(String::new());
+            } else if let Some(pat) = patterns.last_mut() {
+                *pat += line;
+                *pat += "\n";
+            }
+        }
+    }
+
+    patterns
+}
+
+fn save_patterns(m: &mut Matrix, tracker_id: usize, patterns: &[String]) {
+    let mut s = String::new();
+    for (i, pat) in patterns.iter().enumerate() {
+        s += &format!("PATTERN {}\n", i);
+        s += pat;
+    }
+
+    m.set_prop(&patterns_prop(tracker_id), SAtom::str(&s));
+}
+
+/// Stores the played pattern `pat` in the bank and replaces it with the
+/// pattern `idx` of the bank. New patterns are created empty, with the
+/// row count and column types of the played pattern.
+pub fn select_pattern(
+    m: &mut Matrix, tracker_id: usize, pat: &mut dyn UIPatternModel, idx: usize)
+    -> Result<PatternSong, String>
+{
+    if idx >= MAX_BANK_PATTERNS {
+        return Err(format!(
+            "Pattern {} out of range 0-{}", idx, MAX_BANK_PATTERNS - 1));
+    }
+
+    let mut song     = load_song(m, tracker_id);
+    let mut patterns = load_patterns(m, tracker_id);
+
+    patterns.resize(song.count.max(song.current + 1), String::new());
+    patterns[song.current] = pattern_to_text(pat);
+
+    if idx >= patterns.len() || patterns[idx].is_empty() {
+        for row in 0..pat.rows() {
+            for col in 0..pat.cols() {
+                pat.clear_cell(row, col);
+            }
+        }
+        let empty = pattern_to_text(pat);
+
+        if idx >= patterns.len() {
+            patterns.resize(idx + 1, empty.clone());
+        }
+        patterns[idx] = empty;
+    }
+
+    let template = patterns[idx].clone();
+    for p in patterns.iter_mut().filter(|p| p.is_empty()) {
+        *p = template.clone();
+    }
+
+    pattern_from_text(pat, &patterns[idx])?;
+
+    song.current = idx;
+    song.count   = patterns.len();
+
+    save_patterns(m, tracker_id, &patterns);
+    save_song(m, tracker_id, &song);
+
+    Ok(song)
+}
+
+/// Returns the pattern to switch to at the end of the played pattern,
+/// and the new position in the chain.
+fn next_pattern(m: &mut Matrix, song: &PatternSong) -> Option<(usize, usize)> {
+    if let Some((node_id, out)) = song.control {
+        let (value, _) = m.filtered_out_fb_for(&node_id, out);
+        let idx = (value.clamp(0.0, 1.0) * song.count as f32) as usize;
+        return Some((idx.min(song.count - 1), song.chain_pos));
+    }
+
+    if song.song_mode && !song.chain.is_empty() {
+        let pos = (song.chain_pos + 1) % song.chain.len();
+        return Some((song.chain[pos], pos));
+    }
+
+    None
+}
+
+/// Decides when to switch the pattern of a `TSeq`, so that the next
+/// pattern already plays from its first row.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PassDetector {
+    last_phase: Option<f32>,
+    /// How far the phase advanced between the last two calls.
+    step:       f32,
+    /// Whether the pattern was switched ahead of the coming wrap.
+    switched:   bool,
+}
+
+impl PassDetector {
+    /// Returns true once per pass, shortly before the phase wraps around
+    /// to the first row, when it is expected to reach the end of the
+    /// pattern within the next few calls. If the wrap
+    /// was not expected, because the phase jumped or the pattern is very
+    /// short, it returns true right after the wrap instead. This works
+    /// for any number of rows, even for a pattern with a single row.
+    pub fn at_wrap(&mut self, phase: f32) -> bool {
+        let wrapped = matches!(self.last_phase, Some(last) if phase < last);
+
+        if let Some(last) = self.last_phase {
+            if !wrapped { self.step = phase - last; }
+        }
+        self.last_phase = Some(phase);
+
+        if wrapped {
+            let missed    = !self.switched;
+            self.switched = false;
+            return missed;
+        }
+
+        if !self.switched
+           && self.step > 0.0
+           && phase + self.step * SWITCH_AHEAD_CALLS >= 1.0
+        {
+            self.switched = true;
+            return true;
+        }
+
+        false
+    }
+}
+
+/// Switches the pattern of the `TSeq` `tracker_id` to the next one of
+/// its song. Returns the index of the new pattern, if it was switched.
+pub fn advance_song(m: &mut Matrix, tracker_id: usize, pat: &mut dyn UIPatternModel)
+    -> Result<Option<usize>, String>
+{
+    let song = load_song(m, tracker_id);
+    let (idx, pos) =
+        match next_pattern(m, &song) {
+            Some(next) => next,
+            None       => return Ok(None),
+        };
+
+    if idx == song.current && pos == song.chain_pos {
+        return Ok(None);
+    }
+
+    let mut song = select_pattern(m, tracker_id, pat, idx)?;
+    song.chain_pos = pos;
+    save_song(m, tracker_id, &song);
+
+    Ok(Some(idx))
+}
+
+/// Returns the instances of the `TSeq` nodes in the matrix, sorted.
+pub fn tracker_ids(m: &Matrix) -> Vec<usize> {
+    let mut tracker_ids = vec![];
+    m.for_each(|_x, _y, cell| {
+        let node_id = cell.node_id();
+        if node_id.to_instance(0) == NodeId::TSeq(0) {
+            tracker_ids.push(node_id.instance());
+        }
+    });
+
+    tracker_ids.sort_unstable();
+    tracker_ids.dedup();
+    tracker_ids
+}
+
+/// Whether a `TSeq` plays a song or is controlled by a signal. The
+/// [SongSequencer] thread sleeps while this is false.
+static SONGS_ACTIVE  : Mutex<bool> = Mutex::new(false);
+static SONGS_CHANGED : Condvar     = Condvar::new();
+
+/// Wakes up the [SongSequencer], if a `TSeq` of the matrix plays a song
+/// or is controlled by a signal, otherwise lets it sleep. This is called
+/// when a song is saved, and must be called after the properties of the
+/// matrix were replaced, like after loading a patch.
+pub fn songs_changed(m: &mut Matrix) {
+    let active =
+        tracker_ids(m).into_iter()
+            .any(|tracker_id| load_song(m, tracker_id).is_playing());
+
+    if let Ok(mut songs_active) = SONGS_ACTIVE.lock() {
+        *songs_active = active;
+        SONGS_CHANGED.notify_all();
+    }
+}
+
+/// Blocks until a `TSeq` plays a song or `stop` is set. Returns
+/// `None` if it was stopped, otherwise whether it had to wait.
+fn wait_for_songs(stop: &AtomicBool) -> Option<bool> {
+    let mut active = SONGS_ACTIVE.lock().ok()?;
+    let mut waited = false;
+
+    while !*active && !stop.load(Ordering::Relaxed) {
+        active = SONGS_CHANGED.wait(active).ok()?;
+        waited = true;
+    }
+
+    if stop.load(Ordering::Relaxed) { None } else { Some(waited) }
+}
+
+/// The state of the [SongSequencer] thread.
+#[derive(Default)]
+struct SongPoller {
+    /// The last song property of each `TSeq` and the song parsed from it.
+    songs:     HashMap<usize, (String, PatternSong)>,
+    detectors: HashMap<usize, PassDetector>,
+}
+
+impl SongPoller {
+    /// Looks at the phases of the `TSeq` nodes, that play a song, and
+    /// switches their patterns at the end of a pass. The matrix is only
+    /// locked to copy the song properties and the phases, and to switch
+    /// the pattern. The properties are parsed without holding the lock.
+    fn poll(&mut self, matrix: &Mutex<Matrix>) {
+        let trackers : Vec<(usize, String, f32)> =
+            match matrix.lock() {
+                Ok(mut m) => {
+                    tracker_ids(&m).into_iter()
+                        .map(|tracker_id| {
+                            let prop =
+                                match m.get_prop(&song_prop(tracker_id)) {
+                                    Some(SAtom::Str(s)) => s,
+                                    _                   => String::new(),
+                                };
+                            let phase =
+                                m.phase_value_for(&NodeId::TSeq(tracker_id as u8));
+
+                            (tracker_id, prop, phase)
+                        })
+                        .collect()
+                },
+                Err(_) => return,
+            };
+
+        for (tracker_id, prop, phase) in trackers.into_iter() {
+            let changed =
+                self.songs.get(&tracker_id)
+                    .map_or(true, |(old_prop, _)| *old_prop != prop);
+            if changed {
+                let song = PatternSong::from_prop(&prop);
+                self.songs.insert(tracker_id, (prop, song));
+            }
+
+            if !self.songs[&tracker_id].1.is_playing() {
+                // Start from scratch, when the song is switched on:
+                self.detectors.remove(&tracker_id);
+                continue;
+            }
+
+            if !self.detectors.entry(tracker_id).or_default().at_wrap(phase) {
+                continue;
+            }
+
+            if let Ok(mut m) = matrix.lock() {
+                switch_pattern(&mut m, tracker_id);
+            }
+        }
+    }
+}
+
+/// Switches the pattern of a `TSeq` with [advance_song] and syncs
+/// the new pattern to the audio thread.
+fn switch_pattern(m: &mut Matrix, tracker_id: usize) {
+    let model =
+        match m.get_pattern_data(tracker_id) {
+            Some(model) => model,
+            None        => return,
+        };
+
+    let res =
+        match model.lock() {
+            Ok(mut pat) => advance_song(m, tracker_id, &mut *pat),
+            Err(_)      => return,
+        };
+
+    match res {
+        Ok(Some(_)) => m.check_pattern_data(tracker_id),
+        Ok(None)    => (),
+        Err(msg)    => {
+            eprintln!("Can't switch the pattern of TSeq {}: {}", tracker_id, msg);
+        },
+    }
+}
+
+/// A thread, that switches the patterns of the `TSeq` nodes in song mode
+/// or with a control at the end of their pattern. It runs independently
+/// of the GUI, the backends start it next to the audio thread.
+/// The thread sleeps until a song is defined, see [songs_changed],
+/// and is stopped when the sequencer is dropped.
+pub struct SongSequencer {
+    stop:   Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SongSequencer {
+    pub fn start(matrix: Arc<Mutex<Matrix>>) -> Self {
+        let stop        = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        let thread =
+            std::thread::Builder::new()
+                .name("hexosynth_song_sequencer".to_string())
+                .spawn(move || {
+                    let mut poller = SongPoller::default();
+
+                    while let Some(waited) = wait_for_songs(&thread_stop) {
+                        // The phases are outdated after a pause:
+                        if waited { poller.detectors.clear(); }
+
+                        poller.poll(&matrix);
+
+                        std::thread::sleep(
+                            std::time::Duration::from_millis(SEQUENCER_INTERVAL_MS));
+                    }
+                })
+                .ok();
+
+        Self { stop, thread }
+    }
+}
+
+impl Drop for SongSequencer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        // Wake up the thread, if it waits for a song. The lock makes sure,
+        // that it is either waiting already or sees the stop flag:
+        if let Ok(_active) = SONGS_ACTIVE.lock() {
+            SONGS_CHANGED.notify_all();
+        }
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
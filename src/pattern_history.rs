@@ -0,0 +1,81 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+//! Undo and redo for the pattern data of a `TSeq`.
+//!
+//! The pattern editor changes the pattern data directly, so the history
+//! takes snapshots: [PatternHistory::checkpoint] is called after edits
+//! and remembers the pattern, if it changed since the last call.
+
+use hexotk::UIPatternModel;
+
+use crate::pattern_io::{pattern_from_text, pattern_to_text};
+
+const MAX_UNDO_STEPS : usize = 64;
+
+#[derive(Debug, Clone, Default)]
+pub struct PatternHistory {
+    undo: Vec<String>,
+    redo: Vec<String>,
+    last: Option<String>,
+}
+
+impl PatternHistory {
+    pub fn new() -> Self { Self::default() }
+
+    /// Forgets all steps, for instance when another
+    /// pattern was copied into the pattern data.
+    pub fn reset(&mut self, pat: &mut dyn UIPatternModel) {
+        self.undo.clear();
+        self.redo.clear();
+        self.last = Some(pattern_to_text(pat));
+    }
+
+    /// Records an undo step, if the pattern changed since the last call.
+    /// Returns true if a step was recorded.
+    pub fn checkpoint(&mut self, pat: &mut dyn UIPatternModel) -> bool {
+        let text = pattern_to_text(pat);
+
+        match self.last.take() {
+            Some(last) if last != text => {
+                self.undo.push(last);
+                if self.undo.len() > MAX_UNDO_STEPS {
+                    self.undo.remove(0);
+                }
+                self.redo.clear();
+                self.last = Some(text);
+                true
+            },
+            _ => {
+                self.last = Some(text);
+                false
+            },
+        }
+    }
+
+    pub fn undo_steps(&self) -> usize { self.undo.len() }
+    pub fn redo_steps(&self) -> usize { self.redo.len() }
+
+    pub fn undo(&mut self, pat: &mut dyn UIPatternModel) -> Result<bool, String> {
+        self.checkpoint(pat);
+
+        let text = match self.undo.pop() { Some(text) => text, None => return Ok(false) };
+        if let Some(last) = self.last.take() { self.redo.push(last); }
+
+        pattern_from_text(pat, &text)?;
+        self.last = Some(text);
+        Ok(true)
+    }
+
+    pub fn redo(&mut self, pat: &mut dyn UIPatternModel) -> Result<bool, String> {
+        self.checkpoint(pat);
+
+        let text = match self.redo.pop() { Some(text) => text, None => return Ok(false) };
+        if let Some(last) = self.last.take() { self.undo.push(last); }
+
+        pattern_from_text(pat, &text)?;
+        self.last = Some(text);
+        Ok(true)
+    }
+}
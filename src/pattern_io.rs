@@ -0,0 +1,257 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+//! Converts the pattern data of a `TSeq` to CSV and to a tracker like
+//! text format and back, so that sequences can be shared without patches.
+//!
+//! Both formats contain the row count, the column types and the cell
+//! values. Empty cells stay empty. Importing replaces the whole pattern,
+//! but only after the input was parsed without errors.
+//!
+//! CSV:
+//!
+//! ```text
+//! rows,4
+//! types,note,value,step,gate,value,value
+//! 0,03C,FFF,,,,
+//! 1,,,,0F0,,
+//! ```
+//!
+//! Text, the row numbers are hex like in the pattern editor:
+//!
+//! ```text
+//! ROWS 4
+//! TYPES note value step gate value value
+//! 00 | C-4 FFF --- --- --- ---
+//! 01 | --- --- --- 0F0 --- ---
+//! ```
+
+use hexotk::UIPatternModel;
+use crate::pattern_ops::get_cell;
+
+const NOTE_NAMES : [&str; 12] = [
+    "C-", "C#", "D-", "D#", "E-", "F-", "F#", "G-", "G#", "A-", "A#", "B-",
+];
+
+const EMPTY_CELL : &str = "---";
+const COL_TYPES  : [&str; 4] = ["note", "step", "gate", "value"];
+
+pub fn col_type(pat: &dyn UIPatternModel, col: usize) -> &'static str {
+    if      pat.is_col_note(col) { "note" }
+    else if pat.is_col_step(col) { "step" }
+    else if pat.is_col_gate(col) { "gate" }
+    else                         { "value" }
+}
+
+pub fn set_col_type(pat: &mut dyn UIPatternModel, col: usize, typ: &str) -> Result<(), String> {
+    match typ {
+        "note"  => pat.set_col_note_type(col),
+        "step"  => pat.set_col_step_type(col),
+        "gate"  => pat.set_col_gate_type(col),
+        "value" => pat.set_col_value_type(col),
+        _       => return Err(format!("Unknown column type: {}", typ)),
+    }
+
+    Ok(())
+}
+
+fn parse_rows(rows: &str) -> Result<usize, String> {
+    rows.trim().parse::<usize>()
+        .map_err(|_| format!("Bad row count: {}", rows))
+}
+
+fn parse_types<'a, I: Iterator<Item = &'a str>>(types: I) -> Result<Vec<String>, String> {
+    types.map(|typ| {
+        let typ = typ.trim();
+        if COL_TYPES.contains(&typ) { Ok(typ.to_string()) }
+        else { Err(format!("Unknown column type: {}", typ)) }
+    }).collect()
+}
+
+fn parse_hex(s: &str) -> Result<u16, String> {
+    u16::from_str_radix(s, 16).map_err(|_| format!("Bad cell value: {}", s))
+}
+
+/// Notes are MIDI note numbers, 60 is "C-4".
+fn note2str(note: u16) -> String {
+    if note > 127 { return format!("{:03X}", note); }
+    format!("{}{}", NOTE_NAMES[(note % 12) as usize], (note / 12) as i32 - 1)
+}
+
+fn str2note(s: &str) -> Option<u16> {
+    let name   = s.get(0..2)?;
+    let octave = s.get(2..)?.parse::<i32>().ok()?;
+    let idx    = NOTE_NAMES.iter().position(|n| *n == name)?;
+
+    let note = (octave + 1) * 12 + idx as i32;
+    if (0..=127).contains(&note) { Some(note as u16) } else { None }
+}
+
+fn parse_cells<'a, I: Iterator<Item = &'a str>>(
+    cells: I, parse: fn(&str) -> Result<Option<u16>, String>)
+    -> Result<Vec<Option<u16>>, String>
+{
+    cells.map(|cell| parse(cell.trim())).collect()
+}
+
+/// The contents of a CSV or text pattern. Everything is parsed before
+/// the pattern is changed, so a bad line leaves the pattern untouched.
+#[derive(Default)]
+struct ParsedPattern {
+    rows:  Option<usize>,
+    types: Vec<String>,
+    cells: Vec<(usize, Vec<Option<u16>>)>,
+}
+
+impl ParsedPattern {
+    /// Replaces the row count, the column types and all cells of `pat`.
+    /// Cells, that are not in the parsed pattern, are cleared.
+    fn apply(self, pat: &mut dyn UIPatternModel) -> Result<(), String> {
+        let rows = self.rows.unwrap_or_else(|| pat.rows());
+
+        if let Some((row, _)) = self.cells.iter().find(|(row, _)| *row >= rows) {
+            return Err(format!("Row {} is beyond the row count {}", row, rows));
+        }
+
+        let old_rows = pat.rows();
+        pat.set_rows(rows);
+        if pat.rows() != rows {
+            pat.set_rows(old_rows);
+            return Err(format!("Unsupported row count: {}", rows));
+        }
+
+        for (col, typ) in self.types.iter().enumerate().take(pat.cols()) {
+            set_col_type(pat, col, typ)?;
+        }
+
+        for row in 0..pat.rows() {
+            for col in 0..pat.cols() {
+                pat.clear_cell(row, col);
+            }
+        }
+
+        for (row, cells) in self.cells.into_iter() {
+            for (col, cell) in cells.into_iter().enumerate().take(pat.cols()) {
+                if let Some(v) = cell {
+                    pat.set_cell_value(row, col, v);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn pattern_to_csv(pat: &mut dyn UIPatternModel) -> String {
+    let mut out = format!("rows,{}\ntypes", pat.rows());
+    for col in 0..pat.cols() {
+        out += ",";
+        out += col_type(pat, col);
+    }
+    out += "\n";
+
+    for row in 0..pat.rows() {
+        out += &row.to_string();
+
+        for col in 0..pat.cols() {
+            out += ",";
+            if let Some(v) = get_cell(pat, row, col) {
+                out += &format!("{:03X}", v);
+            }
+        }
+        out += "\n";
+    }
+
+    out
+}
+
+/// Replaces the pattern with the `csv`. If the CSV can't be parsed,
+/// the pattern is left unchanged.
+pub fn pattern_from_csv(pat: &mut dyn UIPatternModel, csv: &str) -> Result<(), String> {
+    let mut parsed = ParsedPattern::default();
+
+    for line in csv.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+
+        let mut fields = line.split(',');
+        let first      = fields.next().unwrap_or("").trim();
+
+        match first {
+            "rows"  => parsed.rows  = Some(parse_rows(fields.next().unwrap_or(""))?),
+            "types" => parsed.types = parse_types(fields)?,
+            _ => {
+                let row =
+                    first.parse::<usize>()
+                        .map_err(|_| format!("Bad row number: {}", first))?;
+
+                parsed.cells.push((row, parse_cells(fields, |cell| {
+                    if cell.is_empty() { Ok(None) }
+                    else { parse_hex(cell).map(Some) }
+                })?));
+            },
+        }
+    }
+
+    parsed.apply(pat)
+}
+
+pub fn pattern_to_text(pat: &mut dyn UIPatternModel) -> String {
+    let mut out = format!("ROWS {}\nTYPES", pat.rows());
+    for col in 0..pat.cols() {
+        out += " ";
+        out += col_type(pat, col);
+    }
+    out += "\n";
+
+    for row in 0..pat.rows() {
+        out += &format!("{:02X} |", row);
+
+        for col in 0..pat.cols() {
+            out += " ";
+            out += &match get_cell(pat, row, col) {
+                Some(v) if pat.is_col_note(col) => note2str(v),
+                Some(v)                         => format!("{:03X}", v),
+                None                            => EMPTY_CELL.to_string(),
+            };
+        }
+        out += "\n";
+    }
+
+    out
+}
+
+/// Replaces the pattern with the `text`. If the text can't be parsed,
+/// the pattern is left unchanged.
+pub fn pattern_from_text(pat: &mut dyn UIPatternModel, text: &str) -> Result<(), String> {
+    let mut parsed = ParsedPattern::default();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+
+        if let Some(rows) = line.strip_prefix("ROWS") {
+            parsed.rows = Some(parse_rows(rows)?);
+
+        } else if let Some(types) = line.strip_prefix("TYPES") {
+            parsed.types = parse_types(types.split_whitespace())?;
+
+        } else {
+            let mut parts = line.splitn(2, '|');
+            let row       = parts.next().unwrap_or("").trim();
+            let row       =
+                usize::from_str_radix(row, 16)
+                    .map_err(|_| format!("Bad row number: {}", row))?;
+
+            let cells = parts.next().unwrap_or("").split_whitespace();
+            parsed.cells.push((row, parse_cells(cells, |cell| {
+                if cell == EMPTY_CELL { Ok(None) }
+                else if let Some(note) = str2note(cell) { Ok(Some(note)) }
+                else { parse_hex(cell).map(Some) }
+            })?));
+        }
+    }
+
+    parsed.apply(pat)
+}
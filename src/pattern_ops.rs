@@ -0,0 +1,223 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+//! Bulk operations on the pattern data of a `TSeq` for algorithmic
+//! sequencing. They only change the pattern data model, the changes
+//! are synchronized to the `TSeq` with `Matrix::check_pattern_data`.
+
+use hexotk::UIPatternModel;
+
+/// The highest value a cell can hold.
+pub const MAX_CELL_VALUE : u16 = 0xFFF;
+/// Note cells hold MIDI note numbers.
+pub const MAX_NOTE_VALUE : u16 = 127;
+
+/// A small deterministic random number generator (SplitMix64),
+/// so that generated patterns can be reproduced with the same seed.
+pub struct PatRng(u64);
+
+impl PatRng {
+    pub fn new(seed: u64) -> Self { Self(seed) }
+
+    /// Seeds from the current time.
+    pub fn from_time() -> Self {
+        let seed =
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or(0);
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number between 0.0 and 1.0 (exclusive).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// Returns an integer from `min` to `max` (inclusive).
+    pub fn range(&mut self, min: i64, max: i64) -> i64 {
+        if max <= min { return min; }
+        min + (self.next_u64() % ((max - min + 1) as u64)) as i64
+    }
+}
+
+pub fn check_col(pat: &dyn UIPatternModel, col: usize) -> Result<(), String> {
+    if col >= pat.cols() {
+        return Err(format!("Column {} out of range 0-{}", col, pat.cols() - 1));
+    }
+    Ok(())
+}
+
+pub fn check_row(pat: &dyn UIPatternModel, row: usize) -> Result<(), String> {
+    if row >= pat.rows() {
+        return Err(format!("Row {} beyond the row count {}", row, pat.rows()));
+    }
+    Ok(())
+}
+
+fn max_value(pat: &dyn UIPatternModel, col: usize) -> i64 {
+    if pat.is_col_note(col) { MAX_NOTE_VALUE as i64 } else { MAX_CELL_VALUE as i64 }
+}
+
+pub fn get_cell(pat: &mut dyn UIPatternModel, row: usize, col: usize) -> Option<u16> {
+    pat.get_cell(row, col)?;
+    Some(pat.get_cell_value(row, col))
+}
+
+/// Changes all values in the column `col` by `delta`, for note
+/// columns that is a transposition by `delta` semitones.
+/// Values are clamped to the range of the column.
+pub fn transpose(pat: &mut dyn UIPatternModel, col: usize, delta: i64) -> Result<(), String> {
+    check_col(pat, col)?;
+    let max = max_value(pat, col);
+
+    for row in 0..pat.rows() {
+        if let Some(v) = get_cell(pat, row, col) {
+            pat.set_cell_value(row, col, (v as i64 + delta).clamp(0, max) as u16);
+        }
+    }
+
+    Ok(())
+}
+
+/// Rotates all rows by `offset`, positive offsets move the rows down.
+/// Rows that move out at the end come back in at the start.
+pub fn rotate_rows(pat: &mut dyn UIPatternModel, offset: i64) {
+    let rows = pat.rows();
+    if rows == 0 { return; }
+
+    for col in 0..pat.cols() {
+        let values : Vec<Option<u16>> =
+            (0..rows).map(|row| get_cell(pat, row, col)).collect();
+
+        for (row, v) in values.into_iter().enumerate() {
+            let new_row = (row as i64 + offset).rem_euclid(rows as i64) as usize;
+
+            match v {
+                Some(v) => pat.set_cell_value(new_row, col, v),
+                None    => pat.clear_cell(new_row, col),
+            }
+        }
+    }
+}
+
+/// Fills every row of the column `col` with a random value from `min`
+/// to `max`, with the given `probability`. The other rows are cleared.
+pub fn randomize(
+    pat: &mut dyn UIPatternModel, col: usize, min: i64, max: i64,
+    probability: f64, rng: &mut PatRng)
+    -> Result<(), String>
+{
+    check_col(pat, col)?;
+    let col_max = max_value(pat, col);
+    let (min, max) = (min.clamp(0, col_max), max.clamp(0, col_max));
+
+    for row in 0..pat.rows() {
+        if rng.next_f64() < probability {
+            pat.set_cell_value(row, col, rng.range(min, max) as u16);
+        } else {
+            pat.clear_cell(row, col);
+        }
+    }
+
+    Ok(())
+}
+
+/// Distributes `pulses` as evenly as possible over `steps`, like the
+/// Bjorklund algorithm does. The first step is always a pulse.
+pub fn euclidean_rhythm(pulses: usize, steps: usize) -> Vec<bool> {
+    let pulses = pulses.min(steps);
+
+    (0..steps)
+        .map(|i| pulses > 0 && (i * pulses) % steps < pulses)
+        .collect()
+}
+
+/// Writes an Euclidean rhythm with `pulses` in `steps` into the column
+/// `col`, repeated over all rows. The pulses are set to `value`,
+/// the other rows are cleared.
+pub fn euclidean_fill(
+    pat: &mut dyn UIPatternModel, col: usize, pulses: usize, steps: usize,
+    rotation: i64, value: u16)
+    -> Result<(), String>
+{
+    check_col(pat, col)?;
+    if steps == 0 { return Err("Euclidean rhythm needs at least 1 step".to_string()); }
+
+    let rhythm = euclidean_rhythm(pulses, steps);
+    let value  = (value as i64).clamp(0, max_value(pat, col)) as u16;
+
+    for row in 0..pat.rows() {
+        let step = (row as i64 - rotation).rem_euclid(steps as i64) as usize;
+
+        if rhythm[step] { pat.set_cell_value(row, col, value); }
+        else            { pat.clear_cell(row, col); }
+    }
+
+    Ok(())
+}
+
+/// Adds a random offset from -`amount` to `amount` to every
+/// value in the column `col`, the values are clamped.
+pub fn humanize(
+    pat: &mut dyn UIPatternModel, col: usize, amount: i64, rng: &mut PatRng)
+    -> Result<(), String>
+{
+    check_col(pat, col)?;
+    let max = max_value(pat, col);
+
+    for row in 0..pat.rows() {
+        if let Some(v) = get_cell(pat, row, col) {
+            let offs = rng.range(-amount.abs(), amount.abs());
+            pat.set_cell_value(row, col, (v as i64 + offs).clamp(0, max) as u16);
+        }
+    }
+
+    Ok(())
+}
+
+/// A rectangular block of cells, row by row. `None` is an empty cell.
+pub type PatternBlock = Vec<Vec<Option<u16>>>;
+
+/// Copies `rows` x `cols` cells starting at `row`/`col`,
+/// the block is cut off at the end of the pattern.
+pub fn copy_block(
+    pat: &mut dyn UIPatternModel, row: usize, col: usize, rows: usize, cols: usize)
+    -> PatternBlock
+{
+    let row_end = (row + rows).min(pat.rows());
+    let col_end = (col + cols).min(pat.cols());
+
+    (row..row_end)
+        .map(|r| (col..col_end).map(|c| get_cell(pat, r, c)).collect())
+        .collect()
+}
+
+/// Writes the `block` at `row`/`col`, cells outside of the
+/// pattern are skipped. Values are clamped to the range of the column.
+pub fn paste_block(pat: &mut dyn UIPatternModel, row: usize, col: usize, block: &PatternBlock) {
+    for (r, cells) in block.iter().enumerate() {
+        if row + r >= pat.rows() { break; }
+
+        for (c, cell) in cells.iter().enumerate() {
+            if col + c >= pat.cols() { break; }
+
+            match cell {
+                Some(v) => {
+                    let max = max_value(pat, col + c);
+                    pat.set_cell_value(row + r, col + c, (*v as i64).min(max) as u16);
+                },
+                None => pat.clear_cell(row + r, col + c),
+            }
+        }
+    }
+}
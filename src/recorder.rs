@@ -0,0 +1,190 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+//! Records the audio device channels to a WAV file.
+//!
+//! The audio thread pushes the samples of the recorded channels into the
+//! lock free ring buffer of an [AudioTap]. A writer thread pops the samples
+//! and writes them with `hound`, so the audio thread never waits for the disk.
+//!
+//! HexoDSP passes the signals of the monitored cell only as min/max data to
+//! the frontend, so they can't be recorded. A signal from inside the patch
+//! is recorded by connecting it to the `Out` node.
+
+use crate::audio_tap::{self, AudioTap, TapSource};
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::JoinHandle;
+
+/// Seconds of audio the ring buffer can hold, before the
+/// writer thread falls behind and samples are dropped.
+const RECORD_BUFFER_SECS : usize = 4;
+
+struct RecordingWriter {
+    path:   String,
+    stop:   Arc<AtomicBool>,
+    thread: JoinHandle<Result<usize, String>>,
+}
+
+/// Information about a finished recording.
+#[derive(Debug, Clone)]
+pub struct RecordingInfo {
+    pub path:            String,
+    pub frames:          usize,
+    pub sample_rate:     u32,
+    pub dropped_samples: usize,
+}
+
+/// Writes the samples of `tap` until `stop` is set.
+/// Returns the number of dropped samples.
+fn writer_thread(
+    mut tap: AudioTap,
+    mut writer: hound::WavWriter<std::io::BufWriter<std::fs::File>>,
+    stop: Arc<AtomicBool>,
+    written_frames: Arc<AtomicUsize>)
+    -> Result<usize, String>
+{
+    // Only whole frames are popped:
+    let channels = tap.sources().len();
+    let mut buf  = vec![0.0; (4096 / channels) * channels];
+
+    loop {
+        // Read the flag first, so that the samples, which were pushed
+        // before the recording was stopped, are written:
+        let stopping = stop.load(Ordering::Relaxed);
+        let len      = tap.pop(&mut buf[..]);
+
+        for s in buf[0..len].iter() {
+            writer.write_sample(*s).map_err(|e| e.to_string())?;
+        }
+
+        written_frames.fetch_add(len / channels, Ordering::Relaxed);
+
+        if len == 0 {
+            if stopping { break; }
+
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+
+    let dropped = tap.dropped_samples();
+    writer.finalize().map_err(|e| e.to_string())?;
+
+    Ok(dropped)
+}
+
+/// Records a selection of audio device channels to WAV files.
+/// Every recorder has it's own audio tap, so several recordings
+/// can run at the same time.
+pub struct Recorder {
+    sources:        Vec<TapSource>,
+    writer:         Option<RecordingWriter>,
+    /// Frames written by the running or the last recording.
+    written_frames: Arc<AtomicUsize>,
+}
+
+impl Recorder {
+    /// Creates a recorder for the stereo output.
+    pub fn new() -> Self {
+        Self {
+            sources:        vec![TapSource::Output(0), TapSource::Output(1)],
+            writer:         None,
+            written_frames: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn sources(&self) -> &[TapSource] { &self.sources[..] }
+
+    /// Selects the channels for the next recording.
+    pub fn set_sources(&mut self, sources: Vec<TapSource>) -> Result<(), String> {
+        if sources.is_empty() {
+            return Err("No channels to record selected".to_string());
+        }
+        if self.is_recording() {
+            return Err("Can't change the channels while recording".to_string());
+        }
+
+        self.sources = sources;
+        Ok(())
+    }
+
+    /// Starts recording the selected channels to the WAV file at `path`.
+    pub fn start(&mut self, path: &str) -> Result<(), String> {
+        if self.writer.is_some() {
+            return Err("Already recording".to_string());
+        }
+
+        let sample_rate = audio_tap::sample_rate();
+
+        let spec = hound::WavSpec {
+            channels:        self.sources.len() as u16,
+            sample_rate,
+            bits_per_sample: 32,
+            sample_format:   hound::SampleFormat::Float,
+        };
+
+        let writer =
+            hound::WavWriter::create(path, spec)
+                .map_err(|e| format!("Can't create '{}': {}", path, e))?;
+
+        let tap =
+            AudioTap::new(
+                self.sources.clone(),
+                RECORD_BUFFER_SECS * sample_rate as usize);
+
+        self.written_frames = Arc::new(AtomicUsize::new(0));
+
+        let stop           = Arc::new(AtomicBool::new(false));
+        let thread_stop    = stop.clone();
+        let written_frames = self.written_frames.clone();
+
+        let thread =
+            std::thread::Builder::new()
+                .name("hexosynth_recorder".to_string())
+                .spawn(move || writer_thread(tap, writer, thread_stop, written_frames))
+                .map_err(|e| e.to_string())?;
+
+        self.writer = Some(RecordingWriter { path: path.to_string(), stop, thread });
+
+        Ok(())
+    }
+
+    /// Stops the running recording and waits until the WAV file is written.
+    pub fn stop(&mut self) -> Result<RecordingInfo, String> {
+        let rec =
+            self.writer.take()
+                .ok_or_else(|| "Not recording".to_string())?;
+
+        rec.stop.store(true, Ordering::Relaxed);
+
+        let dropped_samples =
+            rec.thread.join()
+                .map_err(|_| "Recorder thread panicked".to_string())??;
+
+        Ok(RecordingInfo {
+            path:            rec.path,
+            frames:          self.written_frames.load(Ordering::Relaxed),
+            sample_rate:     audio_tap::sample_rate(),
+            dropped_samples,
+        })
+    }
+
+    pub fn is_recording(&self) -> bool { self.writer.is_some() }
+
+    /// Returns the number of seconds recorded so far.
+    pub fn recorded_secs(&self) -> f32 {
+        self.written_frames.load(Ordering::Relaxed) as f32
+        / audio_tap::sample_rate() as f32
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        // Finish the WAV file, so that it is not left without a header:
+        if self.is_recording() {
+            let _ = self.stop();
+        }
+    }
+}
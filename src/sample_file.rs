@@ -0,0 +1,354 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+//! Decoding of WAV files for the `Sampl` node.
+
+use std::path::Path;
+
+/// Information about a WAV file, without decoding the samples.
+#[derive(Debug, Clone)]
+pub struct SampleFileInfo {
+    pub channels:        u16,
+    pub sample_rate:     u32,
+    pub bits_per_sample: u16,
+    pub float:           bool,
+    pub frames:          u32,
+}
+
+pub fn sample_file_info(path: &str) -> Result<SampleFileInfo, String> {
+    let rd =
+        hound::WavReader::open(path)
+            .map_err(|e| format!("Can't open '{}': {}", path, e))?;
+
+    let spec = rd.spec();
+
+    Ok(SampleFileInfo {
+        channels:        spec.channels,
+        sample_rate:     spec.sample_rate,
+        bits_per_sample: spec.bits_per_sample,
+        float:           spec.sample_format == hound::SampleFormat::Float,
+        frames:          rd.duration(),
+    })
+}
+
+/// A decoded sample, mixed down to mono.
+#[derive(Debug, Clone)]
+pub struct DecodedSample {
+    pub sample_rate: u32,
+    pub data:        Vec<f32>,
+}
+
+/// Decodes a WAV file of any bit depth and channel count into
+/// mono `f32` samples in the range -1.0 to 1.0. The channels are
+/// averaged, unless `channel` selects one of them.
+pub fn decode_wav(path: &str, channel: Option<usize>) -> Result<DecodedSample, String> {
+    let rd =
+        hound::WavReader::open(path)
+            .map_err(|e| format!("Can't open '{}': {}", path, e))?;
+
+    decode_wav_reader(rd, path, channel)
+}
+
+/// Like [decode_wav], but decodes the contents of a WAV file in memory.
+/// The `name` is only used for error messages.
+pub fn decode_wav_bytes(bytes: &[u8], name: &str, channel: Option<usize>)
+    -> Result<DecodedSample, String>
+{
+    let rd =
+        hound::WavReader::new(std::io::Cursor::new(bytes))
+            .map_err(|e| format!("Can't open '{}': {}", name, e))?;
+
+    decode_wav_reader(rd, name, channel)
+}
+
+fn decode_wav_reader<R: std::io::Read>(
+    mut rd: hound::WavReader<R>, path: &str, channel: Option<usize>)
+    -> Result<DecodedSample, String>
+{
+    let spec     = rd.spec();
+    let channels = spec.channels as usize;
+
+    if let Some(ch) = channel {
+        if ch >= channels {
+            return Err(format!(
+                "'{}' has only {} channels, can't select channel {}",
+                path, channels, ch));
+        }
+    }
+
+    let interleaved : Vec<f32> =
+        match spec.sample_format {
+            hound::SampleFormat::Float => {
+                rd.samples::<f32>()
+                    .collect::<Result<Vec<f32>, _>>()
+                    .map_err(|e| format!("Can't decode '{}': {}", path, e))?
+            },
+            hound::SampleFormat::Int => {
+                // 8 bit WAV files are unsigned, but hound already
+                // converts them to signed values:
+                let scale = 1.0 / (1_i64 << (spec.bits_per_sample - 1)) as f32;
+
+                rd.samples::<i32>()
+                    .map(|s| s.map(|s| s as f32 * scale))
+                    .collect::<Result<Vec<f32>, _>>()
+                    .map_err(|e| format!("Can't decode '{}': {}", path, e))?
+            },
+        };
+
+    let data =
+        interleaved.chunks(channels)
+            .map(|frame| {
+                if let Some(ch) = channel {
+                    frame[ch]
+                } else {
+                    frame.iter().sum::<f32>() / channels as f32
+                }
+            })
+            .collect();
+
+    Ok(DecodedSample { sample_rate: spec.sample_rate, data })
+}
+
+/// Number of zero crossings of the anti-aliasing filter on each side,
+/// in periods of the target rate.
+const LOWPASS_ZERO_CROSSINGS : f64 = 16.0;
+/// Cutoff of the anti-aliasing filter, relative to the target rate.
+/// It is below the nyquist frequency of 0.5, so that the transition
+/// band of the filter is mostly below it too.
+const LOWPASS_CUTOFF : f64 = 0.45;
+
+/// Low-pass filters `data` with a Blackman windowed sinc, that removes
+/// the frequencies above `cutoff` (relative to the sample rate of `data`).
+fn lowpass(data: &[f32], cutoff: f64, half_len: usize) -> Vec<f32> {
+    let half = half_len as isize;
+
+    let mut kernel : Vec<f64> =
+        (-half..=half)
+            .map(|k| {
+                let x    = k as f64;
+                let sinc =
+                    if k == 0 { 2.0 * cutoff }
+                    else {
+                        (2.0 * std::f64::consts::PI * cutoff * x).sin()
+                        / (std::f64::consts::PI * x)
+                    };
+
+                let w = std::f64::consts::PI * (x + half as f64) / half as f64;
+                sinc * (0.42 - 0.5 * w.cos() + 0.08 * (2.0 * w).cos())
+            })
+            .collect();
+
+    // Normalize, so that DC passes unchanged:
+    let sum : f64 = kernel.iter().sum();
+    kernel.iter_mut().for_each(|c| *c /= sum);
+
+    let at = |i: isize| data[i.clamp(0, data.len() as isize - 1) as usize] as f64;
+
+    (0..data.len() as isize)
+        .map(|i| {
+            kernel.iter().enumerate()
+                .map(|(k, c)| c * at(i + k as isize - half))
+                .sum::<f64>() as f32
+        })
+        .collect()
+}
+
+/// Converts `data` from the rate `from` to the rate `to`
+/// with cubic (Hermite) interpolation. When downsampling, the frequencies
+/// above the nyquist frequency of `to` are filtered out first, so that
+/// they don't alias.
+pub fn resample(data: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || data.is_empty() || from == 0 || to == 0 {
+        return data.to_vec();
+    }
+
+    let ratio   = from as f64 / to as f64;
+    let out_len = ((data.len() as f64) / ratio).floor() as usize;
+
+    let filtered;
+    let data =
+        if ratio > 1.0 {
+            let half_len = (LOWPASS_ZERO_CROSSINGS * ratio).ceil() as usize;
+            filtered = lowpass(data, LOWPASS_CUTOFF / ratio, half_len);
+            &filtered[..]
+        } else {
+            data
+        };
+
+    let at      = |i: isize| data[i.clamp(0, data.len() as isize - 1) as usize];
+
+    (0..out_len)
+        .map(|i| {
+            let pos  = i as f64 * ratio;
+            let idx  = pos.floor() as isize;
+            let frac = (pos - idx as f64) as f32;
+
+            let (xm1, x0, x1, x2) = (at(idx - 1), at(idx), at(idx + 1), at(idx + 2));
+
+            let c0 = x0;
+            let c1 = 0.5 * (x1 - xm1);
+            let c2 = xm1 - 2.5 * x0 + 2.0 * x1 - 0.5 * x2;
+            let c3 = 0.5 * (x2 - xm1) + 1.5 * (x0 - x1);
+
+            ((c3 * frac + c2) * frac + c1) * frac + c0
+        })
+        .collect()
+}
+
+/// Loads a WAV file for the `Sampl` node. The returned vector contains
+/// the sample rate in the first element, followed by the mono samples,
+/// which is the format HexoDSP expects in an `SAtom::AudioSample`.
+/// If `target_rate` is given, the sample is converted with [resample].
+pub fn load_sample_file(path: &str, target_rate: Option<u32>, channel: Option<usize>)
+    -> Result<Vec<f32>, String>
+{
+    Ok(sample_vec(decode_wav(path, channel)?, target_rate))
+}
+
+/// Converts a decoded sample into the vector format of
+/// [load_sample_file].
+pub fn sample_vec(sample: DecodedSample, target_rate: Option<u32>) -> Vec<f32> {
+    let (rate, data) =
+        if let Some(target_rate) = target_rate {
+            (target_rate, resample(&sample.data, sample.sample_rate, target_rate))
+        } else {
+            (sample.sample_rate, sample.data)
+        };
+
+    let mut v = Vec::with_capacity(data.len() + 1);
+    v.push(rate as f32);
+    v.extend_from_slice(&data);
+    v
+}
+
+fn mono_float_spec(sample_rate: u32) -> hound::WavSpec {
+    hound::WavSpec {
+        channels:        1,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format:   hound::SampleFormat::Float,
+    }
+}
+
+fn write_samples<W: std::io::Write + std::io::Seek>(
+    mut writer: hound::WavWriter<W>, data: &[f32]) -> Result<(), hound::Error>
+{
+    for s in data.iter() {
+        writer.write_sample(*s)?;
+    }
+
+    writer.finalize()
+}
+
+/// Writes mono samples to a 32 bit float WAV file.
+pub fn save_wav(path: &str, data: &[f32], sample_rate: u32) -> Result<(), String> {
+    let writer =
+        hound::WavWriter::create(path, mono_float_spec(sample_rate))
+            .map_err(|e| format!("Can't create '{}': {}", path, e))?;
+
+    write_samples(writer, data).map_err(|e| format!("Can't write '{}': {}", path, e))
+}
+
+/// Returns true if all samples are 16 bit values, for instance
+/// because they were decoded from a 16 bit WAV file.
+fn is_16bit(data: &[f32]) -> bool {
+    data.iter().all(|s| {
+        let v = *s * 32768.0;
+        v.fract() == 0.0 && (-32768.0..=32767.0).contains(&v)
+    })
+}
+
+/// Like [save_wav], but returns the contents of the WAV file.
+/// Samples that are 16 bit values are written as 16 bit WAV file
+/// without loss, which halves the size.
+pub fn encode_wav(data: &[f32], sample_rate: u32) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+
+    if is_16bit(data) {
+        let spec = hound::WavSpec {
+            channels:        1,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format:   hound::SampleFormat::Int,
+        };
+
+        let mut writer =
+            hound::WavWriter::new(std::io::Cursor::new(&mut bytes), spec)
+                .map_err(|e| e.to_string())?;
+        for s in data.iter() {
+            writer.write_sample((*s * 32768.0) as i16).map_err(|e| e.to_string())?;
+        }
+        writer.finalize().map_err(|e| e.to_string())?;
+
+        return Ok(bytes);
+    }
+
+    let writer =
+        hound::WavWriter::new(std::io::Cursor::new(&mut bytes), mono_float_spec(sample_rate))
+            .map_err(|e| e.to_string())?;
+    write_samples(writer, data).map_err(|e| e.to_string())?;
+
+    Ok(bytes)
+}
+
+/// An entry of a sample directory listing.
+#[derive(Debug, Clone)]
+pub struct SampleDirEntry {
+    pub name:   String,
+    pub path:   String,
+    pub is_dir: bool,
+}
+
+/// Lists the sub directories and WAV files in `dir`. Directories come first,
+/// the first entry is the parent directory `..`, if there is one.
+pub fn list_sample_dir(dir: &str) -> Result<Vec<SampleDirEntry>, String> {
+    list_dir(dir, &["wav"])
+}
+
+/// Like [list_sample_dir], but lists the files with one of the
+/// `extensions` instead of WAV files.
+pub fn list_dir(dir: &str, extensions: &[&str]) -> Result<Vec<SampleDirEntry>, String> {
+    // Canonicalize, so that a relative path like "." has a parent:
+    let dir = Path::new(dir);
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let dir = dir.as_path();
+    let rd  =
+        std::fs::read_dir(dir)
+            .map_err(|e| format!("Can't read directory '{}': {}", dir.display(), e))?;
+
+    let mut entries = vec![];
+
+    for entry in rd.flatten() {
+        let pb = entry.path();
+        let name =
+            if let Some(Some(s)) = pb.file_name().map(|s| s.to_str()) { s.to_string() }
+            else { continue; };
+
+        let has_ext =
+            pb.extension()
+                .and_then(|e| e.to_str())
+                .map(|e| extensions.iter().any(|x| e.eq_ignore_ascii_case(x)))
+                .unwrap_or(false);
+
+        if pb.is_dir() || has_ext {
+            entries.push(SampleDirEntry {
+                name,
+                path:   pb.to_string_lossy().to_string(),
+                is_dir: pb.is_dir(),
+            });
+        }
+    }
+
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then(a.name.cmp(&b.name)));
+
+    if let Some(parent) = dir.parent() {
+        entries.insert(0, SampleDirEntry {
+            name:   "..".to_string(),
+            path:   parent.to_string_lossy().to_string(),
+            is_dir: true,
+        });
+    }
+
+    Ok(entries)
+}
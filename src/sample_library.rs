@@ -0,0 +1,305 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+//! Stores the samples used by a patch next to the patch or inside of it,
+//! so that patches can be moved between machines.
+//!
+//! The samples are identified by the SHA-256 hash of the WAV file contents,
+//! so a sample used by several `Sampl` nodes is only stored once.
+//! Embedded samples are compressed with deflate before they are
+//! base64 encoded.
+
+use hexodsp::{Matrix, NodeId, ParamId, SAtom};
+use sha2::{Digest, Sha256};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::sample_file::{decode_wav, decode_wav_bytes, encode_wav, sample_vec};
+
+/// Sample paths starting with this prefix refer to a sample in the
+/// "embedded_samples" property of the matrix: "embedded:<hash>/<file name>"
+const EMBEDDED_PREFIX : &str = "embedded:";
+const EMBEDDED_PROP   : &str = "embedded_samples";
+/// Marks the deflate compressed entries in the "embedded_samples"
+/// property: "<hash>=deflate:<base64>". Entries without it are the
+/// uncompressed WAV files of older patches.
+const DEFLATE_PREFIX  : &str = "deflate:";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleStorage {
+    /// Keep the paths of the sample files as they are.
+    Path,
+    /// Copy the samples into the directory "<patch file>.samples"
+    /// next to the patch.
+    Bundle,
+    /// Store the samples compressed and base64 encoded inside the patch.
+    Embed,
+}
+
+impl SampleStorage {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "path"   => Some(SampleStorage::Path),
+            "bundle" => Some(SampleStorage::Bundle),
+            "embed"  => Some(SampleStorage::Embed),
+            _        => None,
+        }
+    }
+}
+
+/// The SHA-256 hash of `bytes` as hex string.
+pub fn content_hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+fn base64_decode(s: &str) -> Result<Vec<u8>, String> {
+    BASE64.decode(s).map_err(|e| format!("Invalid base64 data: {}", e))
+}
+
+/// Compresses the contents of a WAV file for the "embedded_samples" property.
+fn encode_embedded(bytes: &[u8]) -> String {
+    format!("{}{}",
+        DEFLATE_PREFIX,
+        BASE64.encode(miniz_oxide::deflate::compress_to_vec(bytes, 9)))
+}
+
+/// Returns the contents of the WAV file of an "embedded_samples" entry.
+fn decode_embedded(entry: &str) -> Result<Vec<u8>, String> {
+    if let Some(b64) = entry.strip_prefix(DEFLATE_PREFIX) {
+        miniz_oxide::inflate::decompress_to_vec(&base64_decode(b64)?)
+            .map_err(|e| format!("Can't decompress the embedded sample: {:?}", e))
+    } else {
+        base64_decode(entry)
+    }
+}
+
+fn embedded_hash(path: &str) -> Option<&str> {
+    let rest = path.strip_prefix(EMBEDDED_PREFIX)?;
+    Some(rest.split('/').next().unwrap_or(rest))
+}
+
+fn embedded_samples(m: &mut Matrix) -> HashMap<String, String> {
+    let mut samples = HashMap::new();
+
+    if let Some(SAtom::Str(s)) = m.get_prop(EMBEDDED_PROP) {
+        for entry in s.split(';') {
+            let entry : Vec<&str> = entry.splitn(2, '=').collect();
+            if entry.len() < 2 { continue; }
+
+            samples.insert(entry[0].to_string(), entry[1].to_string());
+        }
+    }
+
+    samples
+}
+
+fn patch_dir(patch_path: &str) -> PathBuf {
+    match Path::new(patch_path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Relative sample paths are first looked up relative to the patch,
+/// then relative to the current directory.
+fn resolve_path(path: &str, patch_dir: &Path) -> PathBuf {
+    let p = Path::new(path);
+    if p.is_relative() {
+        let in_patch_dir = patch_dir.join(p);
+        if in_patch_dir.exists() { return in_patch_dir; }
+    }
+
+    p.to_path_buf()
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit(|c| c == '/' || c == '\\').next().unwrap_or(path)
+}
+
+/// Returns all sample atoms of the nodes in the matrix.
+fn sample_params(m: &mut Matrix) -> Vec<(ParamId, String, Option<Arc<Vec<f32>>>)> {
+    let mut node_ids : HashSet<NodeId> = HashSet::new();
+    m.for_each(|_x, _y, cell| { node_ids.insert(cell.node_id()); });
+
+    let mut params = vec![];
+
+    for node_id in node_ids.into_iter() {
+        let mut idx = 0;
+        while let Some(param_id) = node_id.param_by_idx(idx) {
+            idx += 1;
+
+            if let SAtom::AudioSample(_) = param_id.as_atom_def() {
+                if let Some(SAtom::AudioSample((path, data))) = m.get_param(&param_id) {
+                    if !path.is_empty() {
+                        params.push((param_id, path, data));
+                    }
+                }
+            }
+        }
+    }
+
+    params
+}
+
+/// Returns the contents of the WAV file of a sample. The loaded sample
+/// data is what the node plays, so it is encoded if it is in memory,
+/// even if the file has changed on disk since. Otherwise the embedded
+/// or the sample file is read.
+fn sample_bytes(
+    path: &str, data: Option<&Arc<Vec<f32>>>,
+    embedded: &HashMap<String, String>, patch_dir: &Path)
+    -> Result<Vec<u8>, String>
+{
+    if let Some(data) = data {
+        if data.len() > 1 {
+            return encode_wav(&data[1..], data[0] as u32);
+        }
+    }
+
+    if let Some(hash) = embedded_hash(path) {
+        embedded.get(hash)
+            .ok_or_else(|| format!("Embedded sample '{}' not found", path))
+            .and_then(|entry| decode_embedded(entry))
+    } else {
+        std::fs::read(resolve_path(path, patch_dir))
+            .map_err(|e| format!("Sample '{}' not found: {}", path, e))
+    }
+}
+
+/// Prepares the sample atoms before the patch is saved to `patch_path`.
+/// With [SampleStorage::Bundle] the WAV files are copied into the bundle
+/// directory, with [SampleStorage::Embed] they are put into a property
+/// of the matrix. The sample paths are changed to refer to the stored
+/// samples. Returns the number of distinct samples stored.
+///
+/// All samples are read and encoded before anything is changed, so if
+/// one of them can't be stored, the matrix keeps the old paths.
+pub fn store_samples(m: &mut Matrix, patch_path: &str, storage: SampleStorage)
+    -> Result<usize, String>
+{
+    let patch_dir    = patch_dir(patch_path);
+    let old_embedded = embedded_samples(m);
+    let mut embedded = HashMap::new();
+    let mut files    = HashMap::new();
+    let mut atoms    = vec![];
+
+    let bundle_name =
+        format!("{}.samples",
+            Path::new(patch_path).file_name()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "patch".to_string()));
+    let bundle_dir = patch_dir.join(&bundle_name);
+
+    for (param_id, path, data) in sample_params(m) {
+        if storage == SampleStorage::Path {
+            // Keep only the data of samples that are still embedded:
+            if let Some(hash) = embedded_hash(&path) {
+                if let Some(entry) = old_embedded.get(hash) {
+                    embedded.insert(hash.to_string(), entry.clone());
+                }
+            }
+            continue;
+        }
+
+        let bytes = sample_bytes(&path, data.as_ref(), &old_embedded, &patch_dir)?;
+        let hash  = content_hash(&bytes);
+
+        let new_path =
+            if storage == SampleStorage::Bundle {
+                files.entry(hash.clone()).or_insert(bytes);
+                format!("{}/{}.wav", bundle_name, hash)
+            } else {
+                embedded.entry(hash.clone()).or_insert_with(|| encode_embedded(&bytes));
+                format!("{}{}/{}", EMBEDDED_PREFIX, hash, file_name(&path))
+            };
+
+        let atom =
+            match data {
+                Some(data) => SAtom::audio(&new_path, data),
+                None       => SAtom::audio_unloaded(&new_path),
+            };
+        atoms.push((param_id, atom));
+    }
+
+    for (hash, bytes) in files.iter() {
+        let file = bundle_dir.join(format!("{}.wav", hash));
+
+        // Rewrite files, that were left incomplete or were modified:
+        let up_to_date =
+            std::fs::read(&file).map(|old| old == *bytes).unwrap_or(false);
+
+        if !up_to_date {
+            std::fs::create_dir_all(&bundle_dir)
+                .and_then(|_| std::fs::write(&file, bytes))
+                .map_err(|e| format!("Can't write '{}': {}", file.display(), e))?;
+        }
+    }
+
+    for (param_id, atom) in atoms.into_iter() {
+        m.set_param(param_id, atom);
+    }
+
+    let stored =
+        match storage {
+            SampleStorage::Path   => 0,
+            SampleStorage::Bundle => files.len(),
+            SampleStorage::Embed  => embedded.len(),
+        };
+
+    let entries : Vec<String> =
+        embedded.iter().map(|(hash, entry)| format!("{}={}", hash, entry)).collect();
+    m.set_prop(EMBEDDED_PROP, SAtom::str(&entries.join(";")));
+
+    Ok(stored)
+}
+
+/// Loads the samples of a patch, that was loaded from `patch_path`:
+/// Embedded samples, samples in the bundle directory and other samples
+/// with paths relative to the patch. Returns the paths of the samples
+/// that could not be loaded, together with the reason. Every path is
+/// only reported once, even if several nodes use it.
+pub fn resolve_samples(m: &mut Matrix, patch_path: &str, target_rate: Option<u32>)
+    -> Vec<(String, String)>
+{
+    let patch_dir = patch_dir(patch_path);
+    let embedded  = embedded_samples(m);
+    let mut cache : HashMap<String, Arc<Vec<f32>>> = HashMap::new();
+    let mut missing : Vec<(String, String)> = vec![];
+
+    for (param_id, path, data) in sample_params(m) {
+        if data.is_some() { continue; }
+        if missing.iter().any(|(p, _)| *p == path) { continue; }
+
+        if let Some(data) = cache.get(&path) {
+            m.set_param(param_id, SAtom::audio(&path, data.clone()));
+            continue;
+        }
+
+        let sample =
+            if let Some(hash) = embedded_hash(&path) {
+                embedded.get(hash)
+                    .ok_or_else(|| "The embedded sample is missing in the patch".to_string())
+                    .and_then(|entry| decode_embedded(entry))
+                    .and_then(|bytes| decode_wav_bytes(&bytes, &path, None))
+            } else {
+                let file = resolve_path(&path, &patch_dir);
+                decode_wav(&file.to_string_lossy(), None)
+            };
+
+        match sample {
+            Ok(sample) => {
+                let data = Arc::new(sample_vec(sample, target_rate));
+                cache.insert(path.clone(), data.clone());
+                m.set_param(param_id, SAtom::audio(&path, data));
+            },
+            Err(reason) => missing.push((path, reason)),
+        }
+    }
+
+    missing
+}
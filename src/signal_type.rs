@@ -0,0 +1,188 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use hexodsp::{Matrix, Cell, CellDir, NodeId};
+
+/// A rough classification of the signals that flow between the nodes.
+/// HexoDSP does not distinguish these, all signals are just `f32` buffers.
+/// The ports are classified by their names and the node type, inputs
+/// for which the names are misleading are listed in [INPUT_TYPES].
+/// The outputs of pass-through nodes like `amp` and `fbrd` get the type
+/// of the signal at their input, see [edge_signal].
+/// The classification is only used to warn the user about suspicious
+/// connections.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignalType {
+    Audio,
+    Control,
+    Pitch,
+    Trigger,
+}
+
+/// Nodes that produce control signals, like envelopes and LFOs.
+const CONTROL_NODES : [&str; 6] = ["ad", "tslfo", "rndwk", "map", "smap", "tseq"];
+/// Nodes that produce pitch signals.
+const PITCH_NODES   : [&str; 2] = ["quant", "cqnt"];
+
+/// Inputs of known nodes, that the name based classification gets wrong.
+const INPUT_TYPES : [(&str, &str, SignalType); 3] = [
+    ("cqnt", "inp", SignalType::Pitch),
+    ("map",  "inp", SignalType::Control),
+    ("smap", "inp", SignalType::Control),
+];
+
+/// Limits how many pass-through nodes are followed back to find the
+/// type of a signal. This also stops at loops through `fbwr`/`fbrd`.
+const MAX_PASS_THROUGH_DEPTH : usize = 16;
+
+const INPUT_DIRS : [CellDir; 3] = [CellDir::T, CellDir::TL, CellDir::BL];
+
+impl SignalType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SignalType::Audio   => "audio",
+            SignalType::Control => "control",
+            SignalType::Pitch   => "pitch",
+            SignalType::Trigger => "trigger",
+        }
+    }
+
+    pub fn for_input(node_id: NodeId, name: &str) -> Self {
+        if let Some((_, _, typ)) =
+            INPUT_TYPES.iter().find(|(n, p, _)| *n == node_id.name() && *p == name)
+        {
+            return *typ;
+        }
+
+        match name {
+            "trig" | "gate" | "reset" | "rst" | "clock" | "clk" | "sync" =>
+                SignalType::Trigger,
+            "freq" | "det" | "note" =>
+                SignalType::Pitch,
+            "inp" | "sig" | "in_l" | "in_r" =>
+                SignalType::Audio,
+            _ if name.starts_with("ch") || name.starts_with("in_") =>
+                SignalType::Audio,
+            _ =>
+                SignalType::Control,
+        }
+    }
+
+    pub fn for_output(node_id: NodeId, name: &str) -> Self {
+        if name.starts_with("gat") || name.starts_with("eo")
+           || name == "trig" || name == "clk"
+        {
+            SignalType::Trigger
+        } else if PITCH_NODES.contains(&node_id.name()) {
+            SignalType::Pitch
+        } else if CONTROL_NODES.contains(&node_id.name()) {
+            SignalType::Control
+        } else {
+            SignalType::Audio
+        }
+    }
+
+    /// Returns false if a signal of this type should not drive
+    /// an input of type `input`. Audio signals at trigger inputs
+    /// cause random retriggering for instance.
+    pub fn can_drive(&self, input: SignalType) -> bool {
+        !matches!(
+            (self, input),
+            (SignalType::Audio, SignalType::Trigger)
+            | (SignalType::Pitch, SignalType::Trigger))
+    }
+}
+
+/// Describes the signal types on a connected edge between two cells.
+#[derive(Debug, Clone, Copy)]
+pub struct EdgeSignal {
+    pub output: SignalType,
+    pub input:  SignalType,
+}
+
+impl EdgeSignal {
+    pub fn is_miswired(&self) -> bool { !self.output.can_drive(self.input) }
+}
+
+/// Returns the node, whose `inp` input is passed through to the output
+/// of `node_id`. A `fbrd` outputs what the `fbwr` of the same instance gets.
+fn pass_through_source(node_id: NodeId) -> Option<NodeId> {
+    match node_id.name() {
+        "amp"  => Some(node_id),
+        "fbrd" => Some(NodeId::FbWr(0).to_instance(node_id.instance())),
+        _      => None,
+    }
+}
+
+/// Looks up the type of the signal, that feeds the `inp` input
+/// of `node_id` in any of it's cells.
+fn input_signal_type(m: &Matrix, node_id: NodeId, depth: usize) -> Option<SignalType> {
+    if depth > MAX_PASS_THROUGH_DEPTH { return None; }
+
+    let mut cells = vec![];
+    m.for_each(|_x, _y, cell| {
+        if cell.node_id() == node_id { cells.push(*cell); }
+    });
+
+    for cell in cells {
+        for dir in INPUT_DIRS {
+            let in_idx =
+                if let Some(idx) = cell.local_port_idx(dir) { idx }
+                else { continue };
+            if node_id.inp_name_by_idx(in_idx) != Some("inp") { continue; }
+
+            let out_cell =
+                dir.offs_pos(cell.pos())
+                   .and_then(|pos| m.get_copy(pos.0, pos.1));
+            let out_idx =
+                out_cell.and_then(|c| c.local_port_idx(dir.flip()));
+
+            if let (Some(out_cell), Some(out_idx)) = (out_cell, out_idx) {
+                let typ = output_signal_type(m, out_cell.node_id(), out_idx, depth + 1);
+                if typ.is_some() { return typ; }
+            }
+        }
+    }
+
+    None
+}
+
+/// The type of the signal at the output `out_idx` of `node_id`.
+/// Pass-through nodes get the type of their input signal, if it's
+/// connected.
+fn output_signal_type(m: &Matrix, node_id: NodeId, out_idx: u8, depth: usize)
+    -> Option<SignalType>
+{
+    let name = node_id.out_name_by_idx(out_idx)?;
+
+    pass_through_source(node_id)
+        .and_then(|src| input_signal_type(m, src, depth))
+        .or_else(|| Some(SignalType::for_output(node_id, name)))
+}
+
+/// Looks up the signal types of the connection at the edge `dir` of
+/// `cell`. Returns `None` if there is no connection on that edge.
+pub fn edge_signal(m: &Matrix, cell: &Cell, dir: CellDir) -> Option<EdgeSignal> {
+    let (out_cell, out_dir) =
+        if dir.is_output() {
+            (*cell, dir)
+        } else {
+            let pos = dir.offs_pos(cell.pos())?;
+            (m.get_copy(pos.0, pos.1)?, dir.flip())
+        };
+
+    let in_pos  = out_dir.offs_pos(out_cell.pos())?;
+    let in_cell = m.get_copy(in_pos.0, in_pos.1)?;
+
+    let out_idx = out_cell.local_port_idx(out_dir)?;
+    let in_idx  = in_cell.local_port_idx(out_dir.flip())?;
+
+    let out_id  = out_cell.node_id();
+    let in_id   = in_cell.node_id();
+
+    Some(EdgeSignal {
+        output: output_signal_type(m, out_id, out_idx, 0)?,
+        input:  SignalType::for_input(in_id, in_id.inp_name_by_idx(in_idx)?),
+    })
+}
@@ -93,6 +93,10 @@ impl VGraphModel {
             matrix,
         })))
     }
+
+    pub fn from_model(model: Rc<RefCell<dyn GraphModel>>) -> Self {
+        Self(model)
+    }
 }
 
 impl VValUserData for VGraphModel {
@@ -18,7 +18,8 @@ struct MonitorMinMaxData {
     avg:        f32,
 }
 
-fn sigidx2celldir(idx: usize) -> CellDir {
+/// Maps the index of a monitored signal to the edge of the monitored cell.
+pub fn sigidx2celldir(idx: usize) -> CellDir {
     match idx {
         0 => CellDir::T,
         1 => CellDir::TL,
@@ -89,6 +90,10 @@ impl GraphMinMaxModel for MonitorMinMaxData {
 pub struct VGraphMinMaxModel(Rc<RefCell<dyn GraphMinMaxModel>>);
 
 impl VGraphMinMaxModel {
+    pub fn new(model: Rc<RefCell<dyn GraphMinMaxModel>>) -> Self {
+        Self(model)
+    }
+
     pub fn new_monitor_model(matrix: Arc<Mutex<Matrix>>, index: usize) -> Self {
         Self(Rc::new(RefCell::new(MonitorMinMaxData {
             matrix,
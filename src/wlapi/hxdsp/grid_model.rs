@@ -6,20 +6,63 @@ use wlambda::*;
 use crate::arg_chk;
 use hexotk::{HexGridModel, HexCell, HexDir, HexEdge, HexHLight};
 
-use hexodsp::{Matrix, NodeId, SAtom};
+use hexodsp::{Matrix, Cell, CellDir, NodeId, SAtom};
+use super::vv2node_id;
 
 use std::sync::{Arc, Mutex};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use std::rc::Rc;
 use std::cell::RefCell;
 
+/// Signals below this level are considered silent by the signal flow overlay.
+const SILENCE_LEVEL : f32 = 0.0001;
+
+fn is_clipping(value: (f32, f32)) -> bool {
+    value.0.abs() > 1.0 || value.1.abs() > 1.0
+}
+
+fn is_silent(value: (f32, f32)) -> bool {
+    value.0.abs() < SILENCE_LEVEL && value.1.abs() < SILENCE_LEVEL
+}
+
+/// Returns the output signal value that flows over the edge `dir` of `cell`,
+/// regardless of whether `dir` is an input or output edge of the cell.
+/// Returns `None` if the edge is not connected.
+fn edge_value(m: &mut Matrix, cell: &Cell, dir: CellDir) -> Option<(f32, f32)> {
+    let (out_cell, out_dir) =
+        if dir.is_output() {
+            (*cell, dir)
+        } else {
+            let pos = dir.offs_pos(cell.pos())?;
+            (m.get_copy(pos.0, pos.1)?, dir.flip())
+        };
+
+    let in_pos  = out_dir.offs_pos(out_cell.pos())?;
+    let in_cell = m.get_copy(in_pos.0, in_pos.1)?;
+
+    let out_idx = out_cell.local_port_idx(out_dir)?;
+    in_cell.local_port_idx(out_dir.flip())?;
+
+    Some(m.filtered_out_fb_for(&out_cell.node_id(), out_idx))
+}
+
 pub struct MatrixUIModel {
     matrix:         Arc<Mutex<Matrix>>,
     w:              usize,
     h:              usize,
     node_colors:    HashMap<NodeId, u8>,
+    node_labels:    HashMap<NodeId, String>,
     focus:          (usize, usize),
+    marked_cells:   HashSet<(usize, usize)>,
+    /// Cells with suspicious connections. These are kept apart from
+    /// the `marked_cells`, which other features like the lint set.
+    warning_cells:  HashSet<(usize, usize)>,
+    /// Shows the signal levels on all connected edges, marks clipping
+    /// signals and dims nodes whose outputs are silent.
+    signal_flow:    bool,
+    /// Incremented on UI only changes, that need a redraw of the grid.
+    ui_generation:  u64,
     edge_led_cache_generation: Rc<RefCell<usize>>,
     edge_led_cache:  Rc<RefCell<std::collections::HashMap<(usize, usize, HexDir), (NodeId, u8)>>>,
 }
@@ -33,7 +76,12 @@ impl MatrixUIModel {
             w,
             h,
             node_colors: HashMap::new(),
+            node_labels: HashMap::new(),
             focus:  (0, 0),
+            marked_cells: HashSet::new(),
+            warning_cells: HashSet::new(),
+            signal_flow: false,
+            ui_generation: 0,
             edge_led_cache_generation: Rc::new(RefCell::new(0)),
             edge_led_cache: Rc::new(RefCell::new(std::collections::HashMap::new())),
         };
@@ -44,10 +92,76 @@ impl MatrixUIModel {
     }
 
     pub fn set_focus_cell(&mut self, x: usize, y: usize) {
-        self.focus = (x, y);
+        if self.focus != (x, y) {
+            self.focus = (x, y);
+            // The focused cell is highlighted:
+            self.ui_generation += 1;
+        }
+    }
+
+    /// Marks cells, for instance to show the results of a patch lint.
+    /// Pass an empty iterator to clear all marks.
+    pub fn set_marked_cells<I: Iterator<Item=(usize, usize)>>(&mut self, cells: I) {
+        self.marked_cells.clear();
+        self.marked_cells.extend(cells);
+        self.ui_generation += 1;
+    }
+
+    pub fn set_warning_cells<I: Iterator<Item=(usize, usize)>>(&mut self, cells: I) {
+        let cells : HashSet<(usize, usize)> = cells.collect();
+        if cells != self.warning_cells {
+            self.warning_cells = cells;
+            self.ui_generation += 1;
+        }
+    }
+
+    pub fn set_signal_flow(&mut self, enabled: bool) {
+        self.signal_flow = enabled;
+        self.ui_generation += 1;
+    }
+
+    /// Called once per UI frame. While the signal flow overlay is active,
+    /// this forces a redraw of the changing signal levels.
+    pub fn next_frame(&mut self) {
+        if self.signal_flow {
+            self.ui_generation += 1;
+        }
+    }
+
+    /// A node is silent, if it has connected outputs, but none of
+    /// them carries a signal.
+    fn cell_is_silent(m: &mut Matrix, cell: &Cell) -> bool {
+        let mut connected = false;
+
+        for edge in 0..6 {
+            let dir = CellDir::from(edge);
+            if !dir.is_output() { continue; }
+
+            if let Some(value) = edge_value(m, cell, dir) {
+                if !is_silent(value) { return false; }
+                connected = true;
+            }
+        }
+
+        connected
+    }
+
+    /// Returns true if any of the inputs of the cell receives a clipping signal.
+    fn cell_receives_clipping(m: &mut Matrix, cell: &Cell) -> bool {
+        for edge in 0..6 {
+            let dir = CellDir::from(edge);
+            if dir.is_output() { continue; }
+
+            if let Some(value) = edge_value(m, cell, dir) {
+                if is_clipping(value) { return true; }
+            }
+        }
+
+        false
     }
 
-    #[allow(dead_code)]
+    /// Stores the node colors and labels as properties in the matrix,
+    /// so that they are saved with the patch.
     pub fn sync_to_matrix(&self) {
         let mut m = self.matrix.lock().expect("matrix lockable");
 
@@ -61,17 +175,32 @@ impl MatrixUIModel {
         }
 
         m.set_prop("node_colors", SAtom::str(&entries.join(";")));
+
+        let mut entries = vec![];
+
+        for (k, v) in self.node_labels.iter() {
+            entries.push(format!("{},{},{}",
+                k.name(),
+                k.instance(),
+                v));
+        }
+
+        m.set_prop("node_labels", SAtom::str(&entries.join(";")));
     }
 
     pub fn sync_from_matrix(&mut self) {
         let mut m = self.matrix.lock().expect("matrix lockable");
 
+        self.node_colors.clear();
+        self.node_labels.clear();
+
         println!("SYNC FROM");
         if let Some(SAtom::Str(s)) = m.get_prop("node_colors") {
             println!("SYNC FROM {}", s);
 
             for entry in s.split(";") {
                 let entry : Vec<&str> = entry.split(",").collect();
+                if entry.len() < 3 { continue; }
 
                 let node_id = NodeId::from_str(entry[0]);
                 let inst    = entry[1].parse::<usize>().unwrap_or(0);
@@ -81,11 +210,47 @@ impl MatrixUIModel {
                 self.node_colors.insert(node_id, color);
             }
         }
+
+        if let Some(SAtom::Str(s)) = m.get_prop("node_labels") {
+            for entry in s.split(";") {
+                let entry : Vec<&str> = entry.splitn(3, ",").collect();
+                if entry.len() < 3 { continue; }
+
+                let node_id = NodeId::from_str(entry[0]);
+                let inst    = entry[1].parse::<usize>().unwrap_or(0);
+                let node_id = node_id.to_instance(inst);
+
+                self.node_labels.insert(node_id, entry[2].to_string());
+            }
+        }
+
+        self.ui_generation += 1;
     }
 
-    #[allow(dead_code)]
     pub fn set_node_colors(&mut self, node_id: NodeId, color: u8) {
         self.node_colors.insert(node_id, color);
+        self.ui_generation += 1;
+        self.sync_to_matrix();
+    }
+
+    /// Sets a custom display name for the node. An empty label
+    /// resets it to the default label of the node.
+    pub fn set_node_label(&mut self, node_id: NodeId, label: &str) {
+        // ',' and ';' are the separators in the "node_labels" property:
+        let label = label.trim().replace(|c| c == ';' || c == ',', " ");
+
+        if label.is_empty() {
+            self.node_labels.remove(&node_id);
+        } else {
+            self.node_labels.insert(node_id, label);
+        }
+
+        self.ui_generation += 1;
+        self.sync_to_matrix();
+    }
+
+    pub fn label_for_node(&self, node_id: NodeId) -> Option<&str> {
+        self.node_labels.get(&node_id).map(|s| &s[..])
     }
 
     pub fn color_for_node(&self, node_id: NodeId) -> u8 {
@@ -103,7 +268,7 @@ impl HexGridModel for MatrixUIModel {
 
     fn get_generation(&self) -> u64 {
         let m = self.matrix.lock().expect("matrix lockable");
-        m.get_generation() as u64
+        m.get_generation() as u64 + self.ui_generation
     }
 
 //    fn cell_click(&self, x: usize, y: usize, btn: MButton, modkey: bool) {
@@ -156,10 +321,40 @@ impl HexGridModel for MatrixUIModel {
             m.get_copy(x, y)?
         };
 
-        let label = cell.label(buf)?;
+        let label =
+            if let Some(custom) = self.label_for_node(cell.node_id()) {
+                use std::io::Write;
+                let mut cur = std::io::Cursor::new(buf);
+                // Labels that do not fit into the buffer are cut off:
+                let _ = write!(cur, "{}", custom);
+                let len = cur.position() as usize;
+                let buf : &'a [u8] = cur.into_inner();
+
+                // Cutting off might have split a multi byte character:
+                match std::str::from_utf8(&buf[0..len]) {
+                    Ok(s)  => s,
+                    Err(e) => std::str::from_utf8(&buf[0..e.valid_up_to()]).unwrap_or(""),
+                }
+            } else {
+                cell.label(buf)?
+            };
+
+        let (silent, clipping) =
+            if self.signal_flow {
+                let mut m = self.matrix.lock().expect("matrix lockable");
+                (Self::cell_is_silent(&mut m, &cell),
+                 cell.node_id().name() == "out"
+                 && Self::cell_receives_clipping(&mut m, &cell))
+            } else {
+                (false, false)
+            };
 
         let hl =
             if self.focus == (x, y) { HexHLight::HLight }
+            else if self.marked_cells.contains(&(x, y)) { HexHLight::Select }
+            else if self.warning_cells.contains(&(x, y)) { HexHLight::Select }
+            else if clipping        { HexHLight::Select }
+            else if silent          { HexHLight::Plain }
             else                    { HexHLight::Normal };
 
         Some(HexCell {
@@ -191,6 +386,20 @@ impl HexGridModel for MatrixUIModel {
 
     fn cell_edge(&self, x: usize, y: usize, edge: HexDir) -> HexEdge {
         let mut m = self.matrix.lock().expect("matrix lockable");
+
+        if self.signal_flow {
+            // All connected output edges show their level, not only
+            // the ones with a cached label:
+            let dir : CellDir = edge.into();
+            if !dir.is_output() { return HexEdge::NoArrow; }
+
+            return
+                match m.get_copy(x, y).and_then(|cell| edge_value(&mut m, &cell, dir)) {
+                    Some(value) => HexEdge::ArrowValue { value },
+                    None        => HexEdge::NoArrow,
+                };
+        }
+
         let cache = self.edge_led_cache.borrow();
 
         if let Some((node_id, out_idx)) = cache.get(&(x, y, edge)) {
@@ -203,7 +412,7 @@ impl HexGridModel for MatrixUIModel {
     }
 
     fn cell_edge_label<'a>(&self, x: usize, y: usize, edge: HexDir, buf: &'a mut [u8]) -> Option<&'a str> {
-        let m = self.matrix.lock().expect("matrix lockable");
+        let mut m = self.matrix.lock().expect("matrix lockable");
         let mut edge_lbl = None;
 
         let matrix_gen = m.get_generation();
@@ -212,12 +421,16 @@ impl HexGridModel for MatrixUIModel {
             *self.edge_led_cache_generation.borrow_mut() = matrix_gen;
         }
 
-        if let Some(cell) = m.get(x, y) {
+        if let Some(cell) = m.get_copy(x, y) {
             let cell_dir = edge.into();
+            let mut lbl_buf : [u8; 64] = [0; 64];
 
-            if let Some((lbl, is_connected)) = m.edge_label(&cell, cell_dir, buf) {
-                edge_lbl = Some(lbl);
+            let clipping =
+                self.signal_flow
+                && edge_value(&mut m, &cell, cell_dir)
+                    .map(is_clipping).unwrap_or(false);
 
+            if let Some((lbl, is_connected)) = m.edge_label(&cell, cell_dir, &mut lbl_buf[..]) {
                 if is_connected {
                     if let Some(out_idx) = cell.local_port_idx(cell_dir) {
                         self.edge_led_cache.borrow_mut().insert(
@@ -225,6 +438,16 @@ impl HexGridModel for MatrixUIModel {
                             (cell.node_id(), out_idx));
                     }
                 }
+
+                let marker = if clipping { "CLIP " } else { "" };
+
+                use std::io::Write;
+                let mut cur = std::io::Cursor::new(buf);
+                if write!(cur, "{}{}", marker, lbl).is_ok() {
+                    let len = cur.position() as usize;
+                    let buf : &'a [u8] = cur.into_inner();
+                    edge_lbl = std::str::from_utf8(&buf[0..len]).ok();
+                }
             }
         }
 
@@ -396,6 +619,106 @@ impl VValUserData for VValHexGridModel {
 
                 Ok(VVal::None)
             },
+            "set_node_color" => {
+                arg_chk!(args, 2, "hex_grid_model.set_node_color[node_id, color_idx]");
+
+                if let HexGridModelType::Matrix(m) = &self.model {
+                    m.borrow_mut().set_node_colors(
+                        vv2node_id(&args[0]), args[1].i() as u8);
+                }
+
+                Ok(VVal::None)
+            },
+            "get_node_color" => {
+                arg_chk!(args, 1, "hex_grid_model.get_node_color[node_id]");
+
+                if let HexGridModelType::Matrix(m) = &self.model {
+                    Ok(VVal::Int(
+                        m.borrow().color_for_node(vv2node_id(&args[0])) as i64))
+                } else {
+                    Ok(VVal::None)
+                }
+            },
+            "set_node_label" => {
+                arg_chk!(args, 2, "hex_grid_model.set_node_label[node_id, label]");
+
+                if let HexGridModelType::Matrix(m) = &self.model {
+                    let label = if args[1].is_none() { "".to_string() } else { args[1].s_raw() };
+                    m.borrow_mut().set_node_label(vv2node_id(&args[0]), &label);
+                }
+
+                Ok(VVal::None)
+            },
+            "get_node_label" => {
+                arg_chk!(args, 1, "hex_grid_model.get_node_label[node_id]");
+
+                if let HexGridModelType::Matrix(m) = &self.model {
+                    Ok(m.borrow()
+                        .label_for_node(vv2node_id(&args[0]))
+                        .map_or(VVal::None, VVal::new_str))
+                } else {
+                    Ok(VVal::None)
+                }
+            },
+            "sync_from_matrix" => {
+                arg_chk!(args, 0, "hex_grid_model.sync_from_matrix[]");
+
+                if let HexGridModelType::Matrix(m) = &self.model {
+                    m.borrow_mut().sync_from_matrix();
+                }
+
+                Ok(VVal::None)
+            },
+            "set_signal_flow" => {
+                arg_chk!(args, 1, "hex_grid_model.set_signal_flow[bool]");
+
+                if let HexGridModelType::Matrix(m) = &self.model {
+                    m.borrow_mut().set_signal_flow(env.arg(0).b());
+                }
+
+                Ok(VVal::None)
+            },
+            "next_frame" => {
+                arg_chk!(args, 0, "hex_grid_model.next_frame[]");
+
+                if let HexGridModelType::Matrix(m) = &self.model {
+                    m.borrow_mut().next_frame();
+                }
+
+                Ok(VVal::None)
+            },
+            "set_marked_cells" => {
+                arg_chk!(args, 1, "hex_grid_model.set_marked_cells[$[$i(x, y), ...]]");
+
+                if let HexGridModelType::Matrix(m) = &self.model {
+                    let mut cells = vec![];
+                    env.arg(0).with_iter(|iter| {
+                        for (pos, _) in iter {
+                            cells.push((pos.v_i(0) as usize, pos.v_i(1) as usize));
+                        }
+                    });
+
+                    m.borrow_mut().set_marked_cells(cells.into_iter());
+                }
+
+                Ok(VVal::None)
+            },
+            "set_warning_cells" => {
+                arg_chk!(args, 1, "hex_grid_model.set_warning_cells[$[$i(x, y), ...]]");
+
+                if let HexGridModelType::Matrix(m) = &self.model {
+                    let mut cells = vec![];
+                    env.arg(0).with_iter(|iter| {
+                        for (pos, _) in iter {
+                            cells.push((pos.v_i(0) as usize, pos.v_i(1) as usize));
+                        }
+                    });
+
+                    m.borrow_mut().set_warning_cells(cells.into_iter());
+                }
+
+                Ok(VVal::None)
+            },
             _ => Ok(VVal::err_msg(&format!("Unknown method called: {}", key))),
         }
     }
@@ -13,17 +13,23 @@ use super::super::VVPatModel;
 use super::super::VVPatEditFb;
 
 use crate::matrix_param_model::KnobParam;
+use crate::auto_route::{AutoRoute, RouteMode, RouteError, MAX_NODE_INSTANCES};
+use crate::patch_lint::{lint_matrix, signal_mismatches, LintDiagnostic};
+use crate::mod_matrix::{mod_entries, ModEntry};
+use crate::pattern_bank::{self, PatternSong, tracker_ids};
 
 use wlambda::*;
 use hexodsp;
 
-use hexodsp::{Matrix, NodeId, Cell, CellDir};
+use hexodsp::{Matrix, NodeId, Cell, CellDir, ParamId, SAtom};
 use hexodsp::matrix::{MatrixError};
 
 use hexotk::DummyParamModel;
 pub use hexotk::PatternEditorFeedback;
+use hexotk::UIPatternModel;
 
 use std::sync::{Arc, Mutex};
+use std::collections::HashSet;
 use std::rc::Rc;
 use std::cell::RefCell;
 
@@ -120,6 +126,109 @@ fn matrix_error2vval_err(err: MatrixError) -> VVal {
         wlambda::vval::SynPos::empty()))))
 }
 
+fn route_error2vval_err(err: RouteError) -> VVal {
+    let err_val =
+        match err {
+            RouteError::NoRoute => VVal::new_sym("no-route"),
+            RouteError::EmptyCell(pos) =>
+                VVal::pair(
+                    VVal::new_sym("empty-cell"),
+                    VVal::ivec2(pos.0 as i64, pos.1 as i64)),
+            RouteError::UnknownOutput(name) =>
+                VVal::pair(VVal::new_sym("unknown-output"), VVal::new_str_mv(name)),
+            RouteError::UnknownInput(name) =>
+                VVal::pair(VVal::new_sym("unknown-input"), VVal::new_str_mv(name)),
+            RouteError::InputAlreadyUsed(name) =>
+                VVal::pair(VVal::new_sym("input-already-used"), VVal::new_str_mv(name)),
+            RouteError::TooManyInstances(name) =>
+                VVal::pair(VVal::new_sym("too-many-instances"), VVal::new_str_mv(name)),
+        };
+
+    VVal::Err(Rc::new(RefCell::new((
+        err_val,
+        wlambda::vval::SynPos::empty()))))
+}
+
+fn lint_diagnostic2vval(diag: &LintDiagnostic) -> VVal {
+    let v = VVal::map3(
+        "kind",
+        VVal::new_sym(diag.kind.as_str()),
+        "pos",
+        diag.pos.map_or(VVal::None, |pos|
+            VVal::ivec2(pos.0 as i64, pos.1 as i64)),
+        "msg",
+        VVal::new_str(&diag.msg));
+
+    if let Some(node_id) = diag.node_id {
+        let _ = v.set_key_str("node_id", node_id2vv(node_id));
+    }
+
+    v
+}
+
+fn mod_entry2vval(entry: &ModEntry) -> VVal {
+    let node_id = entry.param_id.node_id();
+
+    let v = VVal::map3(
+        "param_id", param_id2vv(entry.param_id),
+        "node_id",  node_id2vv(node_id),
+        "name",     VVal::new_str(entry.param_id.name()));
+    let _ = v.set_key_str("amount", VVal::Flt(entry.amount as f64));
+    let _ = v.set_key_str("source",
+        entry.source.map_or(VVal::None, |(src_id, out)|
+            VVal::pair(node_id2vv(src_id), output2vval(src_id, out))));
+    v
+}
+
+fn pattern_song2vval(song: &PatternSong) -> VVal {
+    let chain = VVal::vec();
+    for idx in song.chain.iter() {
+        chain.push(VVal::Int(*idx as i64));
+    }
+
+    let v = VVal::map3(
+        "current",   VVal::Int(song.current as i64),
+        "count",     VVal::Int(song.count as i64),
+        "chain",     chain);
+    let _ = v.set_key_str("chain_pos", VVal::Int(song.chain_pos as i64));
+    let _ = v.set_key_str("song_mode", VVal::Bol(song.song_mode));
+    let _ = v.set_key_str("control",
+        song.control.map_or(VVal::None, |(node_id, out)|
+            VVal::pair(node_id2vv(node_id), output2vval(node_id, out))));
+    v
+}
+
+/// Runs `f` with the pattern of the tracker and syncs the pattern
+/// to the `TSeq` afterwards.
+fn with_tracker_pattern<F>(m: &mut Matrix, tracker_id: usize, f: F) -> VVal
+    where F: FnOnce(&mut Matrix, &mut dyn UIPatternModel) -> Result<VVal, String>
+{
+    let model =
+        match m.get_pattern_data(tracker_id) {
+            Some(model) => model,
+            None => return VVal::err_msg(&format!("No pattern for tracker {}", tracker_id)),
+        };
+
+    let ret =
+        match model.lock() {
+            Ok(mut pat) => f(&mut *m, &mut *pat),
+            Err(_)      => Err("Pattern data can't be locked".to_string()),
+        };
+    m.check_pattern_data(tracker_id);
+
+    ret.unwrap_or_else(|msg| VVal::err_msg(&msg))
+}
+
+fn vv2route_mode(v: &VVal) -> RouteMode {
+    v.with_s_ref(|s| {
+        match s {
+            "pass"     => RouteMode::Pass,
+            "feedback" => RouteMode::Feedback,
+            _          => RouteMode::Auto,
+        }
+    })
+}
+
 fn build_cell_chain(
     matrix: &mut Matrix, mut pos: (i32, i32), dir: CellDir, v: &VVal
 ) -> Vec<((usize, usize), Cell)>
@@ -184,9 +293,144 @@ fn build_cell_chain(
     chain
 }
 
+/// Collects the state that is needed to roll back a `matrix.transaction`.
+///
+/// The cells are snapshotted when the transaction starts, parameters
+/// remember their value before the first change inside the transaction.
+/// Modulation amount changes are deferred until the commit, because setting
+/// them causes a graph sync in the backend.
+struct MatrixTransaction {
+    cells:          Vec<Cell>,
+    /// The values before the transaction, `None` for parameters that had
+    /// no value yet. These are reset to their default on rollback.
+    old_params:     Vec<(ParamId, Option<SAtom>)>,
+    modamts:        Vec<(ParamId, Option<f32>)>,
+}
+
+impl MatrixTransaction {
+    fn new(m: &Matrix) -> Self {
+        let mut cells = vec![];
+        m.for_each(|_x, _y, cell| cells.push(*cell));
+
+        Self {
+            cells,
+            old_params: vec![],
+            modamts:    vec![],
+        }
+    }
+
+    fn set_param(&mut self, m: &mut Matrix, pid: ParamId, at: SAtom) {
+        if !self.old_params.iter().any(|(p, _)| *p == pid) {
+            self.old_params.push((pid, m.get_param(&pid)));
+        }
+
+        m.set_param(pid, at);
+    }
+
+    fn get_param_modamt(&self, pid: &ParamId) -> Option<Option<f32>> {
+        self.modamts.iter().rev()
+            .find(|(p, _)| p == pid)
+            .map(|(_, ma)| *ma)
+    }
+
+    fn rollback(&self, m: &mut Matrix) {
+        for cell in self.cells.iter() {
+            let pos = cell.pos();
+            m.place(pos.0, pos.1, *cell);
+        }
+
+        for (pid, at) in self.old_params.iter().rev() {
+            m.set_param(*pid, at.clone().unwrap_or_else(|| pid.as_atom_def()));
+        }
+    }
+
+    fn commit(&self, m: &mut Matrix) -> Result<(), MatrixError> {
+        let old_modamts : Vec<(ParamId, Option<f32>)> =
+            self.modamts.iter()
+                .map(|(pid, _)| (*pid, m.get_param_modamt(pid)))
+                .collect();
+
+        for (pid, ma) in self.modamts.iter() {
+            if let Err(e) = m.set_param_modamt(*pid, *ma) {
+                for (pid, ma) in old_modamts.iter().rev() {
+                    let _ = m.set_param_modamt(*pid, *ma);
+                }
+                return Err(e);
+            }
+        }
+
+        m.sync()
+    }
+}
+
 #[derive(Clone)]
 pub struct VValMatrix {
     matrix: Arc<Mutex<hexodsp::Matrix>>,
+    txn:    Rc<RefCell<Option<MatrixTransaction>>>,
+    /// The samples that could not be loaded with the last patch.
+    missing_samples: Rc<RefCell<Vec<(String, String)>>>,
+}
+
+impl VValMatrix {
+    fn set_param(&self, m: &mut Matrix, pid: ParamId, at: SAtom) {
+        if let Some(txn) = self.txn.borrow_mut().as_mut() {
+            txn.set_param(m, pid, at);
+        } else {
+            m.set_param(pid, at);
+        }
+    }
+
+    fn transaction(&self, f: VVal, env: &mut Env) -> Result<VVal, StackAction> {
+        let self_vv = VVal::new_usr(self.clone());
+
+        // Nested transactions are merged into the outermost one:
+        if self.txn.borrow().is_some() {
+            return f.call(env, &[self_vv]);
+        }
+
+        if let Ok(m) = self.matrix.lock() {
+            *self.txn.borrow_mut() = Some(MatrixTransaction::new(&m));
+        } else {
+            return Ok(VVal::err_msg("Can't lock matrix!"));
+        }
+
+        let ret = f.call(env, &[self_vv]);
+
+        let txn = self.txn.borrow_mut().take();
+        let txn =
+            if let Some(txn) = txn { txn }
+            else { return ret; };
+
+        let mut m =
+            if let Ok(m) = self.matrix.lock() { m }
+            else { return Ok(VVal::err_msg("Can't lock matrix!")) };
+
+        match ret {
+            Ok(v) if !v.is_err() => {
+                if let Err(e) = m.check() {
+                    txn.rollback(&mut m);
+                    return Ok(matrix_error2vval_err(e));
+                }
+
+                match txn.commit(&mut m) {
+                    Ok(_)  => Ok(VVal::Bol(true)),
+                    Err(e) => {
+                        txn.rollback(&mut m);
+                        let _ = m.sync();
+                        Ok(matrix_error2vval_err(e))
+                    },
+                }
+            },
+            Ok(v) => {
+                txn.rollback(&mut m);
+                Ok(v)
+            },
+            Err(e) => {
+                txn.rollback(&mut m);
+                Err(e)
+            },
+        }
+    }
 }
 
 impl vval::VValUserData for VValMatrix {
@@ -263,6 +507,12 @@ impl vval::VValUserData for VValMatrix {
                     return Ok(VVal::None);
                 }
             }
+            "transaction" => {
+                arg_chk!(args, 1, "matrix.transaction[function]");
+
+                let f = args[0].clone();
+                return self.transaction(f, env);
+            }
             "create_graph_minmax_monitor" => {
                 arg_chk!(args, 1, "matrix.create_graph_minmax_monitor[index]");
 
@@ -271,6 +521,49 @@ impl vval::VValUserData for VValMatrix {
                     VGraphMinMaxModel::new_monitor_model(
                         matrix, args[0].i() as usize)));
             }
+            "create_scope" => {
+                arg_chk!(args, 0, "matrix.create_scope[]");
+
+                return Ok(VVal::new_usr(VScope::new(self.matrix.clone())));
+            }
+            "create_sample_editor" => {
+                arg_chk!(args, 1, "matrix.create_sample_editor[node_id]");
+
+                return Ok(VVal::new_usr(VSampleEditor::new(
+                    self.matrix.clone(), vv2node_id(&args[0]))));
+            }
+            "create_spectrum_model" => {
+                if args.len() < 2 || args.len() > 3 {
+                    return Err(StackAction::panic_msg(
+                        "matrix.create_spectrum_model[:out | :in | :mon, channel, fft_size] \
+                         called with wrong number of arguments".to_string()));
+                }
+
+                let channel = args[1].i().max(0) as usize;
+                let source =
+                    if let Some(src) = args[0].with_s_ref(|s| ScopeSource::from_str(s, channel)) {
+                        src
+                    } else {
+                        return Ok(VVal::err_msg(
+                            &format!("Unknown spectrum source: {}", args[0].s())));
+                    };
+
+                let fft_size =
+                    if args.len() > 2 {
+                        let size = args[2].i().max(0) as usize;
+                        if !size.is_power_of_two() || !(16..=8192).contains(&size) {
+                            return Ok(VVal::err_msg(
+                                &format!("FFT size must be a power of two \
+                                          between 16 and 8192: {}", size)));
+                        }
+                        Some(size)
+                    } else {
+                        None
+                    };
+
+                return Ok(VVal::new_usr(
+                    VSpectrumModel::new(self.matrix.clone(), source, fft_size)));
+            }
             _ => {}
         }
 
@@ -293,7 +586,7 @@ impl vval::VValUserData for VValMatrix {
                         Ok(VVal::None)
                     }
                 },
-                "set" => {
+                "set" | "place" => {
                     arg_chk!(args, 2, "matrix.set[$i(x, y), cell]");
 
                     if let (Some(vv_cell), Some(pos)) = (env.arg_ref(1), env.arg_ref(0)) {
@@ -332,24 +625,50 @@ impl vval::VValUserData for VValMatrix {
                                     node_id.inp_param(&v.v_s_raw(0))
                                 {
                                     if let VVal::Flt(denorm) = v.v_(1) {
-                                        m.set_param(pid,
+                                        self.set_param(&mut m, pid,
                                             hexodsp::SAtom::param(
                                                 pid.norm(denorm as f32)));
                                     } else {
-                                        m.set_param(pid, vv2atom(v.v_(1)));
+                                        self.set_param(&mut m, pid, vv2atom(v.v_(1)));
                                     }
                                 }
                             }
                         });
 
                         m.place(pos.0, pos.1, cell);
-                        println!("PLACE: {:?} => {:?}", pos, cell);
                     }
 
-//                    println!("CHAIN: {:?}", chain);
-
                     Ok(VVal::None)
                 },
+                "find_route" => {
+                    arg_chk!(args, 5,
+                        "matrix.find_route[src_pos, out_name, dst_pos, in_name, mode]");
+
+                    let src = (args[0].v_i(0) as usize, args[0].v_i(1) as usize);
+                    let dst = (args[2].v_i(0) as usize, args[2].v_i(1) as usize);
+                    let out_name =
+                        if args[1].is_none() { None } else { Some(args[1].s_raw()) };
+                    let in_name =
+                        if args[3].is_none() { None } else { Some(args[3].s_raw()) };
+
+                    let cells =
+                        AutoRoute::new(
+                            &m,
+                            src, out_name.as_deref(),
+                            dst, in_name.as_deref())
+                        .and_then(|route| route.route(&m, vv2route_mode(&args[4])));
+
+                    match cells {
+                        Ok(cells) => {
+                            let v = VVal::vec();
+                            for cell in cells.iter() {
+                                v.push(cell2vval(cell));
+                            }
+                            Ok(v)
+                        },
+                        Err(e) => Ok(route_error2vval_err(e)),
+                    }
+                },
                 "set_param" => {
                     arg_chk!(args, 2, "matrix.set_param[param_id, atom or value]");
 
@@ -357,7 +676,7 @@ impl vval::VValUserData for VValMatrix {
                     let at  = vv2atom(env.arg(1));
 
                     if let Some(pid) = pid {
-                        m.set_param(pid, at);
+                        self.set_param(&mut m, pid, at);
                         Ok(VVal::Bol(true))
                     } else {
                         Ok(VVal::None)
@@ -384,7 +703,14 @@ impl vval::VValUserData for VValMatrix {
                     let pid = vv2param_id(env.arg(0));
 
                     if let Some(pid) = pid {
-                        if let Some(ma) = m.get_param_modamt(&pid) {
+                        let pending =
+                            self.txn.borrow().as_ref()
+                                .and_then(|txn| txn.get_param_modamt(&pid));
+                        let ma =
+                            if let Some(ma) = pending { ma }
+                            else { m.get_param_modamt(&pid) };
+
+                        if let Some(ma) = ma {
                             Ok(VVal::opt(VVal::Flt(ma as f64)))
                         } else {
                             Ok(VVal::opt_none())
@@ -405,6 +731,11 @@ impl vval::VValUserData for VValMatrix {
                             if ma.is_some() { Some(ma.f() as f32) }
                             else { None };
 
+                        if let Some(txn) = self.txn.borrow_mut().as_mut() {
+                            txn.modamts.push((pid, ma));
+                            return Ok(VVal::Bol(true));
+                        }
+
                         match m.set_param_modamt(pid, ma) {
                             Ok(_)  => Ok(VVal::Bol(true)),
                             Err(e) => Ok(matrix_error2vval_err(e)),
@@ -413,6 +744,15 @@ impl vval::VValUserData for VValMatrix {
                         Ok(VVal::None)
                     }
                 },
+                "mod_entries" => {
+                    arg_chk!(args, 0, "matrix.mod_entries[]");
+
+                    let ret = VVal::vec();
+                    for entry in mod_entries(&m).iter() {
+                        ret.push(mod_entry2vval(entry));
+                    }
+                    Ok(ret)
+                },
                 "param_input_is_used" => {
                     arg_chk!(args, 1, "matrix.param_input_is_used[param_id]");
                     if let Some(pid) = vv2param_id(env.arg(0)) {
@@ -439,6 +779,31 @@ impl vval::VValUserData for VValMatrix {
                         Err(e) => Ok(matrix_error2vval_err(e)),
                     }
                 },
+                "remove_unused_sample_points" => {
+                    arg_chk!(args, 0, "matrix.remove_unused_sample_points[]");
+
+                    Ok(VVal::Bol(remove_unused_sample_points(&mut m)))
+                },
+                "lint" => {
+                    arg_chk!(args, 0, "matrix.lint[]");
+
+                    let v = VVal::vec();
+                    for diag in lint_matrix(&mut m).iter() {
+                        v.push(lint_diagnostic2vval(diag));
+                    }
+
+                    Ok(v)
+                },
+                "signal_mismatches" => {
+                    arg_chk!(args, 0, "matrix.signal_mismatches[]");
+
+                    let v = VVal::vec();
+                    for diag in signal_mismatches(&m).iter() {
+                        v.push(lint_diagnostic2vval(diag));
+                    }
+
+                    Ok(v)
+                },
                 "load_patch" => {
                     arg_chk!(args, 1, "matrix.load_patch[filepath]");
 
@@ -451,15 +816,44 @@ impl vval::VValUserData for VValMatrix {
                         },
                     }
 
+                    *self.missing_samples.borrow_mut() =
+                        crate::sample_library::resolve_samples(
+                            &mut m, &env.arg(0).s_raw(),
+                            Some(crate::audio_tap::sample_rate()));
+
+                    pattern_bank::songs_changed(&mut m);
+
                     match m.sync() {
                         Ok(_)  => Ok(VVal::Bol(true)),
                         Err(e) => Ok(matrix_error2vval_err(e)),
                     }
                 },
                 "save_patch" => {
-                    arg_chk!(args, 1, "matrix.save_patch[filepath]");
+                    if args.len() < 1 || args.len() > 2 {
+                        return Err(StackAction::panic_msg(
+                            "matrix.save_patch[filepath, :path | :bundle | :embed] \
+                             called with wrong number of arguments".to_string()));
+                    }
 
                     use hexodsp::matrix_repr::save_patch_to_file;
+                    use crate::sample_library::{store_samples, SampleStorage};
+
+                    let storage =
+                        if args.len() > 1 {
+                            match args[1].with_s_ref(SampleStorage::from_str) {
+                                Some(storage) => storage,
+                                None => {
+                                    return Ok(VVal::err_msg(&format!(
+                                        "Unknown sample storage: {}", args[1].s())));
+                                },
+                            }
+                        } else {
+                            SampleStorage::Path
+                        };
+
+                    if let Err(e) = store_samples(&mut m, &env.arg(0).s_raw(), storage) {
+                        return Ok(VVal::err_msg(&e));
+                    }
 
                     match save_patch_to_file(&mut m, &env.arg(0).s_raw()) {
                         Ok(_) => {
@@ -475,9 +869,24 @@ impl vval::VValUserData for VValMatrix {
                         },
                     }
                 },
+                "missing_samples" => {
+                    arg_chk!(args, 0, "matrix.missing_samples[]");
+
+                    let ret = VVal::vec();
+                    for (path, reason) in self.missing_samples.borrow().iter() {
+                        ret.push(VVal::pair(
+                            VVal::new_str(path), VVal::new_str(reason)));
+                    }
+                    Ok(ret)
+                },
                 "sync" => {
                     arg_chk!(args, 0, "matrix.sync[]");
 
+                    // The transaction syncs once when it's committed:
+                    if self.txn.borrow().is_some() {
+                        return Ok(VVal::Bol(true));
+                    }
+
                     match m.sync() {
                         Ok(_)  => Ok(VVal::Bol(true)),
                         Err(e) => Ok(matrix_error2vval_err(e)),
@@ -486,7 +895,15 @@ impl vval::VValUserData for VValMatrix {
                 "clear" => {
                     arg_chk!(args, 0, "matrix.clear[]");
 
+                    // The transaction only snapshots the cells and the changed
+                    // parameters, it can't restore everything `clear` resets:
+                    if self.txn.borrow().is_some() {
+                        return Ok(VVal::err_msg(
+                            "matrix.clear[] can't be called inside a transaction"));
+                    }
+
                     m.clear();
+                    pattern_bank::songs_changed(&mut m);
                     Ok(VVal::Bol(true))
                 },
                 "monitored_cell" => {
@@ -494,6 +911,27 @@ impl vval::VValUserData for VValMatrix {
 
                     Ok(cell2vval(m.monitored_cell()))
                 },
+                "out_fb_value" => {
+                    arg_chk!(args, 2, "matrix.out_fb_value[node_id, out_name]");
+
+                    let node_id = vv2node_id(&args[0]);
+                    let out_idx = args[1].with_s_ref(|s| node_id.out(s));
+
+                    if let Some(out_idx) = out_idx {
+                        let (value, filtered) =
+                            m.filtered_out_fb_for(&node_id, out_idx);
+                        Ok(VVal::pair(
+                            VVal::Flt(value as f64),
+                            VVal::Flt(filtered as f64)))
+                    } else {
+                        Ok(VVal::None)
+                    }
+                },
+                "phase_value" => {
+                    arg_chk!(args, 1, "matrix.phase_value[node_id]");
+
+                    Ok(VVal::Flt(m.phase_value_for(&vv2node_id(&args[0])) as f64))
+                },
                 "monitor_cell" => {
                     arg_chk!(args, 1, "matrix.monitor_cell[cell]");
 
@@ -512,7 +950,22 @@ impl vval::VValUserData for VValMatrix {
                     arg_chk!(args, 1, "matrix.get_unused_instance_node_id[node_id]");
 
                     let node_id = vv2node_id(&args[0]);
-                    let node_id = m.get_unused_instance_node_id(node_id);
+                    let mut node_id = m.get_unused_instance_node_id(node_id);
+
+                    // The backend only knows about synced nodes, inside a
+                    // transaction we also need to skip the instances that
+                    // were placed but not synced yet.
+                    let mut used = HashSet::new();
+                    m.for_each(|_x, _y, cell| { used.insert(cell.node_id()); });
+                    while used.contains(&node_id) {
+                        if node_id.instance() + 1 >= MAX_NODE_INSTANCES {
+                            return Ok(route_error2vval_err(
+                                RouteError::TooManyInstances(
+                                    node_id.name().to_string())));
+                        }
+                        node_id = node_id.to_instance(node_id.instance() + 1);
+                    }
+
                     Ok(node_id2vv(node_id))
                 },
                 "create_pattern_data_model" => {
@@ -530,6 +983,111 @@ impl vval::VValUserData for VValMatrix {
                     m.check_pattern_data(args[0].i() as usize);
                     Ok(VVal::None)
                 }
+                "pattern_song" => {
+                    arg_chk!(args, 1, "matrix.pattern_song[tracker_id]");
+
+                    Ok(pattern_song2vval(
+                        &pattern_bank::load_song(&mut m, args[0].i().max(0) as usize)))
+                },
+                "select_pattern" => {
+                    arg_chk!(args, 2, "matrix.select_pattern[tracker_id, pattern_idx]");
+
+                    let tracker_id = args[0].i().max(0) as usize;
+                    let idx        = args[1].i().max(0) as usize;
+
+                    Ok(with_tracker_pattern(&mut m, tracker_id, |m, pat| {
+                        pattern_bank::select_pattern(m, tracker_id, pat, idx)
+                            .map(|song| pattern_song2vval(&song))
+                    }))
+                },
+                "set_pattern_chain" => {
+                    arg_chk!(args, 2, "matrix.set_pattern_chain[tracker_id, $[pattern_idx, ...]]");
+
+                    let tracker_id = args[0].i().max(0) as usize;
+                    let mut song   = pattern_bank::load_song(&mut m, tracker_id);
+
+                    let mut idxs = vec![];
+                    args[1].with_iter(|iter| {
+                        for (v, _) in iter { idxs.push(v.i()); }
+                    });
+
+                    let mut chain = vec![];
+                    for idx in idxs.into_iter() {
+                        if idx < 0 || idx as usize >= song.count {
+                            return Ok(VVal::err_msg(&format!(
+                                "Pattern {} not in the bank of {} patterns", idx, song.count)));
+                        }
+                        chain.push(idx as usize);
+                    }
+
+                    song.chain     = chain;
+                    song.chain_pos = 0;
+                    pattern_bank::save_song(&mut m, tracker_id, &song);
+                    Ok(pattern_song2vval(&song))
+                },
+                "set_pattern_song_mode" => {
+                    arg_chk!(args, 2, "matrix.set_pattern_song_mode[tracker_id, bool]");
+
+                    let tracker_id = args[0].i().max(0) as usize;
+                    let mut song   = pattern_bank::load_song(&mut m, tracker_id);
+                    song.song_mode = args[1].b();
+                    song.chain_pos = 0;
+                    pattern_bank::save_song(&mut m, tracker_id, &song);
+
+                    // The song starts with the first pattern of the chain:
+                    if song.song_mode && !song.chain.is_empty() {
+                        let first = song.chain[0];
+                        return Ok(with_tracker_pattern(&mut m, tracker_id, |m, pat| {
+                            pattern_bank::select_pattern(m, tracker_id, pat, first)
+                                .map(|song| pattern_song2vval(&song))
+                        }));
+                    }
+
+                    Ok(pattern_song2vval(&song))
+                },
+                "set_pattern_control" => {
+                    if args.len() < 2 || args.len() > 3 {
+                        return Err(StackAction::panic_msg(
+                            "matrix.set_pattern_control[tracker_id, node_id, out_name] \
+                             called with wrong number of arguments".to_string()));
+                    }
+
+                    let tracker_id = args[0].i().max(0) as usize;
+                    let mut song   = pattern_bank::load_song(&mut m, tracker_id);
+
+                    song.control =
+                        if args[1].is_none() {
+                            None
+                        } else {
+                            let node_id = vv2node_id(&args[1]);
+                            let out =
+                                match args.get(2) {
+                                    Some(name) if name.is_some() =>
+                                        name.with_s_ref(|s| node_id.out(s)),
+                                    _ => Some(0),
+                                };
+
+                            match out {
+                                Some(out) => Some((node_id, out)),
+                                None => {
+                                    return Ok(VVal::err_msg(&format!(
+                                        "Unknown output {} of {}", args[2].s(), node_id.name())));
+                                },
+                            }
+                        };
+
+                    pattern_bank::save_song(&mut m, tracker_id, &song);
+                    Ok(pattern_song2vval(&song))
+                },
+                "tracker_ids" => {
+                    arg_chk!(args, 0, "matrix.tracker_ids[]");
+
+                    let ret = VVal::vec();
+                    for tracker_id in tracker_ids(&m).into_iter() {
+                        ret.push(VVal::Int(tracker_id as i64));
+                    }
+                    Ok(ret)
+                },
                 "create_pattern_feedback_model" => {
                     arg_chk!(args, 1, "matrix.create_pattern_feedback_model[node_id]");
 
@@ -827,6 +1385,15 @@ impl vval::VValUserData for VValCluster {
     fn clone_ud(&self) -> Box<dyn vval::VValUserData> { Box::new(self.clone()) }
 }
 
-pub fn matrix2vv(matrix: Arc<Mutex<Matrix>>) -> VVal {
-    VVal::new_usr(VValMatrix { matrix })
+/// Wraps the matrix for WLambda. `missing_samples` are the samples
+/// of the patch in the matrix, that could not be loaded.
+pub fn matrix2vv(
+    matrix: Arc<Mutex<Matrix>>,
+    missing_samples: Vec<(String, String)>) -> VVal
+{
+    VVal::new_usr(VValMatrix {
+        matrix,
+        txn: Rc::new(RefCell::new(None)),
+        missing_samples: Rc::new(RefCell::new(missing_samples)),
+    })
 }
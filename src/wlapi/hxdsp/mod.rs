@@ -11,6 +11,9 @@ pub mod grid_model;
 pub mod octave_keys;
 pub mod graph;
 pub mod graph_minmax;
+pub mod scope;
+pub mod spectrum;
+pub mod sample_editor;
 
 pub use node_info::*;
 pub use param::*;
@@ -21,10 +24,14 @@ pub use grid_model::*;
 pub use octave_keys::*;
 pub use graph::*;
 pub use graph_minmax::*;
+pub use scope::*;
+pub use spectrum::*;
+pub use sample_editor::*;
 
 use wlambda::*;
 use hexodsp::{NodeId};
 use hexodsp::dsp::{UICategory};
+use crate::signal_type::SignalType;
 
 pub fn vv2node_id(v: &VVal) -> NodeId {
     let node_id = v.v_(0).with_s_ref(|s| NodeId::from_str(s));
@@ -174,17 +181,31 @@ pub fn setup_node_id_module() -> wlambda::SymbolTable {
                 i += 1;
             }
 
-            let inputs = VVal::vec();
+            let inputs      = VVal::vec();
+            let input_types = VVal::vec();
             let mut i = 0;
             while let Some(param) = nid.inp_param_by_idx(i) {
                 inputs.push(param_id2vv(param));
+                input_types.push(VVal::new_sym(
+                    SignalType::for_input(nid, param.name()).as_str()));
                 i += 1;
             }
 
-            Ok(VVal::map2(
-                "atoms",  atoms,
-                "inputs", inputs,
-            ))
+            let outputs = VVal::vec();
+            let mut i = 0;
+            while let Some(name) = nid.out_name_by_idx(i) {
+                outputs.push(VVal::pair(
+                    VVal::new_str(name),
+                    VVal::new_sym(SignalType::for_output(nid, name).as_str())));
+                i += 1;
+            }
+
+            let ret = VVal::map3(
+                "atoms",       atoms,
+                "inputs",      inputs,
+                "input_types", input_types);
+            let _ = ret.set_key_str("outputs", outputs);
+            Ok(ret)
         }, Some(1), Some(1), false);
 
     st.fun(
@@ -194,9 +215,10 @@ pub fn setup_node_id_module() -> wlambda::SymbolTable {
             let outputs = VVal::vec();
             let mut i = 0;
             while let Some(name) = nid.out_name_by_idx(i) {
-                outputs.push(VVal::pair(
+                outputs.push(VVal::vec3(
                     VVal::Int(i as i64),
-                    VVal::new_str(name)));
+                    VVal::new_str(name),
+                    VVal::new_sym(SignalType::for_output(nid, name).as_str())));
                 i += 1;
             }
 
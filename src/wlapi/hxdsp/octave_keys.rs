@@ -2,6 +2,7 @@
 // This file is a part of HexoSynth. Released under GPL-3.0-or-later.
 // See README.md and COPYING for details.
 
+use crate::arg_chk;
 use wlambda::*;
 use hexodsp::{Matrix, NodeId, ParamId, SAtom};
 use hexotk::OctaveKeysModel;
@@ -40,16 +41,65 @@ impl OctaveKeysModel for OctaveKeysNodeData {
     }
 }
 
+/// Keys for entering notes: A click on a key is remembered until it
+/// is popped, the last clicked key stays lit.
+#[derive(Debug, Clone, Default)]
+struct NoteKeysData {
+    mask:       i64,
+    pressed:    Vec<usize>,
+    generation: u64,
+}
+
+impl OctaveKeysModel for NoteKeysData {
+    fn key_mask(&self) -> i64 { self.mask }
+
+    fn phase_value(&self) -> f64 { 0.0 }
+
+    fn get_generation(&self) -> u64 { self.generation }
+
+    fn change(&mut self, new_mask: i64) {
+        // The widget toggles the clicked key, so both a key that was
+        // switched on and the lit key that was switched off are clicks:
+        let toggled = new_mask ^ self.mask;
+
+        for key in 0..12 {
+            if toggled & (1 << key) != 0 {
+                self.pressed.push(key);
+                self.mask = 1 << key;
+            }
+        }
+
+        self.generation += 1;
+    }
+}
+
 #[derive(Clone)]
-pub struct VOctaveKeysModel(Rc<RefCell<dyn OctaveKeysModel>>);
+pub struct VOctaveKeysModel {
+    model:     Rc<RefCell<dyn OctaveKeysModel>>,
+    note_keys: Option<Rc<RefCell<NoteKeysData>>>,
+}
 
 impl VOctaveKeysModel {
     pub fn new(matrix: Arc<Mutex<Matrix>>, param_id: ParamId) -> Self {
-        Self(Rc::new(RefCell::new(OctaveKeysNodeData {
-            matrix,
-            node_id: param_id.node_id(),
-            param_id,
-        })))
+        Self {
+            model: Rc::new(RefCell::new(OctaveKeysNodeData {
+                matrix,
+                node_id: param_id.node_id(),
+                param_id,
+            })),
+            note_keys: None,
+        }
+    }
+
+    /// Creates keys, that are not bound to a parameter. The clicked
+    /// keys are read with the `pop_keys` method.
+    pub fn new_note_keys() -> Self {
+        let note_keys = Rc::new(RefCell::new(NoteKeysData::default()));
+
+        Self {
+            model:     note_keys.clone(),
+            note_keys: Some(note_keys),
+        }
     }
 }
 
@@ -58,10 +108,23 @@ impl VValUserData for VOctaveKeysModel {
     fn as_any(&mut self) -> &mut dyn std::any::Any { self }
     fn clone_ud(&self) -> Box<dyn vval::VValUserData> { Box::new(self.clone()) }
 
-    fn call_method(&self, _key: &str, _env: &mut Env)
+    fn call_method(&self, key: &str, env: &mut Env)
         -> Result<VVal, StackAction>
     {
-//        let args = env.argv_ref();
+        let args = env.argv_ref();
+
+        if key == "pop_keys" {
+            arg_chk!(args, 0, "octave_keys_model.pop_keys[]");
+
+            let ret = VVal::vec();
+            if let Some(note_keys) = &self.note_keys {
+                for key in note_keys.borrow_mut().pressed.drain(..) {
+                    ret.push(VVal::Int(key as i64));
+                }
+            }
+            return Ok(ret);
+        }
+
         Ok(VVal::None)
 
 //        match key {
@@ -117,5 +180,5 @@ impl VValUserData for VOctaveKeysModel {
 }
 
 pub fn vv2octave_keys_model(mut v: VVal) -> Option<Rc<RefCell<dyn OctaveKeysModel>>> {
-    v.with_usr_ref(|model: &mut VOctaveKeysModel| model.0.clone())
+    v.with_usr_ref(|model: &mut VOctaveKeysModel| model.model.clone())
 }
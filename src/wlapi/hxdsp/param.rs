@@ -6,6 +6,7 @@ use crate::arg_chk;
 use super::{vv2node_id, node_id2vv, atom2vv};
 use wlambda::*;
 use hexodsp::{ParamId};
+use crate::signal_type::SignalType;
 
 #[derive(Clone)]
 pub struct VValParamId {
@@ -39,6 +40,18 @@ impl VValUserData for VValParamId {
 
                 Ok(VVal::new_str(self.param.name()))
             },
+            "signal_type" => {
+                arg_chk!(args, 0, "param_id.signal_type[]");
+
+                if self.param.is_atom() {
+                    Ok(VVal::None)
+                } else {
+                    Ok(VVal::new_sym(
+                        SignalType::for_input(
+                            self.param.node_id(),
+                            self.param.name()).as_str()))
+                }
+            },
             "setting_min_max" => {
                 arg_chk!(args, 0, "param_id.setting_min_max[]");
 
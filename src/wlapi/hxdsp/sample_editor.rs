@@ -0,0 +1,500 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::arg_chk;
+use wlambda::*;
+use hexodsp::{Matrix, NodeId, SAtom};
+use hexotk::{GraphMinMaxModel, GraphModel};
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+
+use super::{VGraphMinMaxModel, VGraphModel};
+
+/// The smallest part of the sample that can be zoomed in on, in frames.
+const MIN_VIEW_LEN : usize = 64;
+
+/// The positions in the sample, normalized to 0.0 (first frame)
+/// to 1.0 (last frame).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplePoints {
+    pub start:      f32,
+    pub end:        f32,
+    pub loop_start: f32,
+    pub loop_end:   f32,
+}
+
+impl Default for SamplePoints {
+    fn default() -> Self {
+        Self { start: 0.0, end: 1.0, loop_start: 0.0, loop_end: 1.0 }
+    }
+}
+
+impl SamplePoints {
+    fn get(&self, name: &str) -> Option<f32> {
+        match name {
+            "start"      => Some(self.start),
+            "end"        => Some(self.end),
+            "loop_start" => Some(self.loop_start),
+            "loop_end"   => Some(self.loop_end),
+            _            => None,
+        }
+    }
+
+    /// Sets the point `name` and moves the other points, so that
+    /// start <= loop_start <= loop_end <= end still holds.
+    fn set(&mut self, name: &str, pos: f32) -> bool {
+        let pos = pos.clamp(0.0, 1.0);
+
+        match name {
+            "start" => {
+                self.start      = pos;
+                self.end        = self.end.max(pos);
+                self.loop_start = self.loop_start.max(pos);
+                self.loop_end   = self.loop_end.max(self.loop_start);
+            },
+            "end" => {
+                self.end        = pos;
+                self.start      = self.start.min(pos);
+                self.loop_end   = self.loop_end.min(pos);
+                self.loop_start = self.loop_start.min(self.loop_end);
+            },
+            "loop_start" => {
+                self.loop_start = pos.clamp(self.start, self.end);
+                self.loop_end   = self.loop_end.max(self.loop_start);
+            },
+            "loop_end" => {
+                self.loop_end   = pos.clamp(self.start, self.end);
+                self.loop_start = self.loop_start.min(self.loop_end);
+            },
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+/// Edits the start, end and loop points of the sample loaded into
+/// a `Sampl` node. The start and end are stored in the `offs` and `len`
+/// parameters of the node, so they change what is played.
+///
+/// The `Sampl` node has no parameters for a loop inside the played region,
+/// in its loop mode it repeats the whole region from start to end.
+/// The loop points are therefore only markers: They are stored per node
+/// in the "sample_points" property of the matrix, so that they are saved
+/// with the patch, but they don't change the playback.
+pub struct SampleEditor {
+    matrix:     Arc<Mutex<Matrix>>,
+    node_id:    NodeId,
+    /// The mono samples, without the leading sample rate.
+    data:       Vec<f32>,
+    sample_rate: f32,
+    points:     SamplePoints,
+    view_start: usize,
+    view_len:   usize,
+    generation: u64,
+}
+
+impl SampleEditor {
+    pub fn new(matrix: Arc<Mutex<Matrix>>, node_id: NodeId) -> Self {
+        let mut se = Self {
+            matrix,
+            node_id,
+            data:        vec![],
+            sample_rate: 44100.0,
+            points:      SamplePoints::default(),
+            view_start:  0,
+            view_len:    0,
+            generation:  0,
+        };
+        se.reload();
+        se
+    }
+
+    /// Reads the sample and the points of the node from the matrix.
+    pub fn reload(&mut self) {
+        self.data.clear();
+        self.points = SamplePoints::default();
+
+        if let Ok(mut m) = self.matrix.lock() {
+            let sample =
+                self.node_id.inp_param("sample").and_then(|p| m.get_param(&p));
+
+            if let Some(SAtom::AudioSample((_, Some(data)))) = sample {
+                if data.len() > 1 {
+                    self.sample_rate = data[0];
+                    self.data.extend_from_slice(&data[1..]);
+                }
+            }
+
+            let offs = self.node_id.inp_param("offs").and_then(|p| m.get_param(&p));
+            let len  = self.node_id.inp_param("len").and_then(|p| m.get_param(&p));
+
+            if let (Some(offs), Some(len)) = (offs, len) {
+                self.points.start = offs.f().clamp(0.0, 1.0);
+                self.points.end   = (offs.f() + len.f()).clamp(self.points.start, 1.0);
+            }
+
+            let (ls, le) =
+                loop_points_from_prop(&mut m, self.node_id)
+                    .unwrap_or((self.points.start, self.points.end));
+            self.points.set("loop_start", ls);
+            self.points.set("loop_end",   le);
+        }
+
+        self.reset_view();
+    }
+
+    fn write_points(&mut self) {
+        let mut m = self.matrix.lock().expect("matrix lockable");
+
+        if let Some(pid) = self.node_id.inp_param("offs") {
+            m.set_param(pid, SAtom::param(self.points.start));
+        }
+        if let Some(pid) = self.node_id.inp_param("len") {
+            m.set_param(pid, SAtom::param(self.points.end - self.points.start));
+        }
+
+        let mut entries = vec![];
+        if let Some(SAtom::Str(s)) = m.get_prop("sample_points") {
+            for entry in s.split(';') {
+                if !entry.is_empty()
+                   && parse_loop_entry(entry).map(|e| e.0) != Some(self.node_id)
+                {
+                    entries.push(entry.to_string());
+                }
+            }
+        }
+
+        entries.push(format!("{},{},{},{}",
+            self.node_id.name(),
+            self.node_id.instance(),
+            self.points.loop_start,
+            self.points.loop_end));
+
+        m.set_prop("sample_points", SAtom::str(&entries.join(";")));
+    }
+
+    pub fn set_point(&mut self, name: &str, pos: f32) -> bool {
+        if !self.points.set(name, pos) { return false; }
+
+        self.write_points();
+        self.generation += 1;
+        true
+    }
+
+    fn frames(&self) -> usize { self.data.len().max(1) }
+
+    pub fn reset_view(&mut self) {
+        self.view_start = 0;
+        self.view_len   = self.frames();
+        self.generation += 1;
+    }
+
+    /// Sets the visible part of the sample, `start` and `len` are
+    /// normalized to the length of the sample.
+    pub fn set_view(&mut self, start: f32, len: f32) {
+        let frames    = self.frames();
+        let min_len   = MIN_VIEW_LEN.min(frames);
+        self.view_len = ((len.max(0.0) * frames as f32) as usize).clamp(min_len, frames);
+
+        let start       = (start.max(0.0) * frames as f32) as usize;
+        self.view_start = start.min(frames - self.view_len);
+        self.generation += 1;
+    }
+
+    /// Zooms in (`factor` > 1.0) or out around the center of the view.
+    pub fn zoom_by(&mut self, factor: f32) {
+        if factor <= 0.0 { return; }
+
+        let frames = self.frames() as f32;
+        let center = (self.view_start as f32 + self.view_len as f32 * 0.5) / frames;
+        let len    = (self.view_len as f32 / factor) / frames;
+
+        self.set_view(center - len * 0.5, len);
+    }
+
+    /// Moves the view by `delta` times the view length.
+    pub fn pan_by(&mut self, delta: f32) {
+        let frames = self.frames() as f32;
+        let start  = (self.view_start as f32 + delta * self.view_len as f32) / frames;
+
+        self.set_view(start, self.view_len as f32 / frames);
+    }
+
+    /// Maps a position in the view (0.0 to 1.0) to a sample position.
+    pub fn view2pos(&self, x: f32) -> f32 {
+        (self.view_start as f32 + x * self.view_len as f32) / self.frames() as f32
+    }
+
+    /// Maps a sample position to the view, `None` if it is not visible.
+    pub fn pos2view(&self, pos: f32) -> Option<f32> {
+        let x = (pos * self.frames() as f32 - self.view_start as f32)
+                / self.view_len.max(1) as f32;
+        if (0.0..=1.0).contains(&x) { Some(x) } else { None }
+    }
+}
+
+fn parse_loop_entry(entry: &str) -> Option<(NodeId, f32, f32)> {
+    let entry : Vec<&str> = entry.split(',').collect();
+    if entry.len() < 4 { return None; }
+
+    let node_id = NodeId::from_str(entry[0]);
+    let inst    = entry[1].parse::<usize>().unwrap_or(0);
+
+    Some((
+        node_id.to_instance(inst),
+        entry[2].parse::<f32>().unwrap_or(0.0),
+        entry[3].parse::<f32>().unwrap_or(1.0)))
+}
+
+/// Removes the loop points of the nodes, that are not placed in
+/// the matrix anymore. Returns true if an entry was removed.
+pub fn remove_unused_sample_points(m: &mut Matrix) -> bool {
+    let s =
+        if let Some(SAtom::Str(s)) = m.get_prop("sample_points") { s }
+        else { return false; };
+
+    let mut node_ids = std::collections::HashSet::new();
+    m.for_each(|_x, _y, cell| { node_ids.insert(cell.node_id()); });
+
+    let entries : Vec<&str> =
+        s.split(';')
+         .filter(|entry|
+            parse_loop_entry(entry)
+                .map(|(nid, _, _)| node_ids.contains(&nid))
+                .unwrap_or(false))
+         .collect();
+
+    let new = entries.join(";");
+    if new == s { return false; }
+
+    m.set_prop("sample_points", SAtom::str(&new));
+    true
+}
+
+fn loop_points_from_prop(m: &mut Matrix, node_id: NodeId) -> Option<(f32, f32)> {
+    if let Some(SAtom::Str(s)) = m.get_prop("sample_points") {
+        for entry in s.split(';') {
+            if let Some((nid, ls, le)) = parse_loop_entry(entry) {
+                if nid == node_id { return Some((ls, le)); }
+            }
+        }
+    }
+
+    None
+}
+
+/// The min/max waveform overview of the visible part of the sample.
+struct SampleWaveformModel {
+    editor: Rc<RefCell<SampleEditor>>,
+}
+
+impl GraphMinMaxModel for SampleWaveformModel {
+    fn get_generation(&self) -> u64 {
+        self.editor.borrow().generation
+    }
+
+    fn read(&mut self, buf: &mut [(f32, f32)]) {
+        let ed  = self.editor.borrow();
+        let len = buf.len();
+
+        if ed.data.is_empty() || len == 0 {
+            buf.fill((0.0, 0.0));
+            return;
+        }
+
+        for (i, b) in buf.iter_mut().enumerate() {
+            let from = ed.view_start + (i * ed.view_len) / len;
+            let to   = (ed.view_start + ((i + 1) * ed.view_len) / len).max(from + 1);
+
+            let (mut min, mut max) = (f32::MAX, f32::MIN);
+            for s in ed.data[from.min(ed.data.len())..to.min(ed.data.len())].iter() {
+                min = min.min(*s);
+                max = max.max(*s);
+            }
+
+            *b = if min > max { (0.0, 0.0) } else { (min, max) };
+        }
+    }
+
+    fn fmt_val(&mut self, buf: &mut [u8]) -> usize {
+        use std::io::Write;
+        let ed = self.editor.borrow();
+        let p  = ed.points;
+        let secs = ed.data.len() as f32 / ed.sample_rate.max(1.0);
+
+        let max_len = buf.len();
+        let mut bw = std::io::BufWriter::new(buf);
+        match write!(bw, "S {:5.3}s E {:5.3}s | Loop marks {:5.3}s-{:5.3}s",
+                     p.start * secs, p.end * secs,
+                     p.loop_start * secs, p.loop_end * secs)
+        {
+            Ok(_)  => {
+                if bw.buffer().len() > max_len { max_len }
+                else { bw.buffer().len() }
+            },
+            Err(_) => 0,
+        }
+    }
+}
+
+/// Shows the played region and the loop region of the visible part
+/// of the sample as steps. The vertical lines mark start and end.
+struct SampleMarkersModel {
+    editor: Rc<RefCell<SampleEditor>>,
+}
+
+impl GraphModel for SampleMarkersModel {
+    fn get_generation(&self) -> u64 {
+        self.editor.borrow().generation
+    }
+
+    fn f(&mut self, _init: bool, x: f64, _x_next: f64) -> f64 {
+        let ed  = self.editor.borrow();
+        let pos = ed.view2pos(x as f32);
+        let p   = ed.points;
+
+        if pos >= p.loop_start && pos <= p.loop_end { 1.0 }
+        else if pos >= p.start && pos <= p.end      { 0.5 }
+        else                                        { 0.0 }
+    }
+
+    fn vline1_pos(&self) -> Option<f64> {
+        let ed = self.editor.borrow();
+        ed.pos2view(ed.points.start).map(|x| x as f64)
+    }
+
+    fn vline2_pos(&self) -> Option<f64> {
+        let ed = self.editor.borrow();
+        ed.pos2view(ed.points.end).map(|x| x as f64)
+    }
+}
+
+#[derive(Clone)]
+pub struct VSampleEditor(Rc<RefCell<SampleEditor>>);
+
+impl VSampleEditor {
+    pub fn new(matrix: Arc<Mutex<Matrix>>, node_id: NodeId) -> Self {
+        Self(Rc::new(RefCell::new(SampleEditor::new(matrix, node_id))))
+    }
+}
+
+impl VValUserData for VSampleEditor {
+    fn s(&self) -> String { format!("$<UI::SampleEditor>") }
+    fn as_any(&mut self) -> &mut dyn std::any::Any { self }
+    fn clone_ud(&self) -> Box<dyn vval::VValUserData> { Box::new(self.clone()) }
+
+    fn call_method(&self, key: &str, env: &mut Env)
+        -> Result<VVal, StackAction>
+    {
+        let args = env.argv_ref();
+
+        match key {
+            "reload" => {
+                arg_chk!(args, 0, "sample_editor.reload[]");
+
+                self.0.borrow_mut().reload();
+                Ok(VVal::None)
+            },
+            "waveform_model" => {
+                arg_chk!(args, 0, "sample_editor.waveform_model[]");
+
+                Ok(VVal::new_usr(VGraphMinMaxModel::new(
+                    Rc::new(RefCell::new(SampleWaveformModel {
+                        editor: self.0.clone(),
+                    })))))
+            },
+            "markers_model" => {
+                arg_chk!(args, 0, "sample_editor.markers_model[]");
+
+                Ok(VVal::new_usr(VGraphModel::from_model(
+                    Rc::new(RefCell::new(SampleMarkersModel {
+                        editor: self.0.clone(),
+                    })))))
+            },
+            "len" => {
+                arg_chk!(args, 0, "sample_editor.len[]");
+
+                Ok(VVal::Int(self.0.borrow().data.len() as i64))
+            },
+            "get_points" => {
+                arg_chk!(args, 0, "sample_editor.get_points[]");
+
+                let p   = self.0.borrow().points;
+                let ret = VVal::map2(
+                    "start", VVal::Flt(p.start as f64),
+                    "end",   VVal::Flt(p.end as f64));
+                let _ = ret.set_key_str("loop_start", VVal::Flt(p.loop_start as f64));
+                let _ = ret.set_key_str("loop_end",   VVal::Flt(p.loop_end as f64));
+                Ok(ret)
+            },
+            "set_point" => {
+                arg_chk!(args, 2, "sample_editor.set_point[point_name, pos]");
+
+                let ok =
+                    args[0].with_s_ref(|s|
+                        self.0.borrow_mut().set_point(s, args[1].f() as f32));
+
+                if ok { Ok(VVal::Bol(true)) }
+                else {
+                    Ok(VVal::err_msg(
+                        &format!("Unknown sample point: {}", args[0].s())))
+                }
+            },
+            "move_point" => {
+                arg_chk!(args, 2, "sample_editor.move_point[point_name, view_delta]");
+
+                let mut ed = self.0.borrow_mut();
+                let pos    = args[0].with_s_ref(|s| ed.points.get(s));
+
+                if let Some(pos) = pos {
+                    // The delta is relative to the view, so that the
+                    // points can be moved finer when zoomed in:
+                    let delta = (args[1].f() as f32) * ed.view_len as f32
+                                / ed.frames() as f32;
+                    args[0].with_s_ref(|s| ed.set_point(s, pos + delta));
+                    Ok(VVal::Bol(true))
+                } else {
+                    Ok(VVal::err_msg(
+                        &format!("Unknown sample point: {}", args[0].s())))
+                }
+            },
+            "zoom_by" => {
+                arg_chk!(args, 1, "sample_editor.zoom_by[factor]");
+
+                self.0.borrow_mut().zoom_by(args[0].f() as f32);
+                Ok(VVal::None)
+            },
+            "pan_by" => {
+                arg_chk!(args, 1, "sample_editor.pan_by[view_delta]");
+
+                self.0.borrow_mut().pan_by(args[0].f() as f32);
+                Ok(VVal::None)
+            },
+            "set_view" => {
+                arg_chk!(args, 2, "sample_editor.set_view[start, len]");
+
+                self.0.borrow_mut().set_view(args[0].f() as f32, args[1].f() as f32);
+                Ok(VVal::None)
+            },
+            "get_view" => {
+                arg_chk!(args, 0, "sample_editor.get_view[]");
+
+                let ed     = self.0.borrow();
+                let frames = ed.frames() as f64;
+                Ok(VVal::pair(
+                    VVal::Flt(ed.view_start as f64 / frames),
+                    VVal::Flt(ed.view_len as f64 / frames)))
+            },
+            "reset_view" => {
+                arg_chk!(args, 0, "sample_editor.reset_view[]");
+
+                self.0.borrow_mut().reset_view();
+                Ok(VVal::None)
+            },
+            _ => Ok(VVal::err_msg(&format!("Unknown method called: {}", key))),
+        }
+    }
+}
@@ -0,0 +1,601 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::arg_chk;
+use crate::audio_tap::{AudioTap, TapSource};
+use wlambda::*;
+use hexodsp::Matrix;
+use hexodsp::monitor::MONITOR_MINMAX_SAMPLES;
+use hexotk::GraphMinMaxModel;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+use std::collections::VecDeque;
+
+use super::{VGraphMinMaxModel, sigidx2celldir};
+
+/// Number of samples kept per channel.
+const SCOPE_HISTORY : usize = 65536;
+/// The smallest number of samples the time base can show.
+const MIN_TIME_BASE : usize = 16;
+const DEFAULT_TIME_BASE : usize = 2048;
+/// Number of frames the ring buffer of the audio tap can hold
+/// between two updates of the scope.
+const TAP_BUFFER_FRAMES : usize = 16384;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriggerEdge {
+    Off,
+    Rising,
+    Falling,
+}
+
+impl TriggerEdge {
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "off"     => Some(TriggerEdge::Off),
+            "rising"  => Some(TriggerEdge::Rising),
+            "falling" => Some(TriggerEdge::Falling),
+            _         => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            TriggerEdge::Off     => "off",
+            TriggerEdge::Rising  => "rising",
+            TriggerEdge::Falling => "falling",
+        }
+    }
+}
+
+/// The names of the six signals of the monitored cell, in the order
+/// of the monitor indices: The three inputs, then the three outputs.
+const MONITOR_EDGE_NAMES : [&str; 6] = ["T", "TL", "BL", "TR", "BR", "B"];
+
+/// A signal, that the scope and the spectrum analyzer can show.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScopeSource {
+    /// A channel of the audio device, captured at audio rate.
+    Device(TapSource),
+    /// One of the six signals of the monitored cell. HexoDSP only provides
+    /// them as min/max pairs over the last few seconds, so these channels
+    /// always show that window and ignore the trigger and the time base.
+    Monitor(usize),
+}
+
+impl ScopeSource {
+    pub fn from_str(kind: &str, channel: usize) -> Option<Self> {
+        match kind {
+            "mon" if channel < MONITOR_EDGE_NAMES.len() =>
+                Some(ScopeSource::Monitor(channel)),
+            "mon" => None,
+            _     => TapSource::from_str(kind, channel).map(ScopeSource::Device),
+        }
+    }
+
+    pub fn kind_str(&self) -> &'static str {
+        match self {
+            ScopeSource::Device(src) => src.kind_str(),
+            ScopeSource::Monitor(_)  => "mon",
+        }
+    }
+
+    pub fn channel(&self) -> usize {
+        match self {
+            ScopeSource::Device(src)    => src.channel(),
+            ScopeSource::Monitor(index) => *index,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            ScopeSource::Device(src)    => src.label(),
+            ScopeSource::Monitor(index) => format!("Cell {}", MONITOR_EDGE_NAMES[*index]),
+        }
+    }
+
+    pub fn is_monitor(&self) -> bool { matches!(self, ScopeSource::Monitor(_)) }
+}
+
+/// Copies the min/max data of the monitored signal `index` into `buf`.
+/// The signals of edges without an assigned port are silent.
+pub fn read_monitor_minmax(m: &mut Matrix, index: usize, buf: &mut [(f32, f32)]) {
+    let cell = *m.monitored_cell();
+    if !cell.has_dir_set(sigidx2celldir(index)) {
+        buf.fill((0.0, 0.0));
+        return;
+    }
+
+    let mimbuf = m.get_minmax_monitor_samples(index);
+    for (i, b) in buf.iter_mut().enumerate() {
+        *b = mimbuf.at(i);
+    }
+}
+
+/// A multi channel oscilloscope. The device channels are captured at audio
+/// rate by one [AudioTap], so their samples are aligned. They share the
+/// trigger, which is detected on the first device channel, the time base
+/// and the shown window. The monitor channels show the min/max data of
+/// the monitored cell.
+pub struct Scope {
+    matrix:     Arc<Mutex<Matrix>>,
+    sources:    Vec<ScopeSource>,
+    tap:        Option<AudioTap>,
+    /// The captured samples of the device channels.
+    history:    Vec<VecDeque<f32>>,
+    /// The last min/max data of the monitor channels.
+    monitor:    Vec<Vec<(f32, f32)>>,
+    read_buf:   Vec<f32>,
+    trig_level: f32,
+    trig_edge:  TriggerEdge,
+    /// Number of samples in the shown window.
+    time_base:  usize,
+    frozen:     bool,
+    /// Start of the shown window in the channel histories.
+    window_start: usize,
+    generation: u64,
+}
+
+impl Scope {
+    pub fn new(matrix: Arc<Mutex<Matrix>>) -> Self {
+        Self {
+            matrix,
+            sources:      vec![],
+            tap:          None,
+            history:      vec![],
+            monitor:      vec![],
+            read_buf:     vec![0.0; 4096],
+            trig_level:   0.0,
+            trig_edge:    TriggerEdge::Rising,
+            time_base:    DEFAULT_TIME_BASE,
+            frozen:       false,
+            window_start: 0,
+            generation:   0,
+        }
+    }
+
+    pub fn sources(&self) -> &[ScopeSource] { &self.sources[..] }
+
+    /// Captures the channels `sources`. The histories of all
+    /// channels are cleared, so that they stay aligned.
+    fn set_sources(&mut self, sources: Vec<ScopeSource>) {
+        let device : Vec<TapSource> =
+            sources.iter().filter_map(|src|
+                if let ScopeSource::Device(src) = src { Some(*src) } else { None })
+            .collect();
+
+        self.history = device.iter().map(|_| VecDeque::with_capacity(SCOPE_HISTORY)).collect();
+        self.monitor =
+            sources.iter().filter(|src| src.is_monitor())
+                .map(|_| vec![(0.0, 0.0); MONITOR_MINMAX_SAMPLES])
+                .collect();
+        self.window_start = 0;
+        // Drop the old tap first, so that it is removed from the audio thread:
+        self.tap = None;
+        if !device.is_empty() {
+            self.tap = Some(AudioTap::new(device, TAP_BUFFER_FRAMES));
+        }
+        self.sources = sources;
+        self.generation += 1;
+    }
+
+    fn add_channel(&mut self, source: ScopeSource) -> usize {
+        let mut sources = self.sources.clone();
+        sources.push(source);
+        self.set_sources(sources);
+        self.sources.len() - 1
+    }
+
+    /// Returns the source of the channel `idx` and it's index in
+    /// the `history` or `monitor` of the scope.
+    fn channel_slot(&self, idx: usize) -> Option<(ScopeSource, usize)> {
+        let src  = *self.sources.get(idx)?;
+        let slot =
+            self.sources[..idx].iter()
+                .filter(|s| s.is_monitor() == src.is_monitor())
+                .count();
+        Some((src, slot))
+    }
+
+    pub fn set_time_base(&mut self, samples: usize) {
+        self.time_base = samples.clamp(MIN_TIME_BASE, SCOPE_HISTORY);
+        self.generation += 1;
+    }
+
+    /// Reads the new samples from the audio tap and the monitor.
+    /// A frozen scope discards them and keeps showing the last window.
+    pub fn update(&mut self) {
+        if !self.frozen {
+            self.update_monitor();
+        }
+
+        let tap =
+            if let Some(tap) = self.tap.as_mut() { tap }
+            else {
+                if !self.frozen { self.generation += 1; }
+                return;
+            };
+
+        // Only whole frames are read, to keep the channels aligned:
+        let channels = self.history.len();
+        let max_len  = (self.read_buf.len() / channels) * channels;
+
+        loop {
+            let len = tap.pop(&mut self.read_buf[0..max_len]);
+            if len == 0 { break; }
+            if self.frozen { continue; }
+
+            for frame in self.read_buf[0..len].chunks(channels) {
+                for (hist, sample) in self.history.iter_mut().zip(frame.iter()) {
+                    if hist.len() >= SCOPE_HISTORY {
+                        hist.pop_front();
+                    }
+                    hist.push_back(*sample);
+                }
+            }
+        }
+
+        if self.frozen { return; }
+
+        self.window_start = self.find_trigger();
+        self.generation += 1;
+    }
+
+    fn update_monitor(&mut self) {
+        if self.monitor.is_empty() { return; }
+
+        let mut m =
+            if let Ok(m) = self.matrix.lock() { m }
+            else { return; };
+
+        let indices =
+            self.sources.iter().filter_map(|src|
+                if let ScopeSource::Monitor(index) = src { Some(*index) } else { None });
+
+        for (buf, index) in self.monitor.iter_mut().zip(indices) {
+            read_monitor_minmax(&mut m, index, &mut buf[..]);
+        }
+    }
+
+    /// Searches the history of the first device channel backwards for the
+    /// latest trigger point, that still leaves room for a full window.
+    /// Without a trigger the latest samples are shown.
+    /// Returns the start of the window.
+    fn find_trigger(&self) -> usize {
+        let hist =
+            if let Some(hist) = self.history.get(0) { hist }
+            else { return 0; };
+
+        let window = self.time_base.min(hist.len());
+        let latest = hist.len() - window;
+
+        if self.trig_edge == TriggerEdge::Off { return latest; }
+
+        let level = self.trig_level;
+
+        for i in (1..=latest).rev() {
+            let (prev, cur) = (hist[i - 1], hist[i]);
+
+            let triggered =
+                match self.trig_edge {
+                    TriggerEdge::Rising  => prev < level && cur >= level,
+                    TriggerEdge::Falling => prev > level && cur <= level,
+                    TriggerEdge::Off     => false,
+                };
+
+            if triggered { return i; }
+        }
+
+        latest
+    }
+
+    /// Resamples the shown window of the channel `idx` into `buf`.
+    fn read_channel(&self, idx: usize, buf: &mut [(f32, f32)]) -> (f32, f32) {
+        match self.channel_slot(idx) {
+            Some((ScopeSource::Device(_), slot))  => self.read_device(slot, buf),
+            Some((ScopeSource::Monitor(_), slot)) => self.read_monitor(slot, buf),
+            None => {
+                buf.fill((0.0, 0.0));
+                (0.0, 0.0)
+            },
+        }
+    }
+
+    /// Resamples the min/max data of the monitor channel `slot` into `buf`.
+    fn read_monitor(&self, slot: usize, buf: &mut [(f32, f32)]) -> (f32, f32) {
+        let mon = &self.monitor[slot];
+        let len = buf.len();
+
+        let (mut min, mut max) = (f32::MAX, f32::MIN);
+
+        for (i, b) in buf.iter_mut().enumerate() {
+            let from = (i * mon.len()) / len;
+            let to   = (((i + 1) * mon.len()) / len).max(from + 1).min(mon.len());
+
+            let (mut bmin, mut bmax) = (f32::MAX, f32::MIN);
+            for mm in mon[from.min(to)..to].iter() {
+                bmin = bmin.min(mm.0);
+                bmax = bmax.max(mm.1);
+            }
+            if bmin > bmax { bmin = 0.0; bmax = 0.0; }
+
+            min = min.min(bmin);
+            max = max.max(bmax);
+            *b = (bmin, bmax);
+        }
+
+        if min > max { (0.0, 0.0) } else { (min, max) }
+    }
+
+    /// Resamples the shown window of the device channel `slot` into `buf`.
+    fn read_device(&self, slot: usize, buf: &mut [(f32, f32)]) -> (f32, f32) {
+        let hist =
+            if let Some(hist) = self.history.get(slot) { hist }
+            else {
+                buf.fill((0.0, 0.0));
+                return (0.0, 0.0);
+            };
+
+        let start  = self.window_start.min(hist.len());
+        let window = self.time_base.min(hist.len() - start);
+
+        if window == 0 || buf.is_empty() {
+            buf.fill((0.0, 0.0));
+            return (0.0, 0.0);
+        }
+
+        let (mut min, mut max) = (f32::MAX, f32::MIN);
+        let len = buf.len();
+
+        for (i, b) in buf.iter_mut().enumerate() {
+            let from = start + (i * window) / len;
+            let to   = (start + ((i + 1) * window) / len).max(from + 1);
+
+            let (mut bmin, mut bmax) = (f32::MAX, f32::MIN);
+            for j in from..to.min(start + window) {
+                bmin = bmin.min(hist[j]);
+                bmax = bmax.max(hist[j]);
+            }
+
+            min = min.min(bmin);
+            max = max.max(bmax);
+            *b = (bmin, bmax);
+        }
+
+        (min, max)
+    }
+
+    /// Draws all device channels into `buf`, on the same time base and
+    /// scale. The monitor channels have their own time window, so they
+    /// are not overlaid.
+    fn read_overlay(&self, buf: &mut [(f32, f32)]) -> (f32, f32) {
+        buf.fill((f32::MAX, f32::MIN));
+
+        let mut ch_buf = vec![(0.0, 0.0); buf.len()];
+        let (mut min, mut max) = (f32::MAX, f32::MIN);
+
+        for idx in 0..self.history.len() {
+            let (ch_min, ch_max) = self.read_device(idx, &mut ch_buf[..]);
+            min = min.min(ch_min);
+            max = max.max(ch_max);
+
+            for (b, ch) in buf.iter_mut().zip(ch_buf.iter()) {
+                *b = (b.0.min(ch.0), b.1.max(ch.1));
+            }
+        }
+
+        if self.history.is_empty() {
+            buf.fill((0.0, 0.0));
+            return (0.0, 0.0);
+        }
+
+        (min, max)
+    }
+}
+
+/// The view of one channel of a `Scope` for the `:graph_minmax` widget,
+/// or of all channels overlaid if `index` is `None`.
+/// The views of all channels show the same time window.
+struct ScopeChannelModel {
+    scope:  Rc<RefCell<Scope>>,
+    index:  Option<usize>,
+    min:    f32,
+    max:    f32,
+}
+
+impl GraphMinMaxModel for ScopeChannelModel {
+    fn get_generation(&self) -> u64 {
+        self.scope.borrow().generation
+    }
+
+    fn read(&mut self, buf: &mut [(f32, f32)]) {
+        let scope = self.scope.borrow();
+        let (min, max) =
+            match self.index {
+                Some(index) => scope.read_channel(index, buf),
+                None        => scope.read_overlay(buf),
+            };
+        self.min = min;
+        self.max = max;
+    }
+
+    fn fmt_val(&mut self, buf: &mut [u8]) -> usize {
+        let label =
+            match self.index {
+                Some(index) =>
+                    self.scope.borrow().sources().get(index)
+                        .map(|src| src.label())
+                        .unwrap_or_default(),
+                None => "All".to_string(),
+            };
+
+        use std::io::Write;
+        let max_len = buf.len();
+        let mut bw = std::io::BufWriter::new(buf);
+        match write!(bw, "{} | {:6.3} | {:6.3}",
+                     label, self.min, self.max)
+        {
+            Ok(_)  => {
+                if bw.buffer().len() > max_len { max_len }
+                else { bw.buffer().len() }
+            },
+            Err(_) => 0,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct VScope(Rc<RefCell<Scope>>);
+
+impl VScope {
+    pub fn new(matrix: Arc<Mutex<Matrix>>) -> Self {
+        Self(Rc::new(RefCell::new(Scope::new(matrix))))
+    }
+}
+
+impl VValUserData for VScope {
+    fn s(&self) -> String { format!("$<UI::Scope>") }
+    fn as_any(&mut self) -> &mut dyn std::any::Any { self }
+    fn clone_ud(&self) -> Box<dyn vval::VValUserData> { Box::new(self.clone()) }
+
+    fn call_method(&self, key: &str, env: &mut Env)
+        -> Result<VVal, StackAction>
+    {
+        let args = env.argv_ref();
+
+        match key {
+            "add_channel" => {
+                arg_chk!(args, 2, "scope.add_channel[:out | :in | :mon, channel]");
+
+                let channel = args[1].i().max(0) as usize;
+                if let Some(src) = args[0].with_s_ref(|s| ScopeSource::from_str(s, channel)) {
+                    Ok(VVal::Int(self.0.borrow_mut().add_channel(src) as i64))
+                } else {
+                    Ok(VVal::err_msg(
+                        &format!("Unknown scope source: {}", args[0].s())))
+                }
+            },
+            "channel_sources" => {
+                arg_chk!(args, 0, "scope.channel_sources[]");
+
+                let ret = VVal::vec();
+                for src in self.0.borrow().sources() {
+                    ret.push(VVal::pair(
+                        VVal::new_sym(src.kind_str()),
+                        VVal::Int(src.channel() as i64)));
+                }
+
+                Ok(ret)
+            },
+            "channel_labels" => {
+                arg_chk!(args, 0, "scope.channel_labels[]");
+
+                let ret = VVal::vec();
+                for src in self.0.borrow().sources() {
+                    ret.push(VVal::new_str_mv(src.label()));
+                }
+
+                Ok(ret)
+            },
+            "clear_channels" => {
+                arg_chk!(args, 0, "scope.clear_channels[]");
+
+                self.0.borrow_mut().set_sources(vec![]);
+                Ok(VVal::None)
+            },
+            "channel_count" => {
+                arg_chk!(args, 0, "scope.channel_count[]");
+
+                Ok(VVal::Int(self.0.borrow().sources.len() as i64))
+            },
+            "channel_model" => {
+                arg_chk!(args, 1, "scope.channel_model[channel_index]");
+
+                let index = args[0].i() as usize;
+                if index >= self.0.borrow().sources.len() {
+                    return Ok(VVal::err_msg(
+                        &format!("No such scope channel: {}", index)));
+                }
+
+                Ok(VVal::new_usr(VGraphMinMaxModel::new(
+                    Rc::new(RefCell::new(ScopeChannelModel {
+                        scope: self.0.clone(),
+                        index: Some(index),
+                        min: 0.0,
+                        max: 0.0,
+                    })))))
+            },
+            "overlay_model" => {
+                arg_chk!(args, 0, "scope.overlay_model[]");
+
+                Ok(VVal::new_usr(VGraphMinMaxModel::new(
+                    Rc::new(RefCell::new(ScopeChannelModel {
+                        scope: self.0.clone(),
+                        index: None,
+                        min: 0.0,
+                        max: 0.0,
+                    })))))
+            },
+            "set_trigger" => {
+                arg_chk!(args, 2, "scope.set_trigger[level, :rising | :falling | :off]");
+
+                let edge = args[1].with_s_ref(TriggerEdge::from_str);
+                if let Some(edge) = edge {
+                    let mut scope = self.0.borrow_mut();
+                    scope.trig_level = args[0].f() as f32;
+                    scope.trig_edge  = edge;
+                    Ok(VVal::Bol(true))
+                } else {
+                    Ok(VVal::err_msg(
+                        &format!("Unknown trigger edge: {}", args[1].s())))
+                }
+            },
+            "get_trigger" => {
+                arg_chk!(args, 0, "scope.get_trigger[]");
+
+                let scope = self.0.borrow();
+                Ok(VVal::pair(
+                    VVal::Flt(scope.trig_level as f64),
+                    VVal::new_sym(scope.trig_edge.as_str())))
+            },
+            "set_time_base" => {
+                arg_chk!(args, 1, "scope.set_time_base[samples]");
+
+                self.0.borrow_mut().set_time_base(args[0].i().max(0) as usize);
+                Ok(VVal::None)
+            },
+            "get_time_base" => {
+                arg_chk!(args, 0, "scope.get_time_base[]");
+
+                Ok(VVal::Int(self.0.borrow().time_base as i64))
+            },
+            "get_time_base_ms" => {
+                arg_chk!(args, 0, "scope.get_time_base_ms[]");
+
+                Ok(VVal::Flt(
+                    self.0.borrow().time_base as f64 * 1000.0
+                    / crate::audio_tap::sample_rate() as f64))
+            },
+            "set_freeze" => {
+                arg_chk!(args, 1, "scope.set_freeze[bool]");
+
+                self.0.borrow_mut().frozen = args[0].b();
+                Ok(VVal::None)
+            },
+            "is_frozen" => {
+                arg_chk!(args, 0, "scope.is_frozen[]");
+
+                Ok(VVal::Bol(self.0.borrow().frozen))
+            },
+            "update" => {
+                arg_chk!(args, 0, "scope.update[]");
+
+                self.0.borrow_mut().update();
+                Ok(VVal::None)
+            },
+            _ => Ok(VVal::err_msg(&format!("Unknown method called: {}", key))),
+        }
+    }
+}
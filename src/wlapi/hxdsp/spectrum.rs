@@ -0,0 +1,470 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::arg_chk;
+use crate::audio_tap::{self, AudioTap};
+use wlambda::*;
+use hexodsp::Matrix;
+use hexodsp::monitor::MONITOR_MINMAX_SAMPLES;
+use hexotk::GraphModel;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+use std::collections::VecDeque;
+
+use super::{VGraphModel, ScopeSource, read_monitor_minmax};
+
+/// The lowest level in dB, that is shown at the bottom of the graph.
+const MIN_DB : f32 = -90.0;
+/// The lowest frequency in Hz, that is shown at the left of the graph.
+const MIN_FREQ : f64 = 20.0;
+/// The lowest frequency of a monitor source, in cycles per monitor window.
+const MIN_MONITOR_FREQ : f64 = 1.0;
+const DEFAULT_FFT_SIZE : usize = 2048;
+/// Number of frames the ring buffer of the audio tap can hold
+/// between two updates of the spectrum.
+const TAP_BUFFER_FRAMES : usize = 16384;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FftWindow {
+    Rect,
+    Hann,
+    Hamming,
+    Blackman,
+}
+
+impl FftWindow {
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "rect"     => Some(FftWindow::Rect),
+            "hann"     => Some(FftWindow::Hann),
+            "hamming"  => Some(FftWindow::Hamming),
+            "blackman" => Some(FftWindow::Blackman),
+            _          => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            FftWindow::Rect     => "rect",
+            FftWindow::Hann     => "hann",
+            FftWindow::Hamming  => "hamming",
+            FftWindow::Blackman => "blackman",
+        }
+    }
+
+    fn coefficient(&self, i: usize, len: usize) -> f32 {
+        let x = (2.0 * std::f32::consts::PI * i as f32) / (len - 1) as f32;
+
+        match self {
+            FftWindow::Rect     => 1.0,
+            FftWindow::Hann     => 0.5 - 0.5 * x.cos(),
+            FftWindow::Hamming  => 0.54 - 0.46 * x.cos(),
+            FftWindow::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+        }
+    }
+}
+
+/// In place iterative radix-2 FFT. `re` and `im` must have the same
+/// power of two length.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let ang = -2.0 * std::f32::consts::PI / len as f32;
+        let (w_re, w_im) = (ang.cos(), ang.sin());
+
+        for start in (0..n).step_by(len) {
+            let (mut cur_re, mut cur_im) = (1.0_f32, 0.0_f32);
+
+            for k in 0..(len / 2) {
+                let a = start + k;
+                let b = a + len / 2;
+
+                let t_re = re[b] * cur_re - im[b] * cur_im;
+                let t_im = re[b] * cur_im + im[b] * cur_re;
+
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+
+                let next_re = cur_re * w_re - cur_im * w_im;
+                cur_im      = cur_re * w_im + cur_im * w_re;
+                cur_re      = next_re;
+            }
+        }
+
+        len <<= 1;
+    }
+}
+
+/// Computes the spectrum of an audio device channel or of a signal of the
+/// monitored cell. Device channels are captured at audio rate by an
+/// [AudioTap]. HexoDSP only provides the monitored signals as min/max pairs
+/// over a fixed window, so their spectrum is computed from the midpoints
+/// of these pairs and the frequencies are in cycles per window.
+pub struct Spectrum {
+    matrix:     Arc<Mutex<Matrix>>,
+    source:     ScopeSource,
+    tap:        Option<AudioTap>,
+    /// The latest `fft_size` samples of the analyzed signal.
+    samples:    VecDeque<f32>,
+    read_buf:   Vec<f32>,
+    monitor:    Vec<(f32, f32)>,
+    fft_size:   usize,
+    window:     FftWindow,
+    peak_hold:  bool,
+    /// Magnitudes in dB of the bins 0 to fft_size / 2.
+    mags:       Vec<f32>,
+    peaks:      Vec<f32>,
+    generation: u64,
+}
+
+impl Spectrum {
+    pub fn new(matrix: Arc<Mutex<Matrix>>, source: ScopeSource, fft_size: usize) -> Self {
+        let bins = fft_size / 2 + 1;
+
+        Self {
+            matrix,
+            source,
+            tap:        Self::tap_for(source),
+            samples:    VecDeque::with_capacity(fft_size),
+            read_buf:   vec![0.0; 4096],
+            monitor:    vec![(0.0, 0.0); MONITOR_MINMAX_SAMPLES],
+            fft_size,
+            window:     FftWindow::Hann,
+            peak_hold:  true,
+            mags:       vec![MIN_DB; bins],
+            peaks:      vec![MIN_DB; bins],
+            generation: 0,
+        }
+    }
+
+    fn tap_for(source: ScopeSource) -> Option<AudioTap> {
+        if let ScopeSource::Device(src) = source {
+            Some(AudioTap::new(vec![src], TAP_BUFFER_FRAMES))
+        } else {
+            None
+        }
+    }
+
+    pub fn source(&self) -> ScopeSource { self.source }
+
+    /// Analyzes `source` from now on.
+    pub fn set_source(&mut self, source: ScopeSource) {
+        self.source = source;
+        self.tap    = Self::tap_for(source);
+        self.samples.clear();
+        self.reset_peaks();
+    }
+
+    pub fn reset_peaks(&mut self) {
+        self.peaks.fill(MIN_DB);
+        self.generation += 1;
+    }
+
+    /// Replaces the samples with the midpoints of the min/max data
+    /// of the monitored signal.
+    fn read_monitor(&mut self, index: usize) {
+        if let Ok(mut m) = self.matrix.lock() {
+            read_monitor_minmax(&mut m, index, &mut self.monitor[..]);
+        } else {
+            return;
+        }
+
+        let skip = self.monitor.len().saturating_sub(self.fft_size);
+
+        self.samples.clear();
+        for (min, max) in self.monitor.iter().skip(skip) {
+            self.samples.push_back((min + max) * 0.5);
+        }
+    }
+
+    /// Reads the new samples of the source and computes the spectrum
+    /// of the latest `fft_size` samples.
+    pub fn update(&mut self) {
+        if let ScopeSource::Monitor(index) = self.source {
+            self.read_monitor(index);
+        }
+
+        if let Some(tap) = self.tap.as_mut() {
+            loop {
+                let len = tap.pop(&mut self.read_buf[..]);
+                if len == 0 { break; }
+
+                for s in self.read_buf[0..len].iter() {
+                    if self.samples.len() >= self.fft_size {
+                        self.samples.pop_front();
+                    }
+                    self.samples.push_back(*s);
+                }
+            }
+        }
+
+        let mut re = vec![0.0; self.fft_size];
+        let mut im = vec![0.0; self.fft_size];
+
+        // Until enough samples were captured, and for the short monitor
+        // window, the FFT input is zero padded:
+        let len = self.samples.len();
+        if len > 1 {
+            for (i, s) in self.samples.iter().enumerate() {
+                re[i] = s * self.window.coefficient(i, len);
+            }
+        }
+
+        fft(&mut re[..], &mut im[..]);
+
+        let norm = 2.0 / len.max(1) as f32;
+        for (i, mag) in self.mags.iter_mut().enumerate() {
+            let amp = (re[i] * re[i] + im[i] * im[i]).sqrt() * norm;
+            *mag = (20.0 * amp.max(1e-9).log10()).max(MIN_DB);
+        }
+
+        if self.peak_hold {
+            for (peak, mag) in self.peaks.iter_mut().zip(self.mags.iter()) {
+                *peak = peak.max(*mag);
+            }
+        }
+
+        self.generation += 1;
+    }
+
+    /// The number of samples per frequency unit: The sample rate for
+    /// device channels, the number of min/max pairs per window for
+    /// monitor signals.
+    fn rate(&self) -> f64 {
+        if self.source.is_monitor() {
+            MONITOR_MINMAX_SAMPLES as f64
+        } else {
+            audio_tap::sample_rate() as f64
+        }
+    }
+
+    fn min_freq(&self) -> f64 {
+        if self.source.is_monitor() { MIN_MONITOR_FREQ } else { MIN_FREQ }
+    }
+
+    fn freq_unit(&self) -> &'static str {
+        if self.source.is_monitor() { "cycles" } else { "Hz" }
+    }
+
+    /// The highest frequency of the spectrum, half of the rate.
+    fn nyquist(&self) -> f64 {
+        self.rate() * 0.5
+    }
+
+    fn bin_freq(&self, bin: usize) -> f64 {
+        bin as f64 * self.rate() / self.fft_size as f64
+    }
+
+    /// Maps the graph position `x` (0.0 to 1.0) logarithmically to a
+    /// frequency between the lowest frequency and the nyquist frequency.
+    fn freq_for_x(&self, x: f64) -> f64 {
+        let min_freq = self.min_freq();
+        min_freq * (self.nyquist() / min_freq).powf(x.clamp(0.0, 1.0))
+    }
+
+    /// Maps the graph position `x` to a bin. The DC bin 0 is left out.
+    fn bin_for_x(&self, x: f64) -> usize {
+        let bin = self.freq_for_x(x) * self.fft_size as f64 / self.rate();
+        (bin.round() as usize).clamp(1, self.mags.len() - 1)
+    }
+
+    fn x_for_bin(&self, bin: usize) -> f64 {
+        let min_freq = self.min_freq();
+        let freq     = self.bin_freq(bin.max(1)).max(min_freq);
+        (freq.ln() - min_freq.ln()) / (self.nyquist().ln() - min_freq.ln())
+    }
+
+    /// Returns the bin with the highest magnitude, leaving out DC.
+    fn peak_bin(&self) -> usize {
+        let mut best = 1;
+        for (i, mag) in self.mags.iter().enumerate().skip(1) {
+            if *mag > self.mags[best] { best = i; }
+        }
+        best
+    }
+
+    fn db2graph(db: f32) -> f64 {
+        ((db - MIN_DB) / -MIN_DB) as f64
+    }
+}
+
+/// Shows either the current magnitudes or the held peaks of a `Spectrum`
+/// on a `:graph` widget.
+struct SpectrumGraphModel {
+    spectrum: Rc<RefCell<Spectrum>>,
+    peaks:    bool,
+}
+
+impl GraphModel for SpectrumGraphModel {
+    fn get_generation(&self) -> u64 {
+        self.spectrum.borrow().generation
+    }
+
+    fn f(&mut self, _init: bool, x: f64, x_next: f64) -> f64 {
+        let spec = self.spectrum.borrow();
+        let data = if self.peaks { &spec.peaks } else { &spec.mags };
+
+        // Several bins can fall onto one graph sample at the upper end
+        // of the log scale, show the highest one:
+        let from = spec.bin_for_x(x);
+        let to   = spec.bin_for_x(x_next).max(from);
+
+        let mut db = MIN_DB;
+        for bin in from..=to {
+            db = db.max(data[bin]);
+        }
+
+        Spectrum::db2graph(db)
+    }
+
+    fn vline1_pos(&self) -> Option<f64> {
+        if self.peaks { return None; }
+
+        let spec = self.spectrum.borrow();
+        Some(spec.x_for_bin(spec.peak_bin()))
+    }
+
+    fn vline2_pos(&self) -> Option<f64> { None }
+}
+
+#[derive(Clone)]
+pub struct VSpectrumModel(Rc<RefCell<Spectrum>>);
+
+impl VSpectrumModel {
+    pub fn new(
+        matrix: Arc<Mutex<Matrix>>, source: ScopeSource, fft_size: Option<usize>
+    ) -> Self {
+        Self(Rc::new(RefCell::new(
+            Spectrum::new(matrix, source, fft_size.unwrap_or(DEFAULT_FFT_SIZE)))))
+    }
+}
+
+impl VValUserData for VSpectrumModel {
+    fn s(&self) -> String { format!("$<UI::SpectrumModel>") }
+    fn as_any(&mut self) -> &mut dyn std::any::Any { self }
+    fn clone_ud(&self) -> Box<dyn vval::VValUserData> { Box::new(self.clone()) }
+
+    fn call_method(&self, key: &str, env: &mut Env)
+        -> Result<VVal, StackAction>
+    {
+        let args = env.argv_ref();
+
+        match key {
+            "update" => {
+                arg_chk!(args, 0, "spectrum.update[]");
+
+                self.0.borrow_mut().update();
+                Ok(VVal::None)
+            },
+            "graph_model" => {
+                arg_chk!(args, 1, "spectrum.graph_model[:spectrum | :peaks]");
+
+                let peaks = args[0].with_s_ref(|s| s == "peaks");
+
+                Ok(VVal::new_usr(VGraphModel::from_model(
+                    Rc::new(RefCell::new(SpectrumGraphModel {
+                        spectrum: self.0.clone(),
+                        peaks,
+                    })))))
+            },
+            "set_window" => {
+                arg_chk!(args, 1, "spectrum.set_window[:rect | :hann | :hamming | :blackman]");
+
+                if let Some(window) = args[0].with_s_ref(FftWindow::from_str) {
+                    self.0.borrow_mut().window = window;
+                    Ok(VVal::Bol(true))
+                } else {
+                    Ok(VVal::err_msg(
+                        &format!("Unknown FFT window: {}", args[0].s())))
+                }
+            },
+            "get_window" => {
+                arg_chk!(args, 0, "spectrum.get_window[]");
+
+                Ok(VVal::new_sym(self.0.borrow().window.as_str()))
+            },
+            "set_peak_hold" => {
+                arg_chk!(args, 1, "spectrum.set_peak_hold[bool]");
+
+                let mut spec = self.0.borrow_mut();
+                spec.peak_hold = args[0].b();
+                if !spec.peak_hold { spec.reset_peaks(); }
+
+                Ok(VVal::None)
+            },
+            "reset_peaks" => {
+                arg_chk!(args, 0, "spectrum.reset_peaks[]");
+
+                self.0.borrow_mut().reset_peaks();
+                Ok(VVal::None)
+            },
+            "peak" => {
+                arg_chk!(args, 0, "spectrum.peak[]");
+
+                let spec = self.0.borrow();
+                let bin  = spec.peak_bin();
+
+                Ok(VVal::pair(
+                    VVal::Flt(spec.bin_freq(bin)),
+                    VVal::Flt(spec.mags[bin] as f64)))
+            },
+            "set_source" => {
+                arg_chk!(args, 2, "spectrum.set_source[:out | :in | :mon, channel]");
+
+                let channel = args[1].i().max(0) as usize;
+                if let Some(src) = args[0].with_s_ref(|s| ScopeSource::from_str(s, channel)) {
+                    self.0.borrow_mut().set_source(src);
+                    Ok(VVal::Bol(true))
+                } else {
+                    Ok(VVal::err_msg(
+                        &format!("Unknown spectrum source: {}", args[0].s())))
+                }
+            },
+            "get_source" => {
+                arg_chk!(args, 0, "spectrum.get_source[]");
+
+                let src = self.0.borrow().source();
+                Ok(VVal::pair(
+                    VVal::new_sym(src.kind_str()),
+                    VVal::Int(src.channel() as i64)))
+            },
+            "get_source_label" => {
+                arg_chk!(args, 0, "spectrum.get_source_label[]");
+
+                Ok(VVal::new_str_mv(self.0.borrow().source().label()))
+            },
+            "get_freq_unit" => {
+                arg_chk!(args, 0, "spectrum.get_freq_unit[]");
+
+                Ok(VVal::new_str(self.0.borrow().freq_unit()))
+            },
+            "x_to_freq" => {
+                arg_chk!(args, 1, "spectrum.x_to_freq[x]");
+
+                Ok(VVal::Flt(self.0.borrow().freq_for_x(args[0].f())))
+            },
+            _ => Ok(VVal::err_msg(&format!("Unknown method called: {}", key))),
+        }
+    }
+}
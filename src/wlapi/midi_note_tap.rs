@@ -0,0 +1,46 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::arg_chk;
+use crate::midi_input::MidiNoteTap;
+
+use wlambda::*;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+#[derive(Clone)]
+pub struct VValMidiNoteTap(Rc<RefCell<MidiNoteTap>>);
+
+impl VValMidiNoteTap {
+    pub fn new() -> Self {
+        Self(Rc::new(RefCell::new(MidiNoteTap::new())))
+    }
+}
+
+impl vval::VValUserData for VValMidiNoteTap {
+    fn s(&self) -> String { format!("$<MidiNoteTap>") }
+
+    fn call_method(&self, key: &str, env: &mut Env)
+        -> Result<VVal, StackAction>
+    {
+        let args = env.argv_ref();
+
+        match key {
+            "pop_notes" => {
+                arg_chk!(args, 0, "midi_note_tap.pop_notes[]");
+
+                let ret = VVal::vec();
+                while let Some(note) = self.0.borrow_mut().pop() {
+                    ret.push(VVal::Int(note as i64));
+                }
+                Ok(ret)
+            },
+            _ => Ok(VVal::err_msg(&format!("Unknown method called: {}", key))),
+        }
+    }
+
+    fn as_any(&mut self) -> &mut dyn std::any::Any { self }
+    fn clone_ud(&self) -> Box<dyn vval::VValUserData> { Box::new(self.clone()) }
+}
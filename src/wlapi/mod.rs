@@ -20,6 +20,12 @@ pub use wichtext::*;
 pub mod pattern_editor;
 pub use pattern_editor::*;
 
+pub mod recorder;
+pub use recorder::*;
+
+pub mod midi_note_tap;
+pub use midi_note_tap::*;
+
 use wlambda::*;
 use std::sync::{Arc, Mutex};
 
@@ -43,12 +49,66 @@ macro_rules! wl_panic {
     }
 }
 
+/// Scores how well `pattern` matches `text`, if all characters of
+/// `pattern` appear in `text` in the same order (case insensitive).
+/// Consecutive characters and characters at the start of a word
+/// are rated higher, gaps between the matched characters lower.
+pub fn fuzzy_score(pattern: &str, text: &str) -> Option<i64> {
+    let text : Vec<char> = text.to_lowercase().chars().collect();
+
+    let mut score    = 0;
+    let mut ti       = 0;
+    let mut last_hit : Option<usize> = None;
+
+    for pc in pattern.to_lowercase().chars() {
+        if pc.is_whitespace() {
+            continue;
+        }
+
+        while ti < text.len() && text[ti] != pc {
+            ti += 1;
+        }
+
+        if ti >= text.len() {
+            return None;
+        }
+
+        score += 1;
+
+        if ti == 0 || !text[ti - 1].is_alphanumeric() {
+            score += 8;
+        }
+
+        match last_hit {
+            Some(last) if last + 1 == ti => { score += 5; },
+            Some(last) => { score -= ((ti - last) as i64).min(5); },
+            None       => { score -= (ti as i64).min(5); },
+        }
+
+        last_hit = Some(ti);
+        ti += 1;
+    }
+
+    Some(score)
+}
+
 pub fn setup_hx_module(matrix: Arc<Mutex<Matrix>>) -> wlambda::SymbolTable {
     let mut st = wlambda::SymbolTable::new();
 
+    // init_hexosynth() already tried to load the samples of the startup
+    // patch, this only collects the reasons for those that are missing:
+    let missing_samples =
+        crate::sample_library::resolve_samples(
+            &mut matrix.lock().expect("matrix lockable"),
+            crate::INIT_PATCH,
+            Some(crate::audio_tap::sample_rate()));
+
+    // All handles share the same transaction state:
+    let matrix_vv = matrix2vv(matrix.clone(), missing_samples);
+
     st.fun(
         "get_main_matrix_handle", move |_env: &mut Env, _argc: usize| {
-            Ok(matrix2vv(matrix.clone()))
+            Ok(matrix_vv.clone())
         }, Some(0), Some(0), false);
 
     st.fun(
@@ -56,6 +116,16 @@ pub fn setup_hx_module(matrix: Arc<Mutex<Matrix>>) -> wlambda::SymbolTable {
             Ok(VVal::new_usr(VValCluster::new()))
         }, Some(0), Some(0), false);
 
+    st.fun(
+        "new_recorder", move |_env: &mut Env, _argc: usize| {
+            Ok(VVal::new_usr(VValRecorder::new()))
+        }, Some(0), Some(0), false);
+
+    st.fun(
+        "new_midi_note_tap", move |_env: &mut Env, _argc: usize| {
+            Ok(VVal::new_usr(VValMidiNoteTap::new()))
+        }, Some(0), Some(0), false);
+
     st.fun(
         "new_sample_buf_from", move |env: &mut Env, _argc: usize| {
             let mut v = vec![];
@@ -68,6 +138,22 @@ pub fn setup_hx_module(matrix: Arc<Mutex<Matrix>>) -> wlambda::SymbolTable {
             Ok(VVal::new_usr(VValSampleBuf::from_vec(v)))
         }, Some(1), Some(1), false);
 
+    st.fun(
+        "new_sample_buf_from_file", move |env: &mut Env, argc: usize| {
+            let path    = env.arg(0).s_raw();
+            let channel =
+                if argc > 1 && env.arg(1).is_some() { Some(env.arg(1).i() as usize) }
+                else { None };
+
+            match crate::sample_file::decode_wav(&path, channel) {
+                Ok(sample) =>
+                    Ok(VVal::pair(
+                        VVal::new_usr(VValSampleBuf::from_vec(sample.data)),
+                        VVal::Int(sample.sample_rate as i64))),
+                Err(msg) => Ok(VVal::err_msg(&msg)),
+            }
+        }, Some(1), Some(2), false);
+
     st.fun(
         "dir", move |env: &mut Env, _argc: usize| {
             Ok(VVal::new_usr(VValCellDir::from_vval(&env.arg(0))))
@@ -119,6 +205,92 @@ pub fn setup_hx_module(matrix: Arc<Mutex<Matrix>>) -> wlambda::SymbolTable {
             }
         }, Some(2), Some(2), false);
 
+    st.fun(
+        "fuzzy_score", move |env: &mut Env, _argc: usize| {
+            let score =
+                env.arg(0).with_s_ref(|pattern|
+                    env.arg(1).with_s_ref(|text|
+                        fuzzy_score(pattern, text)));
+
+            Ok(score.map_or(VVal::None, VVal::Int))
+        }, Some(2), Some(2), false);
+
+    st.fun(
+        "load_sample_file", move |env: &mut Env, argc: usize| {
+            let path    = env.arg(0).s_raw();
+            let channel =
+                if argc > 1 && env.arg(1).is_some() { Some(env.arg(1).i() as usize) }
+                else { None };
+
+            let sample_rate = crate::audio_tap::sample_rate();
+
+            match crate::sample_file::load_sample_file(&path, Some(sample_rate), channel) {
+                Ok(data) =>
+                    Ok(atom2vv(hexodsp::SAtom::audio(&path, Arc::new(data)))),
+                Err(msg) =>
+                    Ok(VVal::err_msg(&msg)),
+            }
+        }, Some(1), Some(2), false);
+
+    st.fun(
+        "sample_file_info", move |env: &mut Env, _argc: usize| {
+            match crate::sample_file::sample_file_info(&env.arg(0).s_raw()) {
+                Ok(info) => {
+                    let ret = VVal::map3(
+                        "channels",        VVal::Int(info.channels as i64),
+                        "sample_rate",     VVal::Int(info.sample_rate as i64),
+                        "bits_per_sample", VVal::Int(info.bits_per_sample as i64));
+                    let _ = ret.set_key_str("float",  VVal::Bol(info.float));
+                    let _ = ret.set_key_str("frames", VVal::Int(info.frames as i64));
+                    Ok(ret)
+                },
+                Err(msg) => Ok(VVal::err_msg(&msg)),
+            }
+        }, Some(1), Some(1), false);
+
+    st.fun(
+        "list_sample_dir", move |env: &mut Env, argc: usize| {
+            let exts : Vec<String> =
+                if argc > 1 {
+                    env.arg(1).s_raw().split(',').map(|e| e.trim().to_string()).collect()
+                } else {
+                    vec!["wav".to_string()]
+                };
+            let exts : Vec<&str> = exts.iter().map(|e| e.as_str()).collect();
+
+            match crate::sample_file::list_dir(&env.arg(0).s_raw(), &exts) {
+                Ok(entries) => {
+                    let ret = VVal::vec();
+                    for e in entries {
+                        ret.push(VVal::map3(
+                            "name",   VVal::new_str_mv(e.name),
+                            "path",   VVal::new_str_mv(e.path),
+                            "is_dir", VVal::Bol(e.is_dir)));
+                    }
+                    Ok(ret)
+                },
+                Err(msg) => Ok(VVal::err_msg(&msg)),
+            }
+        }, Some(1), Some(2), false);
+
+    st.fun(
+        "midi_file_info", move |env: &mut Env, _argc: usize| {
+            match crate::midi_import::load_midi_file(&env.arg(0).s_raw()) {
+                Ok(midi) => {
+                    let tracks = VVal::vec();
+                    for track in midi.tracks.iter() {
+                        tracks.push(VVal::map2(
+                            "name",  VVal::new_str(&track.name),
+                            "notes", VVal::Int(track.notes.len() as i64)));
+                    }
+                    Ok(VVal::map2(
+                        "division", VVal::Int(midi.division as i64),
+                        "tracks",   tracks))
+                },
+                Err(msg) => Ok(VVal::err_msg(&msg)),
+            }
+        }, Some(1), Some(1), false);
+
     st.set(
         "MONITOR_MINMAX_SAMPLES",
         VVal::Int(hexodsp::monitor::MONITOR_MINMAX_SAMPLES as i64));
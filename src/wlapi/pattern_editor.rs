@@ -2,13 +2,17 @@
 // This file is a part of HexoSynth. Released under GPL-3.0-or-later.
 // See README.md and COPYING for details.
 
-//use crate::arg_chk;
+use crate::arg_chk;
 use wlambda::*;
 pub use hexotk::{
     UIPatternModel, PatternData,
     PatternEditorFeedback, PatternEditorFeedbackDummy
 };
 use std::sync::{Arc, Mutex};
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use crate::pattern_history::PatternHistory;
 
 #[derive(Clone)]
 pub struct VVPatEditFb(Arc<Mutex<dyn PatternEditorFeedback>>);
@@ -54,15 +58,340 @@ impl VVPatModel {
     }
 }
 
+fn arg_range_chk(args: &[VVal], min: usize, max: usize, name: &str)
+    -> Result<(), StackAction>
+{
+    if args.len() < min || args.len() > max {
+        return Err(StackAction::panic_msg(format!(
+            "{} called with wrong number of arguments", name)));
+    }
+    Ok(())
+}
+
+fn opt_rng(args: &[VVal], idx: usize) -> crate::pattern_ops::PatRng {
+    use crate::pattern_ops::PatRng;
+
+    match args.get(idx) {
+        Some(seed) if seed.is_some() => PatRng::new(seed.i() as u64),
+        _                            => PatRng::from_time(),
+    }
+}
+
+fn res2vv(res: Result<(), String>) -> VVal {
+    match res {
+        Ok(())   => VVal::Bol(true),
+        Err(msg) => VVal::err_msg(&msg),
+    }
+}
+
 impl VValUserData for VVPatModel {
     fn s(&self) -> String { format!("$<UI::PatModel>") }
     fn as_any(&mut self) -> &mut dyn std::any::Any { self }
     fn clone_ud(&self) -> Box<dyn vval::VValUserData> { Box::new(self.clone()) }
 
-    fn call_method(&self, key: &str, _env: &mut Env)
+    fn call_method(&self, key: &str, env: &mut Env)
+        -> Result<VVal, StackAction>
+    {
+        use crate::pattern_io::*;
+        use crate::pattern_ops::*;
+
+        let args = env.argv_ref();
+
+        let mut pat = self.0.lock().map_err(|_|
+            StackAction::panic_msg("Pattern data can't be locked".to_string()))?;
+
+        match key {
+            "rows" => {
+                arg_chk!(args, 0, "pattern.rows[]");
+
+                Ok(VVal::Int(pat.rows() as i64))
+            },
+            "set_rows" => {
+                arg_chk!(args, 1, "pattern.set_rows[row_count]");
+
+                pat.set_rows(args[0].i().max(1) as usize);
+                Ok(VVal::None)
+            },
+            "cols" => {
+                arg_chk!(args, 0, "pattern.cols[]");
+
+                Ok(VVal::Int(pat.cols() as i64))
+            },
+            "get_cell" => {
+                arg_chk!(args, 2, "pattern.get_cell[row, col]");
+
+                let (row, col) = (args[0].i().max(0) as usize, args[1].i().max(0) as usize);
+                if let Err(msg) = check_row(&*pat, row).and_then(|_| check_col(&*pat, col)) {
+                    return Ok(VVal::err_msg(&msg));
+                }
+
+                Ok(get_cell(&mut *pat, row, col)
+                    .map_or(VVal::None, |v| VVal::Int(v as i64)))
+            },
+            "set_cell" | "clear_cell" => {
+                if key == "set_cell" {
+                    arg_chk!(args, 3, "pattern.set_cell[row, col, value]");
+                } else {
+                    arg_chk!(args, 2, "pattern.clear_cell[row, col]");
+                }
+
+                let (row, col) = (args[0].i().max(0) as usize, args[1].i().max(0) as usize);
+                if let Err(msg) = check_row(&*pat, row).and_then(|_| check_col(&*pat, col)) {
+                    return Ok(VVal::err_msg(&msg));
+                }
+
+                match args.get(2) {
+                    Some(v) if v.is_some() => {
+                        let v = v.i().clamp(0, MAX_CELL_VALUE as i64) as u16;
+                        pat.set_cell_value(row, col, v);
+                    },
+                    _ => pat.clear_cell(row, col),
+                }
+
+                Ok(VVal::Bol(true))
+            },
+            "get_col_type" => {
+                arg_chk!(args, 1, "pattern.get_col_type[col]");
+
+                let col = args[0].i().max(0) as usize;
+                if let Err(msg) = check_col(&*pat, col) {
+                    return Ok(VVal::err_msg(&msg));
+                }
+
+                Ok(VVal::new_sym(col_type(&*pat, col)))
+            },
+            "set_col_type" => {
+                arg_chk!(args, 2, "pattern.set_col_type[col, :note | :step | :value | :gate]");
+
+                let col = args[0].i().max(0) as usize;
+                Ok(res2vv(
+                    check_col(&*pat, col).and_then(|_|
+                        args[1].with_s_ref(|s| set_col_type(&mut *pat, col, s)))))
+            },
+            "transpose" => {
+                arg_chk!(args, 2, "pattern.transpose[col, delta]");
+
+                Ok(res2vv(transpose(&mut *pat, args[0].i().max(0) as usize, args[1].i())))
+            },
+            "rotate_rows" => {
+                arg_chk!(args, 1, "pattern.rotate_rows[offset]");
+
+                rotate_rows(&mut *pat, args[0].i());
+                Ok(VVal::None)
+            },
+            "randomize" => {
+                arg_range_chk(
+                    args, 3, 5, "pattern.randomize[col, min, max, probability, seed]")?;
+
+                let probability = args.get(3).map_or(1.0, |p| p.f());
+                let mut rng     = opt_rng(args, 4);
+
+                Ok(res2vv(randomize(
+                    &mut *pat, args[0].i().max(0) as usize,
+                    args[1].i(), args[2].i(), probability, &mut rng)))
+            },
+            "euclidean" => {
+                arg_range_chk(
+                    args, 2, 5, "pattern.euclidean[col, pulses, steps, rotation, value]")?;
+
+                let steps =
+                    args.get(2).filter(|s| s.is_some())
+                        .map_or(pat.rows(), |s| s.i().max(0) as usize);
+                let rotation = args.get(3).map_or(0, |r| r.i());
+                let value    =
+                    args.get(4).map_or(MAX_CELL_VALUE, |v|
+                        v.i().clamp(0, MAX_CELL_VALUE as i64) as u16);
+
+                Ok(res2vv(euclidean_fill(
+                    &mut *pat, args[0].i().max(0) as usize,
+                    args[1].i().max(0) as usize, steps, rotation, value)))
+            },
+            "humanize" => {
+                arg_range_chk(args, 2, 3, "pattern.humanize[col, amount, seed]")?;
+
+                let mut rng = opt_rng(args, 2);
+
+                Ok(res2vv(humanize(
+                    &mut *pat, args[0].i().max(0) as usize, args[1].i(), &mut rng)))
+            },
+            "to_csv" => {
+                arg_chk!(args, 0, "pattern.to_csv[]");
+
+                Ok(VVal::new_str_mv(pattern_to_csv(&mut *pat)))
+            },
+            "from_csv" => {
+                arg_chk!(args, 1, "pattern.from_csv[csv_string]");
+
+                Ok(res2vv(args[0].with_s_ref(|s| pattern_from_csv(&mut *pat, s))))
+            },
+            "to_text" => {
+                arg_chk!(args, 0, "pattern.to_text[]");
+
+                Ok(VVal::new_str_mv(pattern_to_text(&mut *pat)))
+            },
+            "from_text" => {
+                arg_chk!(args, 1, "pattern.from_text[text]");
+
+                Ok(res2vv(args[0].with_s_ref(|s| pattern_from_text(&mut *pat, s))))
+            },
+            "get_cursor" => {
+                arg_chk!(args, 0, "pattern.get_cursor[]");
+
+                let (row, col) = pat.get_cursor();
+                Ok(VVal::ivec2(row as i64, col as i64))
+            },
+            "set_cursor" => {
+                arg_chk!(args, 2, "pattern.set_cursor[row, col]");
+
+                let (row, col) = (args[0].i().max(0) as usize, args[1].i().max(0) as usize);
+                if let Err(msg) = check_row(&*pat, row).and_then(|_| check_col(&*pat, col)) {
+                    return Ok(VVal::err_msg(&msg));
+                }
+
+                pat.set_cursor(row, col);
+                Ok(VVal::None)
+            },
+            "get_edit_step" => {
+                arg_chk!(args, 0, "pattern.get_edit_step[]");
+
+                Ok(VVal::Int(pat.get_edit_step() as i64))
+            },
+            "copy_block" => {
+                arg_chk!(args, 4, "pattern.copy_block[row, col, rows, cols]");
+
+                let block =
+                    copy_block(&mut *pat,
+                        args[0].i().max(0) as usize, args[1].i().max(0) as usize,
+                        args[2].i().max(0) as usize, args[3].i().max(0) as usize);
+
+                let ret = VVal::vec();
+                for cells in block.iter() {
+                    let row = VVal::vec();
+                    for cell in cells.iter() {
+                        row.push(cell.map_or(VVal::None, |v| VVal::Int(v as i64)));
+                    }
+                    ret.push(row);
+                }
+                Ok(ret)
+            },
+            "paste_block" => {
+                arg_chk!(args, 3, "pattern.paste_block[row, col, block]");
+
+                let mut block : PatternBlock = vec![];
+                args[2].with_iter(|rows| {
+                    for (row, _) in rows {
+                        let mut cells = vec![];
+                        row.with_iter(|it| {
+                            for (cell, _) in it {
+                                cells.push(
+                                    if cell.is_none() { None }
+                                    else { Some(cell.i().clamp(0, MAX_CELL_VALUE as i64) as u16) });
+                            }
+                        });
+                        block.push(cells);
+                    }
+                });
+
+                paste_block(
+                    &mut *pat, args[0].i().max(0) as usize, args[1].i().max(0) as usize,
+                    &block);
+                Ok(VVal::None)
+            },
+            "import_midi" => {
+                arg_range_chk(args, 2, 5,
+                    "pattern.import_midi[path, track, note_col, gate_col, rows_per_beat]")?;
+
+                let opt = |idx: usize, default: i64| {
+                    match args.get(idx) {
+                        Some(v) if v.is_some() => v.i().max(0) as usize,
+                        _                      => default as usize,
+                    }
+                };
+
+                let res =
+                    crate::midi_import::load_midi_file(&args[0].s_raw())
+                        .and_then(|midi| crate::midi_import::import_track(
+                            &mut *pat, &midi, args[1].i().max(0) as usize,
+                            opt(2, 0), opt(3, 1), opt(4, 4)));
+
+                match res {
+                    Ok(info) => Ok(VVal::map3(
+                        "rows",    VVal::Int(info.rows as i64),
+                        "notes",   VVal::Int(info.notes as i64),
+                        "dropped", VVal::Int(info.dropped as i64))),
+                    Err(msg) => Ok(VVal::err_msg(&msg)),
+                }
+            },
+            _ => Ok(VVal::err_msg(&format!("Unknown method called: {}", key))),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct VVPatHistory(Rc<RefCell<PatternHistory>>);
+
+impl VVPatHistory {
+    pub fn new_vv() -> VVal {
+        VVal::new_usr(VVPatHistory(Rc::new(RefCell::new(PatternHistory::new()))))
+    }
+}
+
+impl VValUserData for VVPatHistory {
+    fn s(&self) -> String {
+        format!("$<UI::PatHistory undo={} redo={}>",
+            self.0.borrow().undo_steps(), self.0.borrow().redo_steps())
+    }
+    fn as_any(&mut self) -> &mut dyn std::any::Any { self }
+    fn clone_ud(&self) -> Box<dyn vval::VValUserData> { Box::new(self.clone()) }
+
+    fn call_method(&self, key: &str, env: &mut Env)
         -> Result<VVal, StackAction>
     {
+        let args = env.argv_ref();
+
         match key {
+            "undo_steps" => {
+                arg_chk!(args, 0, "history.undo_steps[]");
+
+                Ok(VVal::Int(self.0.borrow().undo_steps() as i64))
+            },
+            "redo_steps" => {
+                arg_chk!(args, 0, "history.redo_steps[]");
+
+                Ok(VVal::Int(self.0.borrow().redo_steps() as i64))
+            },
+            "reset" | "checkpoint" | "undo" | "redo" => {
+                if args.len() != 1 {
+                    return Err(StackAction::panic_msg(format!(
+                        "history.{}[pattern] called with wrong number of arguments", key)));
+                }
+
+                let model =
+                    match vv2pat_model(args[0].clone()) {
+                        Some(model) => model,
+                        None => {
+                            return Ok(VVal::err_msg(&format!(
+                                "history.{} expects a $<UI::PatModel>, got: {}",
+                                key, args[0].s())));
+                        },
+                    };
+                let mut pat = model.lock().map_err(|_|
+                    StackAction::panic_msg("Pattern data can't be locked".to_string()))?;
+
+                let mut hist = self.0.borrow_mut();
+                let res =
+                    match key {
+                        "reset"      => { hist.reset(&mut *pat); Ok(true) },
+                        "checkpoint" => Ok(hist.checkpoint(&mut *pat)),
+                        "undo"       => hist.undo(&mut *pat),
+                        _            => hist.redo(&mut *pat),
+                    };
+
+                match res {
+                    Ok(changed) => Ok(VVal::Bol(changed)),
+                    Err(msg)    => Ok(VVal::err_msg(&msg)),
+                }
+            },
             _ => Ok(VVal::err_msg(&format!("Unknown method called: {}", key))),
         }
     }
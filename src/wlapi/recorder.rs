@@ -0,0 +1,127 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoSynth. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::arg_chk;
+use crate::audio_tap::TapSource;
+use crate::recorder::Recorder;
+
+use wlambda::*;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+#[derive(Clone)]
+pub struct VValRecorder(Rc<RefCell<Recorder>>);
+
+impl VValRecorder {
+    pub fn new() -> Self {
+        Self(Rc::new(RefCell::new(Recorder::new())))
+    }
+}
+
+impl vval::VValUserData for VValRecorder {
+    fn s(&self) -> String {
+        format!("$<Recorder[{}]>", self.0.borrow().sources().len())
+    }
+
+    fn call_method(&self, key: &str, env: &mut Env)
+        -> Result<VVal, StackAction>
+    {
+        let args = env.argv_ref();
+
+        match key {
+            "set_sources" => {
+                arg_chk!(args, 1, "recorder.set_sources[$[$p(:out | :in, channel), ...]]");
+
+                let mut sources = vec![];
+                for i in 0..args[0].len() {
+                    let src     = args[0].v_(i);
+                    let channel = src.v_i(1).max(0) as usize;
+
+                    if src.v_(0).with_s_ref(|s| s == "mon") {
+                        return Ok(VVal::err_msg(
+                            "The signals of the monitored cell are not available \
+                             at audio rate, connect them to the Out node to \
+                             record them"));
+                    }
+
+                    match src.v_(0).with_s_ref(|s| TapSource::from_str(s, channel)) {
+                        Some(src) => sources.push(src),
+                        None => {
+                            return Ok(VVal::err_msg(
+                                &format!("Unknown recorder source: {}", src.s())));
+                        }
+                    }
+                }
+
+                match self.0.borrow_mut().set_sources(sources) {
+                    Ok(())   => Ok(VVal::Bol(true)),
+                    Err(msg) => Ok(VVal::err_msg(&msg)),
+                }
+            },
+            "get_sources" => {
+                arg_chk!(args, 0, "recorder.get_sources[]");
+
+                let ret = VVal::vec();
+                for src in self.0.borrow().sources() {
+                    ret.push(VVal::pair(
+                        VVal::new_sym(src.kind_str()),
+                        VVal::Int(src.channel() as i64)));
+                }
+
+                Ok(ret)
+            },
+            "get_source_labels" => {
+                arg_chk!(args, 0, "recorder.get_source_labels[]");
+
+                let ret = VVal::vec();
+                for src in self.0.borrow().sources() {
+                    ret.push(VVal::new_str_mv(src.label()));
+                }
+
+                Ok(ret)
+            },
+            "start" => {
+                arg_chk!(args, 1, "recorder.start[path]");
+
+                let path = args[0].s_raw();
+
+                match self.0.borrow_mut().start(&path) {
+                    Ok(())   => Ok(VVal::Bol(true)),
+                    Err(msg) => Ok(VVal::err_msg(&msg)),
+                }
+            },
+            "stop" => {
+                arg_chk!(args, 0, "recorder.stop[]");
+
+                match self.0.borrow_mut().stop() {
+                    Ok(info) => {
+                        let ret = VVal::map3(
+                            "path",        VVal::new_str_mv(info.path),
+                            "frames",      VVal::Int(info.frames as i64),
+                            "sample_rate", VVal::Int(info.sample_rate as i64));
+                        let _ = ret.set_key_str(
+                            "dropped_samples", VVal::Int(info.dropped_samples as i64));
+                        Ok(ret)
+                    },
+                    Err(msg) => Ok(VVal::err_msg(&msg)),
+                }
+            },
+            "is_recording" => {
+                arg_chk!(args, 0, "recorder.is_recording[]");
+
+                Ok(VVal::Bol(self.0.borrow().is_recording()))
+            },
+            "recorded_secs" => {
+                arg_chk!(args, 0, "recorder.recorded_secs[]");
+
+                Ok(VVal::Flt(self.0.borrow().recorded_secs() as f64))
+            },
+            _ => Ok(VVal::err_msg(&format!("Unknown method called: {}", key))),
+        }
+    }
+
+    fn as_any(&mut self) -> &mut dyn std::any::Any { self }
+    fn clone_ud(&self) -> Box<dyn vval::VValUserData> { Box::new(self.clone()) }
+}
@@ -19,6 +19,41 @@ impl VValSampleBuf {
     }
 }
 
+/// Scales the samples so that the highest absolute value is `peak`.
+fn normalize(buf: &mut [f32], peak: f32) {
+    let max = buf.iter().fold(0.0_f32, |m, s| m.max(s.abs()));
+    if max <= 0.0 { return; }
+
+    let gain = peak / max;
+    for s in buf.iter_mut() { *s *= gain; }
+}
+
+/// Applies a linear fade over the first (`fade_in`) or
+/// last `frames` samples.
+fn fade(buf: &mut [f32], frames: usize, fade_in: bool) {
+    let frames = frames.min(buf.len());
+    let len    = buf.len();
+
+    for i in 0..frames {
+        let gain = i as f32 / frames as f32;
+
+        if fade_in { buf[i] *= gain; }
+        else       { buf[len - 1 - i] *= gain; }
+    }
+}
+
+/// Returns the range of `buf`, without the silence at
+/// the start and the end.
+fn non_silent_range(buf: &[f32], threshold: f32) -> std::ops::Range<usize> {
+    let start = buf.iter().position(|s| s.abs() > threshold);
+    let end   = buf.iter().rposition(|s| s.abs() > threshold);
+
+    match (start, end) {
+        (Some(start), Some(end)) => start..(end + 1),
+        _                        => 0..0,
+    }
+}
+
 impl vval::VValUserData for VValSampleBuf {
     fn s(&self) -> String {
         let size = self.buf.lock().map_or(0, |guard| guard.len());
@@ -59,6 +94,153 @@ impl vval::VValUserData for VValSampleBuf {
                 let size = self.buf.lock().map_or(0, |guard| guard.len());
                 Ok(VVal::Int(size as i64))
             },
+            "normalize" => {
+                if args.len() > 1 {
+                    return Err(StackAction::panic_msg(
+                        "sample_buf.normalize[peak] called with wrong number \
+                         of arguments".to_string()));
+                }
+
+                let peak = if args.len() > 0 { args[0].f() as f32 } else { 1.0 };
+
+                if let Ok(mut guard) = self.buf.lock() {
+                    normalize(&mut guard[..], peak);
+                }
+                Ok(VVal::None)
+            },
+            "reverse" => {
+                arg_chk!(args, 0, "sample_buf.reverse[]");
+
+                if let Ok(mut guard) = self.buf.lock() {
+                    guard.reverse();
+                }
+                Ok(VVal::None)
+            },
+            "fade_in" | "fade_out" => {
+                arg_chk!(args, 1, "sample_buf.fade_in[frames] / sample_buf.fade_out[frames]");
+
+                let frames = args[0].i().max(0) as usize;
+
+                if let Ok(mut guard) = self.buf.lock() {
+                    fade(&mut guard[..], frames, key == "fade_in");
+                }
+                Ok(VVal::None)
+            },
+            "trim_silence" => {
+                if args.len() > 1 {
+                    return Err(StackAction::panic_msg(
+                        "sample_buf.trim_silence[threshold] called with wrong \
+                         number of arguments".to_string()));
+                }
+
+                let threshold =
+                    if args.len() > 0 { args[0].f() as f32 } else { 0.001 };
+
+                if let Ok(mut guard) = self.buf.lock() {
+                    let range = non_silent_range(&guard[..], threshold);
+                    guard.truncate(range.end);
+                    guard.drain(0..range.start);
+                }
+                Ok(VVal::None)
+            },
+            "resample" => {
+                arg_chk!(args, 2, "sample_buf.resample[from_rate, to_rate]");
+
+                let from = args[0].i().max(0) as u32;
+                let to   = args[1].i().max(0) as u32;
+
+                if let Ok(mut guard) = self.buf.lock() {
+                    *guard = crate::sample_file::resample(&guard[..], from, to);
+                }
+                Ok(VVal::None)
+            },
+            "slice" => {
+                arg_chk!(args, 2, "sample_buf.slice[start, end]");
+
+                let guard = self.buf.lock().map_err(|_|
+                    StackAction::panic_msg("SampleBuf can't be locked".to_string()))?;
+
+                let end   = (args[1].i().max(0) as usize).min(guard.len());
+                let start = (args[0].i().max(0) as usize).min(end);
+
+                Ok(sample_buf2vv(Arc::new(Mutex::new(guard[start..end].to_vec()))))
+            },
+            "slices" => {
+                arg_chk!(args, 1, "sample_buf.slices[count]");
+
+                let guard = self.buf.lock().map_err(|_|
+                    StackAction::panic_msg("SampleBuf can't be locked".to_string()))?;
+
+                let count = args[0].i().max(1) as usize;
+                let len   = guard.len();
+                let ret   = VVal::vec();
+
+                for i in 0..count {
+                    let (start, end) = ((i * len) / count, ((i + 1) * len) / count);
+                    ret.push(sample_buf2vv(
+                        Arc::new(Mutex::new(guard[start..end].to_vec()))));
+                }
+
+                Ok(ret)
+            },
+            "mix" => {
+                if args.len() < 1 || args.len() > 3 {
+                    return Err(StackAction::panic_msg(
+                        "sample_buf.mix[other_buf, gain, other_gain] called with \
+                         wrong number of arguments".to_string()));
+                }
+
+                let other =
+                    if let Some(other) = vv2sample_buf(args[0].clone()) { other }
+                    else {
+                        return Ok(VVal::err_msg(
+                            &format!("Can't mix, not a SampleBuf: {}", args[0].s())));
+                    };
+
+                let gain       = if args.len() > 1 { args[1].f() as f32 } else { 1.0 };
+                let other_gain = if args.len() > 2 { args[2].f() as f32 } else { 1.0 };
+
+                // Copy first, the other buffer might be this one:
+                let other : Vec<f32> =
+                    other.lock().map_or_else(|_| vec![], |guard| guard.clone());
+
+                if let Ok(mut guard) = self.buf.lock() {
+                    if other.len() > guard.len() {
+                        guard.resize(other.len(), 0.0);
+                    }
+
+                    for (i, s) in guard.iter_mut().enumerate() {
+                        *s = *s * gain + other.get(i).copied().unwrap_or(0.0) * other_gain;
+                    }
+                }
+                Ok(VVal::None)
+            },
+            "to_sample_atom" => {
+                arg_chk!(args, 2, "sample_buf.to_sample_atom[name, sample_rate]");
+
+                let guard = self.buf.lock().map_err(|_|
+                    StackAction::panic_msg("SampleBuf can't be locked".to_string()))?;
+
+                let mut v = Vec::with_capacity(guard.len() + 1);
+                v.push(args[1].f() as f32);
+                v.extend_from_slice(&guard[..]);
+
+                Ok(super::atom2vv(
+                    hexodsp::SAtom::audio(&args[0].s_raw(), Arc::new(v))))
+            },
+            "save_wav" => {
+                arg_chk!(args, 2, "sample_buf.save_wav[path, sample_rate]");
+
+                let guard = self.buf.lock().map_err(|_|
+                    StackAction::panic_msg("SampleBuf can't be locked".to_string()))?;
+
+                match crate::sample_file::save_wav(
+                    &args[0].s_raw(), &guard[..], args[1].i().max(1) as u32)
+                {
+                    Ok(())   => Ok(VVal::Bol(true)),
+                    Err(msg) => Ok(VVal::err_msg(&msg)),
+                }
+            },
             _ => Ok(VVal::err_msg(&format!("Unknown method called: {}", key))),
         }
     }
@@ -158,13 +158,20 @@ editor.reg :set_focus {!(cell) = @;
 
     grid.reg :click {
         std:displayln "GRID CLICK:" @;
-        editor.set_focus_cell $i(@.1.x, @.1.y);
+        !pos = $i(@.1.x, @.1.y);
+        editor.set_focus_cell pos;
+
+        if @.1.button == :right {
+            editor.inspect_cell_connections pos;
+        };
+
         click_cb[];
     };
 
     grid.reg :hex_drag {!(wid, ev) = @;
         !src = $i(ev.x_src, ev.y_src);
         !dst = $i(ev.x_dst, ev.y_dst);
+
         editor.handle_drag_gesture src dst ev.button;
     };
 
@@ -238,17 +245,85 @@ top_menu_button_bar.add about_button;
 };
 top_menu_button_bar.add about_button;
 
+!lint_button = styling:new_button_with_label :button_float_menu "Lint" {
+    editor.handle_top_menu_click :lint;
+};
+top_menu_button_bar.add lint_button;
+
+!flow_button = styling:new_button_with_label :button_float_menu "Flow" {
+    editor.handle_top_menu_click :signal_flow;
+};
+top_menu_button_bar.add flow_button;
+
+!mod_button = styling:new_button_with_label :button_float_menu "Mod" {
+    editor.handle_top_menu_click :mod_matrix;
+};
+top_menu_button_bar.add mod_button;
+
+!route_button_txt = ui:txt "Route";
+!route_button = styling:new_widget :button_float_menu;
+route_button.set_ctrl :button route_button_txt;
+route_button.reg :click {
+    editor.handle_top_menu_click :auto_route;
+};
+top_menu_button_bar.add route_button;
+
+editor.reg :auto_route_changed {!(armed) = @;
+    route_button_txt.set ~ if armed { "[Route]" } { "Route" };
+};
+
+!rec_button_txt = ui:txt "Rec";
+!rec_button = styling:new_widget :button_float_menu;
+rec_button.set_ctrl :button rec_button_txt;
+rec_button.reg :click {
+    editor.handle_top_menu_click :record;
+};
+top_menu_button_bar.add rec_button;
+
+!rec_src_button_txt = ui:txt editor.get_rec_source_label[];
+!rec_src_button = styling:new_widget :button_float_menu;
+rec_src_button.set_ctrl :button rec_src_button_txt;
+rec_src_button.reg :click { editor.rec_cycle_source[]; };
+top_menu_button_bar.add rec_src_button;
+
+editor.reg :rec_source_changed {!(label) = @;
+    rec_src_button_txt.set label;
+};
+
+editor.reg :recording_changed {!(active) = @;
+    rec_button_txt.set ~ if active { "Stop" } { "Rec" };
+};
+
+!update_rec_button = {
+    if editor.is_recording[] {
+        rec_button_txt.set ~ $F "Stop {:.0!f}s" editor.recorded_secs[];
+    };
+};
+
+!find_button = styling:new_button_with_label :button_float_menu "Find" {
+    editor.handle_top_menu_click :palette;
+};
+top_menu_button_bar.add find_button;
+
 !save_btn = styling:new_button_with_label :button_float_menu "Save" {
-    matrix.save_patch "init.hxy";
+    editor.save_patch "init.hxy";
 };
 top_menu_button_bar.add save_btn;
+!storage_btn_txt = ui:txt "Smp:path";
+!storage_btn = styling:new_widget :button_float_menu;
+storage_btn.set_ctrl :button storage_btn_txt;
+storage_btn.reg :click {
+    storage_btn_txt.set ~ $F "Smp:{}" editor.cycle_sample_storage[];
+};
+top_menu_button_bar.add storage_btn;
 !load_btn = styling:new_button_with_label :button_float_menu "Load" {
     matrix.load_patch "init.hxy";
+    editor.handle_patch_loaded[];
 };
 top_menu_button_bar.add load_btn;
 
 !color_btn = styling:new_button_with_label :button_float_menu "_C" {
-    editor.show_color_info[];
+    editor.open_node_style[];
 };
 top_menu_button_bar.add color_btn;
 
@@ -281,8 +356,133 @@ editor.reg :pattern_editor_set_data {!(tracker_id, data) = _;
     patedit.set_ctrl :pattern_editor data;
 };
 
+!patedit_io_row = styling:new_widget :pattern_io_row;
+patedit_io_row.add ~ styling:new_button_with_label :pattern_io_button "Export" {
+    editor.export_pattern[];
+};
+patedit_io_row.add ~ styling:new_button_with_label :pattern_io_button "Import" {
+    editor.import_pattern[];
+};
+patedit_io_row.add ~ styling:new_button_with_label :pattern_io_button "MIDI" {
+    editor.open_midi_browser[];
+};
+!midi_track_btn_txt = ui:txt editor.midi_import_track_label[];
+!midi_track_btn = styling:new_widget :pattern_io_button;
+midi_track_btn.set_ctrl :button midi_track_btn_txt;
+midi_track_btn.reg :click {
+    midi_track_btn_txt.set editor.cycle_midi_import_track[];
+};
+patedit_io_row.add midi_track_btn;
+patedit_io_row.add ~ styling:new_button_with_label :pattern_io_button "All" {
+    editor.open_tseq_overview[];
+};
+
+!patedit_song_row = styling:new_widget :pattern_io_row;
+patedit_song_row.add ~ styling:new_button_with_label :pattern_io_button "<" {
+    editor.select_next_pattern (-1);
+};
+!pattern_idx_txt = ui:txt "Pat:0/1";
+!pattern_idx_lbl = styling:new_widget :pattern_song_label;
+pattern_idx_lbl.set_ctrl :label pattern_idx_txt;
+patedit_song_row.add pattern_idx_lbl;
+patedit_song_row.add ~ styling:new_button_with_label :pattern_io_button ">" {
+    editor.select_next_pattern 1;
+};
+!song_mode_btn_txt = ui:txt "Song:off";
+!song_mode_btn = styling:new_widget :pattern_io_button;
+song_mode_btn.set_ctrl :button song_mode_btn_txt;
+song_mode_btn.reg :click { editor.toggle_pattern_song_mode[]; };
+patedit_song_row.add song_mode_btn;
+patedit_song_row.add ~ styling:new_button_with_label :pattern_io_button "Chain+" {
+    editor.chain_append_pattern[];
+};
+patedit_song_row.add ~ styling:new_button_with_label :pattern_io_button "Clr" {
+    editor.chain_clear[];
+};
+!pattern_ctl_btn_txt = ui:txt "Ctl";
+!pattern_ctl_btn = styling:new_widget :pattern_io_button;
+pattern_ctl_btn.set_ctrl :button pattern_ctl_btn_txt;
+pattern_ctl_btn.reg :click { editor.toggle_pattern_control[]; };
+patedit_song_row.add pattern_ctl_btn;
+
+!pattern_chain_txt = ui:txt "";
+!pattern_chain_lbl = styling:new_widget :pattern_chain_label;
+pattern_chain_lbl.set_ctrl :label pattern_chain_txt;
+
+editor.reg :pattern_song_changed {!(tracker_id, song) = @;
+    pattern_idx_txt.set ~ $F "Pat:{}/{}" song.current song.count;
+    song_mode_btn_txt.set ~ if song.song_mode { "Song:on" } { "Song:off" };
+    pattern_ctl_btn_txt.set
+        ~ if is_some[song.control] { $F "Ctl:{}" song.control.0.0 } { "Ctl" };
+    pattern_chain_txt.set
+        ~ if len[song.chain] == 0 { "Chain: -" } {
+            $F "Chain:{}"
+                ~ $@s iter i 0 => len[song.chain] {
+                    $+ ~ if i == song.chain_pos &and song.song_mode
+                        { $F " [{}]" song.chain.(i) }
+                        { $F " {}" song.chain.(i) };
+                };
+        };
+};
+editor.emit_pattern_song[];
+
+!patedit_edit_row = styling:new_widget :pattern_io_row;
+!follow_btn_txt = ui:txt "Follow";
+!follow_btn = styling:new_widget :pattern_io_button;
+follow_btn.set_ctrl :button follow_btn_txt;
+follow_btn.reg :click { editor.toggle_pattern_follow[]; };
+patedit_edit_row.add follow_btn;
+!record_btn_txt = ui:txt "Rec";
+!record_btn = styling:new_widget :pattern_io_button;
+record_btn.set_ctrl :button record_btn_txt;
+record_btn.reg :click { editor.toggle_pattern_record[]; };
+patedit_edit_row.add record_btn;
+patedit_edit_row.add ~ styling:new_button_with_label :pattern_io_button "Undo" {
+    editor.pattern_undo[];
+};
+patedit_edit_row.add ~ styling:new_button_with_label :pattern_io_button "Redo" {
+    editor.pattern_redo[];
+};
+patedit_edit_row.add ~ styling:new_button_with_label :pattern_io_button "Mark" {
+    editor.pattern_mark[];
+};
+patedit_edit_row.add ~ styling:new_button_with_label :pattern_io_button "Copy" {
+    editor.pattern_copy[];
+};
+patedit_edit_row.add ~ styling:new_button_with_label :pattern_io_button "Paste" {
+    editor.pattern_paste[];
+};
+
+editor.reg :pattern_edit_mode_changed {
+    follow_btn_txt.set ~ if editor.is_pattern_follow[] { "[Follow]" } { "Follow" };
+    record_btn_txt.set ~ if editor.is_pattern_record[] { "[Rec]" } { "Rec" };
+};
+
+# Note keys for recording notes into the pattern:
+!patedit_note_row = styling:new_widget :pattern_io_row;
+!record_octave_txt = ui:txt ~ $F "O{}" editor.get_record_octave[];
+patedit_note_row.add ~ styling:new_button_with_label :pattern_note_button "-" {
+    record_octave_txt.set ~ $F "O{}" ~ editor.change_record_octave (-1);
+};
+!record_octave_lbl = styling:new_widget :pattern_note_label;
+record_octave_lbl.set_ctrl :label record_octave_txt;
+patedit_note_row.add record_octave_lbl;
+patedit_note_row.add ~ styling:new_button_with_label :pattern_note_button "+" {
+    record_octave_txt.set ~ $F "O{}" ~ editor.change_record_octave 1;
+};
+
+# A click on a key or a note-on of the MIDI input records the note:
+!patedit_note_keys = styling:new_widget :pattern_note_keys;
+patedit_note_keys.set_ctrl :octave_keys editor.get_note_keys_model[];
+
 !patedit_container = styling:new_widget :pattern_editor_container;
 patedit_container.add patedit_label;
+patedit_container.add patedit_io_row;
+patedit_container.add patedit_song_row;
+patedit_container.add pattern_chain_lbl;
+patedit_container.add patedit_edit_row;
+patedit_container.add patedit_note_row;
+patedit_container.add patedit_note_keys;
 patedit_container.add patedit;
 
 right_panel_container.add patedit_container;
@@ -338,19 +538,57 @@ con.set_ctrl :connector con_data;
 con.reg :change {
     std:displayln "NEW CON:" con_data.get_connection[];
     connector_popup.hide[];
+    editor.close_link_inspector[];
     connect_cb con_data.get_connection[];
 };
 
+!con_value_txt = ui:txt "";
+!con_value = styling:new_widget :connector_value;
+con_value.set_ctrl :label con_value_txt;
+
 connector_popup.set_ctrl :rect $n;
 connector_popup.auto_hide[];
+connector_popup.add con_value;
 connector_popup.add con;
 
 !clear_con = styling:new_button_with_label :button_label "Clear" {
     connector_popup.hide[];
+    editor.close_link_inspector[];
     connect_cb $n;
 };
 connector_popup.add clear_con;
 
+!update_con_value = {
+    if not[connector_popup.is_visible[]] { return $n };
+
+    !value = editor.inspected_link_value[];
+    con_value_txt.set ~
+        if is_some[value] { $F "Value: {:.3!f}" value.0 }
+                          { "(not connected)" };
+};
+
+!connection_select_popup = styling:new_widget :connection_select_popup;
+connection_select_popup.auto_hide[];
+
+editor.reg :select_connection {!(connections, select_cb) = @;
+    connection_select_popup.remove_childs[];
+
+    iter con connections {
+        !btn = styling:new_widget :connection_select_item;
+        btn.set_ctrl :button (ui:txt con.label);
+
+        !my_con = con;
+        btn.reg :click {
+            connection_select_popup.hide[];
+            select_cb my_con;
+        };
+
+        connection_select_popup.add btn;
+    };
+
+    connection_select_popup.popup_at_mouse[];
+};
+
 connector_popup.change_layout ${
     position_type = :self,
     layout_type   = :column,
@@ -382,7 +620,302 @@ editor.reg :show_main_help {!(text) = @;
     help_wichtext.show[];
 };
 
+!palette_popup = styling:new_widget :palette_popup;
+palette_popup.set_ctrl :rect $n;
+palette_popup.auto_hide[];
+
+!palette_page_txt = ui:txt "";
+!palette_nav = styling:new_widget :palette_nav;
+
+!palette_results = styling:new_widget :palette_results;
+
+palette_popup.add palette_nav;
+palette_popup.add palette_results;
+
+!palette_page = 0;
+
+!palette_execute = {!(entry) = @;
+    palette_popup.hide[];
+    editor.palette_execute entry;
+};
+
+!palette_update = {
+    !res = editor.palette_page palette_page;
+    .palette_page = res.page;
+
+    palette_page_txt.set ~ $F "Page {} / {}" (res.page + 1) res.pages;
+    palette_results.remove_childs[];
+
+    iter entry res.entries {
+        !item = styling:new_widget :palette_item;
+
+        !kind_lbl = if entry.kind == :node { "node" } { "cmd" };
+        item.set_ctrl :button (ui:txt ~ $F "{} ({})" entry.label kind_lbl);
+
+        !my_entry = entry;
+        item.reg :click { palette_execute my_entry };
+        item.reg :hover {
+            if my_entry.kind == :node {
+                editor.handle_hover :node_picker my_entry.node;
+            };
+        };
+
+        palette_results.add item;
+    };
+};
+
+palette_nav.add ~ styling:new_button_with_label :palette_nav_btn "Prev" {
+    .palette_page = palette_page - 1;
+    palette_update[];
+};
+!palette_page_lbl = styling:new_widget :palette_page;
+palette_page_lbl.set_ctrl :label palette_page_txt;
+palette_nav.add palette_page_lbl;
+palette_nav.add ~ styling:new_button_with_label :palette_nav_btn "Next" {
+    .palette_page = palette_page + 1;
+    palette_update[];
+};
+
+editor.reg :open_palette {
+    .palette_page = 0;
+    palette_update[];
+    palette_popup.popup_at_mouse[];
+};
+
+popup_layer.add palette_popup;
+!node_style_popup = styling:new_widget :node_style_popup;
+node_style_popup.set_ctrl :rect $n;
+node_style_popup.auto_hide[];
+
+!node_style_node_id   = $n;
+!node_style_label_txt = ui:txt "";
+
+# The labels are set by scripts with `editor.set_node_label`,
+# the popup only shows and clears them:
+!node_style_label_row = styling:new_widget :node_style_label_row;
+node_style_popup.add node_style_label_row;
+
+!node_style_label = styling:new_widget :node_style_label;
+node_style_label.set_ctrl :label node_style_label_txt;
+node_style_label_row.add node_style_label;
+
+node_style_label_row.add ~
+    styling:new_button_with_label :node_style_clear_label "Clear" {
+        node_style_popup.hide[];
+        editor.set_node_label node_style_node_id "";
+    };
+
+!node_style_color_row = $n;
+iter clr_idx 0 => len[ui:STD_COLORS] {
+    if clr_idx % 10 == 0 {
+        .node_style_color_row = styling:new_widget :node_style_color_row;
+        node_style_popup.add node_style_color_row;
+    };
+
+    !clr_btn = styling:new_widget :node_style_color_btn;
+    styling:apply_color_idx_border clr_btn clr_idx;
+    clr_btn.set_ctrl :button (ui:txt ~ str clr_idx);
+
+    !my_clr_idx = clr_idx;
+    clr_btn.reg :click {
+        node_style_popup.hide[];
+        editor.set_node_color node_style_node_id my_clr_idx;
+    };
+
+    node_style_color_row.add clr_btn;
+};
+
+editor.reg :open_node_style {!(node_id, label, color_idx) = @;
+    .node_style_node_id = node_id;
+    node_style_label_txt.set
+        ~ $F "Label: {}" (if is_some[label] { label } { "(none)" });
+    node_style_popup.popup_at_mouse[];
+};
+
+!sample_file_name = {!(path) = @;
+    if len[path] == 0 { return "(no sample)" };
+    !parts = $p("/", 0) path;
+    parts.(len[parts] - 1)
+};
+
+!sample_browser_popup = styling:new_widget :sample_browser_popup;
+sample_browser_popup.set_ctrl :rect $n;
+sample_browser_popup.auto_hide[];
+
+!sample_browser_dir_txt = ui:txt "";
+!sample_browser_dir = styling:new_widget :sample_browser_dir;
+sample_browser_dir.set_ctrl :label sample_browser_dir_txt;
+sample_browser_popup.add sample_browser_dir;
+
+!sample_browser_list = styling:new_widget :sample_browser_list;
+sample_browser_popup.add sample_browser_list;
+
+editor.reg :sample_dir_listing {!(dir, entries) = @;
+    sample_browser_dir_txt.set dir;
+    sample_browser_list.remove_childs[];
+
+    iter entry entries {
+        !btn = styling:new_widget
+            (if entry.is_dir { :sample_browser_dir_item } { :sample_browser_item });
+        btn.set_ctrl :button
+            (ui:txt ~ if entry.is_dir { entry.name "/" } { entry.name });
+
+        !my_entry = entry;
+        btn.reg :click {
+            if not[my_entry.is_dir] { sample_browser_popup.hide[]; };
+            editor.select_sample_entry my_entry;
+        };
+
+        sample_browser_list.add btn;
+    };
+};
+
+# The label of the sample button in the parameter panel:
+!sample_button_lbl = $n;
+
+editor.reg :sample_loaded {!(atom, path) = @;
+    if is_some[sample_button_lbl] {
+        sample_button_lbl.set ~ sample_file_name path;
+    };
+};
+
+editor.reg :open_sample_browser {!(atom) = @;
+    sample_browser_popup.popup_at_mouse[];
+};
+
+!tseq_overview_popup = styling:new_widget :tseq_overview_popup;
+tseq_overview_popup.set_ctrl :rect $n;
+tseq_overview_popup.auto_hide[];
+
+!tseq_overview_cols = styling:new_widget :tseq_overview_cols;
+tseq_overview_popup.add tseq_overview_cols;
+
+# One column per TSeq, the pattern editors show the playheads.
+# Clicking the header of a column edits that TSeq:
+editor.reg :open_tseq_overview {!(trackers) = @;
+    tseq_overview_cols.remove_childs[];
+
+    if len[trackers] == 0 {
+        !lbl = styling:new_widget :tseq_overview_header;
+        lbl.set_ctrl :label (ui:txt "No TSeq in the patch");
+        tseq_overview_cols.add lbl;
+    };
+
+    iter trk trackers {
+        !col = styling:new_widget :tseq_overview_col;
+
+        !my_tracker_id = trk.tracker_id;
+        col.add ~ styling:new_button_with_label :tseq_overview_header
+            ($F "TSeq {} - Pat {}/{}" trk.tracker_id trk.song.current trk.song.count)
+            {
+                tseq_overview_popup.hide[];
+                editor.edit_tracker my_tracker_id;
+            };
+
+        !patedit = styling:new_widget :tseq_overview_pattern;
+        patedit.set_ctrl :pattern_editor $[6, trk.pattern, trk.feedback];
+        col.add patedit;
+
+        tseq_overview_cols.add col;
+    };
+
+    tseq_overview_popup.popup_at_mouse[];
+};
+
+!mod_matrix_popup = styling:new_widget :mod_matrix_popup;
+mod_matrix_popup.set_ctrl :rect $n;
+mod_matrix_popup.auto_hide[];
+
+!mod_matrix_title = ui:txt "Modulation";
+!mod_matrix_title_lbl = styling:new_widget :mod_matrix_title;
+mod_matrix_title_lbl.set_ctrl :label mod_matrix_title;
+mod_matrix_popup.add mod_matrix_title_lbl;
+
+# The bulk operations apply to all listed parameters:
+!mod_matrix_bulk_row = styling:new_widget :mod_matrix_row;
+mod_matrix_bulk_row.add ~ styling:new_button_with_label :mod_matrix_button "All" {
+    editor.set_mod_filter_node $n;
+};
+mod_matrix_bulk_row.add ~ styling:new_button_with_label :mod_matrix_button "x0.5" {
+    editor.scale_mod_amounts 0.5;
+};
+mod_matrix_bulk_row.add ~ styling:new_button_with_label :mod_matrix_button "x2" {
+    editor.scale_mod_amounts 2.0;
+};
+mod_matrix_bulk_row.add ~ styling:new_button_with_label :mod_matrix_button "Inv" {
+    editor.invert_mod_amounts[];
+};
+mod_matrix_bulk_row.add ~ styling:new_button_with_label :mod_matrix_button "Clear" {
+    editor.clear_mod_amounts[];
+};
+mod_matrix_popup.add mod_matrix_bulk_row;
+
+!mod_matrix_list = styling:new_widget :mod_matrix_list;
+mod_matrix_popup.add mod_matrix_list;
+
+editor.reg :open_mod_matrix {
+    mod_matrix_popup.popup_at_mouse[];
+};
+
+# One row per modulated parameter. Clicking the node name lists only
+# the parameters of that node, "xN" removes all of its modulation:
+editor.reg :mod_matrix_changed {!(entries, filter_node) = @;
+    mod_matrix_title.set ~
+        if is_none[filter_node]
+            { "Modulation - all nodes" }
+            { $F "Modulation - {} {}" filter_node.0 filter_node.1 };
+
+    mod_matrix_list.remove_childs[];
+
+    if len[entries] == 0 {
+        !lbl = styling:new_widget :mod_matrix_param;
+        lbl.set_ctrl :label (ui:txt "No modulated parameters");
+        mod_matrix_list.add lbl;
+    };
+
+    iter e entries {
+        !my_entry = e;
+        !row = styling:new_widget :mod_matrix_row;
+
+        row.add ~ styling:new_button_with_label :mod_matrix_node
+            ($F "{} {}" e.node_id.0 e.node_id.1)
+            { editor.set_mod_filter_node my_entry.node_id; };
+
+        !src = e.source;
+        !param_lbl = styling:new_widget :mod_matrix_param;
+        param_lbl.set_ctrl :label (ui:txt ~
+            if is_none[src]
+                { $F "{} <- (none)" e.name }
+                { $F "{} <- {} {}:{}" e.name src.0.0 src.0.1 src.1 });
+        row.add param_lbl;
+
+        !amt_lbl = styling:new_widget :mod_matrix_amount;
+        amt_lbl.set_ctrl :label (ui:txt ~ $F "{:.3!f}" e.amount);
+        row.add amt_lbl;
+
+        row.add ~ styling:new_button_with_label :mod_matrix_button "-" {
+            editor.nudge_mod_amount my_entry (-0.05);
+        };
+        row.add ~ styling:new_button_with_label :mod_matrix_button "+" {
+            editor.nudge_mod_amount my_entry 0.05;
+        };
+        row.add ~ styling:new_button_with_label :mod_matrix_button "x" {
+            editor.clear_mod_amount my_entry;
+        };
+        row.add ~ styling:new_button_with_label :mod_matrix_button "xN" {
+            editor.clear_node_mod_amounts my_entry.node_id;
+        };
+
+        mod_matrix_list.add row;
+    };
+};
+
+popup_layer.add mod_matrix_popup;
+popup_layer.add tseq_overview_popup;
+popup_layer.add sample_browser_popup;
+popup_layer.add node_style_popup;
 popup_layer.add connector_popup;
+popup_layer.add connection_select_popup;
 popup_layer.add mode_selector_popup;
 popup_layer.add help_wichtext;
 
@@ -478,12 +1011,55 @@ editor.reg :setup_edit_connection {
         con_data.clear_connection[];
     };
 
+    update_con_value[];
     connector_popup.popup_at_mouse[];
     std:displayln "SETUP EDIT CON:" con;
 };
 
+!create_sample_editor_widgets = {
+    !sed = editor.create_sample_editor[];
+    if is_none[sed] { return $[] };
+
+    !wave = styling:new_widget :sample_waveform;
+    wave.set_ctrl :graph_minmax $[256, sed.waveform_model[]];
+
+    !markers = styling:new_widget :sample_markers;
+    markers.set_ctrl :graph $[256, $f, sed.markers_model[]];
+
+    !row = styling:new_widget :sample_edit_row;
+
+    !point_lbl = ui:txt "start";
+    !point_btn = styling:new_widget :sample_edit_button;
+    point_btn.set_ctrl :button point_lbl;
+    point_btn.reg :click {
+        point_lbl.set ~ editor.sample_cycle_edit_point[];
+    };
+    row.add point_btn;
+
+    iter btn $[
+        $["<<",   { editor.sample_move_point (-0.1) }],
+        $["<",    { editor.sample_move_point (-0.01) }],
+        $[">",    { editor.sample_move_point 0.01 }],
+        $[">>",   { editor.sample_move_point 0.1 }],
+        $["Zoom+", { editor.sample_zoom 2.0 }],
+        $["Zoom-", { editor.sample_zoom 0.5 }],
+        $["<V",   { editor.sample_pan (-0.5) }],
+        $["V>",   { editor.sample_pan 0.5 }],
+        $["All",  { editor.sample_reset_view[] }],
+    ] {
+        !b = styling:new_widget :sample_edit_button;
+        b.set_ctrl :button (ui:txt btn.0);
+        b.reg :click btn.1;
+        row.add b;
+    };
+
+    $[wave, markers, row]
+};
+
 editor.reg :update_param_ui {
     param_panel.remove_childs[];
+    .sample_button_lbl = $n;
+    editor.close_sample_editor[];
     !plist = editor.get_current_param_list[];
 
     !knob_row = styling:new_widget :knob_row;
@@ -564,6 +1140,23 @@ editor.reg :update_param_ui {
                         editor.handle_hover :param_knob my_atom;
                     };
                 }
+                :sample => {
+                    !wid = styling:new_widget :sample_button;
+                    !path = (matrix.get_param atom).s[];
+                    .sample_button_lbl = ui:txt ~ sample_file_name path;
+                    wid.set_ctrl :button sample_button_lbl;
+                    !my_atom = atom;
+                    wid.reg :click {
+                        editor.open_sample_browser my_atom;
+                    };
+                    wid.reg :hover {
+                        editor.handle_hover :param_knob my_atom;
+                    };
+                    iter sed_wid create_sample_editor_widgets[] {
+                        std:push extra_widgets sed_wid;
+                    };
+                    wid
+                }
                 :keys => {
                     !wid = styling:new_widget :keys;
                     !model = matrix.create_octave_keys_model atom;
@@ -644,11 +1237,137 @@ moni_panel.add moni_col_outputs;
 
 signal_panel.add moni_panel;
 
+!scope_panel = styling:new_widget :scope_panel;
+
+!scope_btns = styling:new_widget :scope_button_row;
+!add_scope_btn = {!(label, cb) = @;
+    scope_btns.add ~ styling:new_button_with_label :scope_button label cb;
+};
+add_scope_btn "Add"    { editor.scope_add_channel[]; };
+add_scope_btn "Clear"  { editor.scope_clear[]; };
+add_scope_btn "Freeze" { editor.scope_toggle_freeze[]; };
+add_scope_btn "Trig"   { editor.scope_cycle_trigger[]; };
+add_scope_btn "Lvl+"   { editor.scope_trigger_level 0.1; };
+add_scope_btn "Lvl-"   { editor.scope_trigger_level (-0.1); };
+add_scope_btn "T+"     { editor.scope_time_base 2.0; };
+add_scope_btn "T-"     { editor.scope_time_base 0.5; };
+scope_panel.add scope_btns;
+
+!scope_status_txt = ui:txt "";
+!scope_status = styling:new_widget :scope_status;
+scope_status.set_ctrl :label scope_status_txt;
+scope_panel.add scope_status;
+
+editor.reg :update_scope_status {!(text) = @;
+    scope_status_txt.set text;
+};
+
+!scope_graphs = styling:new_widget :scope_graphs;
+scope_panel.add scope_graphs;
+
+# All audio channels are drawn overlaid in the first lane, below that each
+# channel is drawn in it's own lane and color. The lanes of the audio channels
+# are aligned in time, because they share the trigger and time base. The lanes
+# of the monitored cell show the last seconds of the cell monitor:
+!rebuild_scope_graphs = {!(scope) = @;
+    scope_graphs.remove_childs[];
+
+    !overlay = styling:new_widget :scope_channel;
+    overlay.set_ctrl :graph_minmax $[
+        hx:MONITOR_MINMAX_SAMPLES,
+        scope.overlay_model[]
+    ];
+    scope_graphs.add overlay;
+
+    iter ch_idx 0 => scope.channel_count[] {
+        !graph = styling:new_widget :scope_channel;
+        graph.set_ctrl :graph_minmax $[
+            hx:MONITOR_MINMAX_SAMPLES,
+            scope.channel_model ch_idx
+        ];
+        styling:apply_color_idx graph (((ch_idx + 1) * 3) % len[ui:STD_COLORS]);
+        scope_graphs.add graph;
+    };
+};
+
+editor.reg :scope_channels_changed {!(scope) = @;
+    rebuild_scope_graphs scope;
+};
+rebuild_scope_graphs editor.get_scope[];
+editor.show_scope_status[];
+
+signal_panel.add scope_panel;
+
+!spectrum_panel = styling:new_widget :scope_panel;
+
+!spectrum_btns = styling:new_widget :scope_button_row;
+!add_spectrum_btn = {!(label, cb) = @;
+    spectrum_btns.add ~ styling:new_button_with_label :scope_button label cb;
+};
+add_spectrum_btn "Src"    { editor.spectrum_cycle_source[]; };
+add_spectrum_btn "Window" { editor.spectrum_cycle_window[]; };
+add_spectrum_btn "Hold"   { editor.spectrum_toggle_peak_hold[]; };
+add_spectrum_btn "Reset"  { editor.spectrum_reset_peaks[]; };
+spectrum_panel.add spectrum_btns;
+
+!spectrum_status_txt = ui:txt "";
+!spectrum_status = styling:new_widget :scope_status;
+spectrum_status.set_ctrl :label spectrum_status_txt;
+spectrum_panel.add spectrum_status;
+
+editor.reg :update_spectrum_status {!(text) = @;
+    spectrum_status_txt.set text;
+};
+
+# The current spectrum with a line at the strongest frequency,
+# and below it the held peaks:
+!spectrum = editor.get_spectrum[];
+
+!spectrum_graph = styling:new_widget :spectrum_graph;
+spectrum_graph.set_ctrl :graph $[256, $t, spectrum.graph_model :spectrum];
+spectrum_panel.add spectrum_graph;
+
+!spectrum_peaks = styling:new_widget :spectrum_graph;
+spectrum_peaks.set_ctrl :graph $[256, $t, spectrum.graph_model :peaks];
+spectrum_panel.add spectrum_peaks;
+
+# The frequency axis of the logarithmic spectrum graphs:
+!spectrum_axis = styling:new_widget :spectrum_axis;
+!spectrum_axis_txts = $[];
+iter i 0 => 5 {
+    !txt = ui:txt "";
+    !lbl = styling:new_widget :spectrum_axis_label;
+    lbl.set_ctrl :label txt;
+    std:push spectrum_axis_txts txt;
+    spectrum_axis.add lbl;
+};
+spectrum_panel.add spectrum_axis;
+
+editor.reg :update_spectrum_axis {!(labels) = @;
+    iter i 0 => len[labels] {
+        spectrum_axis_txts.(i).set labels.(i);
+    };
+};
+
+editor.show_spectrum_status[];
+
+signal_panel.add spectrum_panel;
+
 root.add left_panel;
 root.add grid;
 
+# The patch was loaded at startup, before the editor existed.
+# This shows the node colors and labels stored in the patch:
+editor.handle_patch_loaded[];
+
 !@export on_frame = {!(matrix_records) = @;
+    editor.update_pattern_songs[];
+    editor.update_pattern_edit[];
     editor.check_pattern_data[];
+    editor.update_scope[];
+    editor.update_grid[];
+    update_con_value[];
+    update_rec_button[];
 
     iter r matrix_records {
         std:displayln "REC:" r;
@@ -30,28 +30,108 @@
     }
 };
 
+!PALETTE_COMMANDS = $[
+    $p(:help,    "Show Help"),
+    $p(:tracker, "Show Tracker Help"),
+    $p(:about,   "About HexoSynth"),
+    $p(:lint,    "Toggle Patch Lint"),
+    $p(:mod_matrix, "Show Modulation Matrix"),
+    $p(:auto_route, "Auto Route the Next Drag"),
+    $p(:delete_node,    "Delete the Focused Node"),
+    $p(:duplicate_node, "Duplicate the Focused Node"),
+];
+
+!PALETTE_MAX_RESULTS = 10;
+
+# The audio channels, that the scope can show, in the order they are added:
+!SCOPE_SOURCES = $[$p(:out, 0), $p(:out, 1), $p(:in, 0), $p(:in, 1)];
+# The outputs and inputs of the monitored cell, that the scope and the
+# spectrum analyzer show after the audio channels:
+!MONITOR_SOURCES = $[
+    $p(:mon, 3), $p(:mon, 4), $p(:mon, 5),
+    $p(:mon, 0), $p(:mon, 1), $p(:mon, 2),
+];
+
+# Channel selections the recorder cycles through:
+!REC_SOURCES = $[
+    $[$p(:out, 0), $p(:out, 1)],
+    $[$p(:out, 0)],
+    $[$p(:out, 1)],
+    $[$p(:in, 0), $p(:in, 1)],
+    $[$p(:out, 0), $p(:out, 1), $p(:in, 0), $p(:in, 1)],
+];
+
+# Number of frequency labels below the spectrum:
+!SPECTRUM_AXIS_LABELS = 5;
+
+!best_score = {
+    !best = $n;
+    iter s @ {
+        if is_some[s] &and (is_none[best] &or s > best) { .best = s };
+    };
+    best
+};
+
+!clamp = {!(v, lo, hi) = @;
+    if v < lo { lo } { if v > hi { hi } { v } }
+};
+
 !EditorClass = ${
     new = {!(matrix) = @;
         !grid_model = matrix.create_grid_model[];
+        !scope      = matrix.create_scope[];
+        scope.add_channel :out 0;
+        !spectrum   = matrix.create_spectrum_model :out 0;
         ${
             _proto = $self,
             _data = ${
                 matrix                  = matrix,
                 grid_model              = grid_model,
+                scope                   = scope,
+                spectrum                = spectrum,
+                recorder                = hx:new_recorder[],
+                rec_source_idx          = 0,
                 focus_cell              = $n,
                 current_help_node_id    = $n,
                 last_active_tracker_id  = 0,
+                lint_active             = $f,
+                auto_route_armed        = $f,
+                signal_flow_active      = $f,
+                spectrum_peak_hold      = $t,
+                palette_entries         = $n,
+                inspected_link          = $n,
+                sample_dir              = ".",
+                sample_browser_atom     = $n,
+                sample_browser_mode     = :sample,
+                midi_import_track       = $n,
+                midi_import_confirm     = $n,
+                pattern_follow          = $f,
+                pattern_record          = $f,
+                pattern_histories       = ${},
+                shown_pattern_song      = $n,
+                pattern_clipboard       = $n,
+                pattern_mark            = $n,
+                pattern_selection       = $n,
+                record_octave           = 4,
+                note_keys               = ui:create_note_keys_model[],
+                midi_note_tap           = hx:new_midi_note_tap[],
+                pattern_edit_cursor     = $n,
+                mod_filter_node         = $n,
+                sample_editor           = $n,
+                sample_storage          = :path,
+                sample_edit_point       = 0,
                 cbs                     = ${},
             },
         }
     },
     get_grid_model = { $data.grid_model },
     place_new_instance_at = {!(node_id, pos) = @;
-        !cell = $data.matrix.get pos;
-        !new_node_id = $data.matrix.get_unused_instance_node_id node_id;
-        cell.node_id = new_node_id;
-        $data.matrix.set pos cell;
-        $data.matrix.sync[];
+        $self.matrix_apply_change {!(matrix) = @;
+            !cell = matrix.get pos;
+            cell.node_id = _? ~ matrix.get_unused_instance_node_id node_id;
+            matrix.set pos cell;
+            $F "Place {} at {}" cell.node_id pos
+        };
     },
     set_focus_cell = {!(pos) = @;
         $data.grid_model.set_focus_cell pos;
@@ -67,18 +147,37 @@
         };
 
         if cell.node_id.0 == "tseq" {
-            $data.last_active_tracker_id = cell.node_id.1;
-            $self.emit :pattern_editor_set_data $[
-                $data.last_active_tracker_id,
-                $[
-                    6,
-                    $data.matrix.create_pattern_data_model
-                        $data.last_active_tracker_id,
-                    $data.matrix.create_pattern_feedback_model cell.node_id,
-                ]
-            ];
+            $self.edit_tracker cell.node_id.1;
         };
     },
+    edit_tracker = {!(tracker_id) = @;
+        $data.last_active_tracker_id = tracker_id;
+        $self.emit :pattern_editor_set_data $[
+            tracker_id,
+            $[
+                6,
+                $data.matrix.create_pattern_data_model tracker_id,
+                $data.matrix.create_pattern_feedback_model $p(:tseq, tracker_id),
+            ]
+        ];
+        $self.emit_pattern_song[];
+    },
+    # The pattern, the playhead feedback and the song settings
+    # of all TSeq nodes in the patch:
+    tseq_overview_data = {
+        $@vec iter tracker_id $data.matrix.tracker_ids[] {
+            $+ ${
+                tracker_id = tracker_id,
+                pattern    = $data.matrix.create_pattern_data_model tracker_id,
+                feedback   =
+                    $data.matrix.create_pattern_feedback_model $p(:tseq, tracker_id),
+                song       = $data.matrix.pattern_song tracker_id,
+            };
+        }
+    },
+    open_tseq_overview = {
+        $self.emit :open_tseq_overview $self.tseq_overview_data[];
+    },
     get_current_graph_fun = {
         $data.matrix.create_graph_model $data.focus_cell.node_id
     },
@@ -139,29 +238,43 @@
         $data.matrix.set dst dst_cell;
     },
     matrix_apply_change = {!(cb) = @;
-        !matrix = $data.matrix;
-        matrix.save_snapshot[];
+        !change_text = $n;
+
+        !res = $data.matrix.transaction {!(matrix) = @;
+            .change_text = cb matrix;
+            change_text
+        };
 
-        !change_text = cb matrix;
-        match change_text
+        match res
             ($error v) => {
-                std:displayln "ERROR1:" $\.v;
-                matrix.restore_snapshot[];
-                return $n;
+                std:displayln "ERROR:" change_text $\.v;
+                $self.emit :update_status_help_text
+                    ~ format_txt2wichtext
+                        ($F "The change was undone:\n{}" $\.v)
+                        "Matrix Error";
+                return $f;
             };
 
-        !check_res = matrix.check[];
-        if check_res {
-            matrix.sync[];
-            $t
-        } {
-            matrix.restore_snapshot[];
-            match check_res
-                ($error v) => {
-                    std:displayln change_text "ERROR2:" $\.v;
-                    return $f;
-                };
-            $t
+        # Forget the loop points of deleted Sampl nodes:
+        $data.matrix.remove_unused_sample_points[];
+        $t
+    },
+    auto_route = {!(src, dst, out_name, in_name, mode) = @;
+        !route = $data.matrix.find_route src out_name dst in_name mode;
+        match route
+            ($error v) => {
+                $self.emit :update_status_help_text
+                    ~ format_txt2wichtext
+                        ($F "No route found\n{}" $\.v)
+                        "Auto Route";
+                return $f;
+            };
+
+        $self.matrix_apply_change {!(matrix) = @;
+            iter cell route {
+                matrix.set cell.pos cell;
+            };
+            $F "Auto route from {} to {}" src dst
         };
     },
     handle_drag_gesture = {!(src, dst, btn) = @;
@@ -206,72 +319,162 @@
             return $n;
         };
 
-        if is_some[adj] {
-            if adj.is_input[] {
-                .(src,      dst)      = $p(dst,      src);
-                .(src_cell, dst_cell) = $p(dst_cell, src_cell);
-                .adj = adj.flip[];
+        if src_exists &and dst_exists &and is_none[adj] {
+            if not[$data.auto_route_armed] {
+                $self.emit :update_status_help_text
+                    ~ format_txt2wichtext
+                        "Click 'Route' first, then drag from the source\nto the distant destination node."
+                        "Auto Route";
+                return $n;
             };
 
-            !edge_idx     = adj.as_edge[];
-            !dst_edge_idx = adj.flip[].as_edge[];
+            $self.toggle_auto_route[];
+            if $self.auto_route src dst $n $n :auto {
+                $self.set_focus_cell dst;
+            };
+            return $n;
+        };
 
-            if dst_cell.node_id.0 == "nop" \return $n;
-            if src_cell.node_id.0 == "nop" \return $n;
+        if is_some[adj] {
+            $self.edit_connection src dst;
+        };
+    },
+    # Arms the auto routing for the next drag between two distant nodes:
+    toggle_auto_route = {
+        $data.auto_route_armed = not[$data.auto_route_armed];
+        $self.emit :auto_route_changed $data.auto_route_armed;
+    },
+    edit_connection = {!(src, dst) = @;
+        !this = $self;
+        !adj = hx:pos_are_adjacent src dst;
+        if is_none[adj] { return $n; };
 
-            #d# std:displayln "CELLS:" src_cell dst_cell;
-            #d# std:displayln "PORTS:"
-            #d#     src_cell.ports.(edge_idx)
-            #d#     dst_cell.ports.(dst_edge_idx);
+        !src_cell = $data.matrix.get src;
+        !dst_cell = $data.matrix.get dst;
 
-            !src_outs = node_id:out_list   src_cell.node_id;
-            !dst_ins  = node_id:param_list dst_cell.node_id;
+        if adj.is_input[] {
+            .(src,      dst)      = $p(dst,      src);
+            .(src_cell, dst_cell) = $p(dst_cell, src_cell);
+            .adj = adj.flip[];
+        };
 
-            !out_name = src_cell.ports.(edge_idx);
-            !in_name  = dst_cell.ports.(dst_edge_idx);
+        !edge_idx     = adj.as_edge[];
+        !dst_edge_idx = adj.flip[].as_edge[];
 
-            !connection =
-                if is_some[out_name] &and is_some[in_name] {
-                    !out_idx  = node_id:out_name2idx src_cell.node_id out_name;
-                    !in_idx   = node_id:inp_name2idx dst_cell.node_id in_name;
-                    $i(out_idx, in_idx)
-                } {
-                    $none
-                };
+        if dst_cell.node_id.0 == "nop" \return $n;
+        if src_cell.node_id.0 == "nop" \return $n;
 
-            .dst_ins =
-                $@vec iter inp dst_ins.inputs {
-                    $+ $p(inp.name[],
-                          not $data.matrix.param_input_is_used[inp])
-                };
-            .src_outs = $@vec iter out src_outs { $+ out.1 };
-
-            #d# std:displayln "INS"  dst_ins;
-            #d# std:displayln "OUTS" src_outs;
-
-            $self.emit :setup_edit_connection
-                src_cell dst_cell
-                src_outs dst_ins
-                connection {!(con) = @;
-                    this.matrix_apply_change {!(matrix) = @;
-                        if is_none[con] {
-                            this.matrix_clear_connection src dst;
-                            return ~ $F "Clear connection between {} and {}"
-                                src_cell.node_id dst_cell.node_id;
-                        } {
-                            this.matrix_set_connection_by_io_names
-                                src dst
-                                src_outs.(con.0)
-                                dst_ins.(con.1).0;
-                            return ~ $F "Set connection between {} output {} and {} input {}"
-                                src_cell.node_id
-                                src_outs.(con.0)
-                                dst_cell.node_id
-                                dst_ins.(con.1).0;
-                        };
+        #d# std:displayln "CELLS:" src_cell dst_cell;
+        #d# std:displayln "PORTS:"
+        #d#     src_cell.ports.(edge_idx)
+        #d#     dst_cell.ports.(dst_edge_idx);
+
+        !src_outs = node_id:out_list   src_cell.node_id;
+        !dst_ins  = node_id:param_list dst_cell.node_id;
+
+        !out_name = src_cell.ports.(edge_idx);
+        !in_name  = dst_cell.ports.(dst_edge_idx);
+
+        !connection =
+            if is_some[out_name] &and is_some[in_name] {
+                !out_idx  = node_id:out_name2idx src_cell.node_id out_name;
+                !in_idx   = node_id:inp_name2idx dst_cell.node_id in_name;
+                $i(out_idx, in_idx)
+            } {
+                $none
+            };
+
+        $data.inspected_link =
+            if is_some[connection] {
+                ${ node_id = src_cell.node_id, out_name = out_name }
+            } { $n };
+
+        .dst_ins =
+            $@vec iter inp dst_ins.inputs {
+                $+ $p(inp.name[],
+                      not $data.matrix.param_input_is_used[inp])
+            };
+        .src_outs = $@vec iter out src_outs { $+ out.1 };
+
+        #d# std:displayln "INS"  dst_ins;
+        #d# std:displayln "OUTS" src_outs;
+
+        $self.emit :setup_edit_connection
+            src_cell dst_cell
+            src_outs dst_ins
+            connection {!(con) = @;
+                this.matrix_apply_change {!(matrix) = @;
+                    if is_none[con] {
+                        this.matrix_clear_connection src dst;
+                        return ~ $F "Clear connection between {} and {}"
+                            src_cell.node_id dst_cell.node_id;
+                    } {
+                        this.matrix_set_connection_by_io_names
+                            src dst
+                            src_outs.(con.0)
+                            dst_ins.(con.1).0;
+                        return ~ $F "Set connection between {} output {} and {} input {}"
+                            src_cell.node_id
+                            src_outs.(con.0)
+                            dst_cell.node_id
+                            dst_ins.(con.1).0;
                     };
                 };
-        };
+            };
+    },
+    cell_connections = {!(pos) = @;
+        !cell = $data.matrix.get pos;
+        if is_none[cell] &or cell.node_id.0 == "nop" { return $[] };
+
+        $@vec iter edge 0 => 6 {
+            !port = cell.ports.(edge);
+            if is_none[port] { next[] };
+
+            !dir       = hx:dir_edge edge;
+            !other_pos = dir.offs_pos pos;
+            if is_none[other_pos] { next[] };
+
+            !other = $data.matrix.get other_pos;
+            if is_none[other] { next[] };
+
+            !other_port = other.ports.(dir.flip[].as_edge[]);
+            if is_none[other_port] { next[] };
+
+            $+ ~ if dir.is_input[] {
+                ${ src = other_pos, dst = pos,
+                   label = $F "{} {} > {} {}"
+                       other.node_id.0 other_port cell.node_id.0 port }
+            } {
+                ${ src = pos, dst = other_pos,
+                   label = $F "{} {} > {} {}"
+                       cell.node_id.0 port other.node_id.0 other_port }
+            };
+        }
+    },
+    inspect_cell_connections = {!(pos) = @;
+        !cons = $self.cell_connections pos;
+
+        match len[cons]
+            0 => {
+                $self.emit :update_status_help_text
+                    ~ format_txt2wichtext "This cell has no connections." "Inspect";
+            }
+            1 => { $self.edit_connection cons.0.src cons.0.dst; }
+            {
+                !this = $self;
+                $self.emit :select_connection cons {!(con) = @;
+                    this.edit_connection con.src con.dst;
+                };
+            };
+    },
+    inspected_link_value = {
+        !link = $data.inspected_link;
+        if is_none[link] { return $n };
+
+        $data.matrix.out_fb_value link.node_id link.out_name
+    },
+    close_link_inspector = {
+        $data.inspected_link = $n;
     },
     show_param_id_desc = {!(param_id) = @;
         !(node_id, idx) = param_id.as_parts[];
@@ -296,11 +499,63 @@
         $data.current_help_node_id = info;
         $self.emit :update_status_help_text text;
     },
-    show_color_info = {
-        !text = $@s iter clr 0 => 19 { $+ ~ $F"[c{}:XX {:02!i} XX]\n" clr clr; };
-        $self.emit
-            :update_status_help_text
-            text;
+    open_node_style = {
+        if $self.focus_cell_is_empty[] {
+            $self.emit :update_status_help_text
+                ~ format_txt2wichtext "Select a node to change it's color or label." "Style";
+            return $n;
+        };
+
+        !node_id = $data.focus_cell.node_id;
+        $self.emit :open_node_style
+            node_id
+            ($data.grid_model.get_node_label node_id)
+            ($data.grid_model.get_node_color node_id);
+    },
+    set_node_color = {!(node_id, color_idx) = @;
+        $data.grid_model.set_node_color node_id color_idx;
+    },
+    set_node_label = {!(node_id, label) = @;
+        $data.grid_model.set_node_label node_id label;
+        $self.set_focus_cell $data.focus_cell.pos;
+    },
+    handle_patch_loaded = {
+        $data.grid_model.sync_from_matrix[];
+
+        !missing = $data.matrix.missing_samples[];
+        if len[missing] > 0 {
+            !lines = $@vec iter m missing {
+                $+ $F "'{}': {}" m.0 m.1;
+            };
+            $self.emit :update_status_help_text
+                ~ format_txt2wichtext
+                    ($F "{} samples could not be loaded\n{}"
+                        len[missing] (std:str:join "\n" lines))
+                    "Load";
+        };
+    },
+    save_patch = {!(path) = @;
+        match ($data.matrix.save_patch path $data.sample_storage)
+            ($error v) => {
+                $self.emit :update_status_help_text
+                    ~ format_txt2wichtext ($F "Saving failed\n{}" $\.v) "Save";
+            };
+    },
+    cycle_sample_storage = {
+        $data.sample_storage =
+            match $data.sample_storage
+                :path   => :bundle
+                :bundle => :embed
+                :path;
+
+        $self.emit :update_status_help_text
+            ~ format_txt2wichtext
+                (match $data.sample_storage
+                    :path   => "Samples are saved as file paths"
+                    :bundle => "Samples are copied next to the patch\ninto the directory <patch>.samples"
+                    "Samples are embedded into the patch")
+                "Save";
+        $data.sample_storage
     },
     handle_hover = {!(where, arg1) = @;
         match where
@@ -316,22 +571,1014 @@
             $self.emit :show_main_help $data.current_help_node_id.help[];
         };
     },
+    lint_patch = {
+        !diags = $data.matrix.lint[];
+
+        $data.grid_model.set_marked_cells
+            ~ $@vec iter d diags {
+                if is_some[d.pos] { $+ d.pos };
+            };
+
+        if len[diags] == 0 {
+            $self.emit :update_status_help_text
+                ~ format_txt2wichtext "No problems found in the patch." "Lint";
+            return $n;
+        };
+
+        !text = $@s iter d diags {
+            if is_some[d.pos] {
+                $+ ~ $F "({},{}) " d.pos.x d.pos.y;
+            };
+            $+ d.msg;
+            $+ "\n";
+        };
+
+        $self.emit :update_status_help_text
+            ~ format_txt2wichtext
+                ($F "{} problem(s) found\n{}" len[diags] text)
+                "Lint";
+    },
+    get_scope = { $data.scope },
+    update_scope = {
+        $data.scope.update[];
+        $data.spectrum.update[];
+    },
+    get_spectrum = { $data.spectrum },
+    show_spectrum_status = {
+        !peak = $data.spectrum.peak[];
+        $self.emit :update_spectrum_status ~
+            $F "{} | peak {:.1!f} {} @ {:.1!f} dB | hold {} | {}"
+                $data.spectrum.get_window[]
+                peak.0 $data.spectrum.get_freq_unit[] peak.1
+                (if $data.spectrum_peak_hold { "on" } { "off" })
+                $data.spectrum.get_source_label[];
+
+        # The sample rate or the source might have changed,
+        # so the axis is updated too:
+        $self.emit :update_spectrum_axis ~
+            $@vec iter i 0 => SPECTRUM_AXIS_LABELS {
+                !freq = $data.spectrum.x_to_freq
+                    ((float i) / float[SPECTRUM_AXIS_LABELS - 1]);
+                $+ ~ if freq >= 1000.0 { $F "{:.1!f}k" (freq / 1000.0) }
+                                       { $F "{}" int[freq] };
+            };
+    },
+    spectrum_cycle_source = {
+        !src     = $data.spectrum.get_source[];
+        !sources = $[*SCOPE_SOURCES, *MONITOR_SOURCES];
+
+        !idx = 0;
+        iter i 0 => len[sources] {
+            !s = sources.(i);
+            if s.0 == src.0 &and s.1 == src.1 { .idx = i + 1 };
+        };
+        !next = sources.(idx % len[sources]);
+
+        $data.spectrum.set_source next.0 next.1;
+        $self.show_spectrum_status[];
+    },
+    spectrum_cycle_window = {
+        !next_window =
+            match $data.spectrum.get_window[]
+                :rect    => :hann
+                :hann    => :hamming
+                :hamming => :blackman
+                :rect;
+        $data.spectrum.set_window next_window;
+        $self.show_spectrum_status[];
+    },
+    spectrum_toggle_peak_hold = {
+        $data.spectrum_peak_hold = not[$data.spectrum_peak_hold];
+        $data.spectrum.set_peak_hold $data.spectrum_peak_hold;
+        $self.show_spectrum_status[];
+    },
+    spectrum_reset_peaks = {
+        $data.spectrum.reset_peaks[];
+        $self.show_spectrum_status[];
+    },
+    show_scope_status = {
+        !trig = $data.scope.get_trigger[];
+        $self.emit :update_scope_status ~
+            $F "{} ch {} | T {} {:.2!f} | {:.1!f} ms{}"
+                $data.scope.channel_count[]
+                (std:str:join ", " $data.scope.channel_labels[])
+                trig.1 trig.0
+                $data.scope.get_time_base_ms[]
+                (if $data.scope.is_frozen[] { " | FROZEN" } { "" });
+    },
+    scope_add_channel = {
+        !used = $data.scope.channel_sources[];
+
+        !next = $n;
+        iter src $[*SCOPE_SOURCES, *MONITOR_SOURCES] {
+            !is_used = $f;
+            iter u used {
+                if u.0 == src.0 &and u.1 == src.1 { .is_used = $t };
+            };
+
+            if is_none[next] &and not[is_used] { .next = src; };
+        };
+
+        if is_none[next] {
+            $self.emit :update_status_help_text
+                ~ format_txt2wichtext "All channels are already shown." "Scope";
+            return $n;
+        };
+
+        _? ~ $data.scope.add_channel next.0 next.1;
+        $self.emit :scope_channels_changed $data.scope;
+        $self.show_scope_status[];
+    },
+    scope_clear = {
+        $data.scope.clear_channels[];
+        $data.scope.add_channel :out 0;
+        $self.emit :scope_channels_changed $data.scope;
+        $self.show_scope_status[];
+    },
+    scope_toggle_freeze = {
+        $data.scope.set_freeze not[$data.scope.is_frozen[]];
+        $self.show_scope_status[];
+    },
+    scope_cycle_trigger = {
+        !trig = $data.scope.get_trigger[];
+        !next_edge =
+            match trig.1
+                :rising  => :falling
+                :falling => :off
+                :rising;
+        $data.scope.set_trigger trig.0 next_edge;
+        $self.show_scope_status[];
+    },
+    scope_trigger_level = {!(delta) = @;
+        !trig = $data.scope.get_trigger[];
+        $data.scope.set_trigger (trig.0 + delta) trig.1;
+        $self.show_scope_status[];
+    },
+    scope_time_base = {!(factor) = @;
+        $data.scope.set_time_base ~ int ($data.scope.get_time_base[] * factor);
+        $self.show_scope_status[];
+    },
+    is_recording = { $data.recorder.is_recording[] },
+    recorded_secs = { $data.recorder.recorded_secs[] },
+    get_rec_source_label = {
+        std:str:join "+" $data.recorder.get_source_labels[]
+    },
+    rec_cycle_source = {
+        if $data.recorder.is_recording[] {
+            $self.emit :update_status_help_text
+                ~ format_txt2wichtext
+                    "Stop the recording before selecting other channels." "Rec";
+            return $n;
+        };
+
+        $data.rec_source_idx = ($data.rec_source_idx + 1) % len[REC_SOURCES];
+        _? ~ $data.recorder.set_sources REC_SOURCES.($data.rec_source_idx);
+
+        $self.emit :rec_source_changed $self.get_rec_source_label[];
+    },
+    toggle_recording = {
+        if $data.recorder.is_recording[] {
+            !info = $data.recorder.stop[];
+            match info
+                ($error v) => {
+                    $self.emit :update_status_help_text
+                        ~ format_txt2wichtext ($F "Recording failed\n{}" $\.v) "Rec";
+                    $self.emit :recording_changed $f;
+                    return $n;
+                };
+
+            !dropped =
+                if info.dropped_samples > 0 {
+                    $F "\n{} samples were dropped, the disk was too slow."
+                        info.dropped_samples
+                } { "" };
+
+            $self.emit :update_status_help_text
+                ~ format_txt2wichtext
+                    ($F "Recording stopped\nWrote {:.1!f} seconds to '{}'.{}"
+                        (float[info.frames] / float[info.sample_rate])
+                        info.path
+                        dropped)
+                    "Rec";
+            $self.emit :recording_changed $f;
+            return $n;
+        };
+
+        !path = $F "hexosynth_rec_{}.wav" ~ std:chrono:timestamp "%Y%m%d_%H%M%S";
+        match ($data.recorder.start path)
+            ($error v) => {
+                $self.emit :update_status_help_text
+                    ~ format_txt2wichtext ($F "Recording failed\n{}" $\.v) "Rec";
+                return $n;
+            };
+
+        $self.emit :update_status_help_text
+            ~ format_txt2wichtext
+                ($F "Recording {}\nto '{}'." $self.get_rec_source_label[] path)
+                "Rec";
+        $self.emit :recording_changed $t;
+    },
+    load_sample_into = {!(atom, path) = @;
+        !sample = hx:load_sample_file path;
+        match sample
+            ($error v) => {
+                $self.emit :update_status_help_text
+                    ~ format_txt2wichtext ($F "Loading sample failed\n{}" $\.v) "Sample";
+                return $f;
+            };
+
+        $data.matrix.set_param atom sample;
+        if is_some[$data.sample_editor] {
+            $data.sample_editor.reload[];
+        };
+
+        !info = hx:sample_file_info path;
+        $self.emit :update_status_help_text
+            ~ format_txt2wichtext
+                ($F "Sample loaded\n'{}'\n{} ch, {} Hz, {} bit, {} frames"
+                    path info.channels info.sample_rate
+                    info.bits_per_sample info.frames)
+                "Sample";
+        $self.emit :sample_loaded atom path;
+        $t
+    },
+    browse_sample_dir = {!(dir) = @;
+        !entries =
+            hx:list_sample_dir dir
+                (if $data.sample_browser_mode == :midi { "mid,midi" } { "wav" });
+        match entries
+            ($error v) => {
+                $self.emit :update_status_help_text
+                    ~ format_txt2wichtext ($F "Can't browse samples\n{}" $\.v) "Sample";
+                return $n;
+            };
+
+        $data.sample_dir = dir;
+        $self.emit :sample_dir_listing dir entries;
+    },
+    open_sample_browser = {!(atom) = @;
+        $data.sample_browser_atom = atom;
+        $data.sample_browser_mode = :sample;
+        $self.emit :open_sample_browser atom;
+        $self.browse_sample_dir $data.sample_dir;
+    },
+    select_sample_entry = {!(entry) = @;
+        if entry.is_dir {
+            $self.browse_sample_dir entry.path;
+        } {
+            if $data.sample_browser_mode == :midi {
+                $self.import_midi_pattern entry.path;
+            } {
+                $self.load_sample_into $data.sample_browser_atom entry.path;
+            };
+        };
+    },
+    create_sample_editor = {
+        if is_none[$data.focus_cell] { return $n };
+        $data.sample_editor =
+            $data.matrix.create_sample_editor $data.focus_cell.node_id;
+        $data.sample_editor
+    },
+    close_sample_editor = { $data.sample_editor = $n; },
+    sample_edit_point_name = {
+        $[:start, :end, :loop_start, :loop_end].($data.sample_edit_point)
+    },
+    sample_cycle_edit_point = {
+        $data.sample_edit_point = ($data.sample_edit_point + 1) % 4;
+
+        if $data.sample_edit_point >= 2 {
+            $self.emit :update_status_help_text
+                ~ format_txt2wichtext
+                    ("Loop points are markers only\n"
+                     "They are saved with the patch, but the Sampl node\n"
+                     "does not play them. In loop mode it repeats the\n"
+                     "region from start to end.")
+                    "Sample";
+        };
+
+        $self.sample_edit_point_name[]
+    },
+    sample_move_point = {!(view_delta) = @;
+        if is_none[$data.sample_editor] { return $n };
+        $data.sample_editor.move_point $self.sample_edit_point_name[] view_delta;
+    },
+    sample_zoom = {!(factor) = @;
+        if is_none[$data.sample_editor] { return $n };
+        $data.sample_editor.zoom_by factor;
+    },
+    sample_pan = {!(view_delta) = @;
+        if is_none[$data.sample_editor] { return $n };
+        $data.sample_editor.pan_by view_delta;
+    },
+    sample_reset_view = {
+        if is_none[$data.sample_editor] { return $n };
+        $data.sample_editor.reset_view[];
+    },
+    toggle_signal_flow = {
+        $data.signal_flow_active = not[$data.signal_flow_active];
+        $data.grid_model.set_signal_flow $data.signal_flow_active;
+
+        $self.emit :update_status_help_text
+            ~ format_txt2wichtext
+                (if $data.signal_flow_active {
+                    "Signal flow overlay enabled\nEdges show the signal level, CLIP marks signals beyond +-1.0.\nNodes with silent outputs are dimmed."
+                } {
+                    "Signal flow overlay disabled"
+                })
+                "Flow";
+    },
+    update_grid = {
+        $data.grid_model.next_frame[];
+    },
+    toggle_lint = {
+        $data.lint_active = not[$data.lint_active];
+
+        if $data.lint_active {
+            $self.lint_patch[];
+        } {
+            $data.grid_model.set_marked_cells $[];
+        };
+    },
+    check_signal_types = {
+        !diags = $data.matrix.signal_mismatches[];
+
+        $data.grid_model.set_warning_cells
+            ~ $@vec iter d diags { $+ d.pos };
+
+        if len[diags] == 0 { return $n };
+
+        !text = $@s iter d diags {
+            $+ ~ $F "({},{}) {}\n" d.pos.x d.pos.y d.msg;
+        };
+
+        $self.emit :update_status_help_text
+            ~ format_txt2wichtext
+                ($F "Suspicious signal type connection\n{}" text)
+                "Miswire";
+    },
     handle_matrix_graph_change = {
         $self.set_focus_cell $data.focus_cell.pos;
+
+        if $data.lint_active {
+            $self.lint_patch[];
+        };
+        $self.check_signal_types[];
+    },
+    palette_entries = {
+        if is_some[$data.palette_entries] {
+            return $data.palette_entries;
+        };
+
+        # The commands come first, so that they are on the first page
+        # of the palette:
+        !entries = $[];
+        iter cmd PALETTE_COMMANDS {
+            std:push entries ${
+                kind  = :command,
+                cmd   = cmd.0,
+                name  = cmd.0,
+                label = cmd.1,
+                text  = "",
+            };
+        };
+
+        iter cat node_id:ui_category_node_id_map[] {
+            iter nid cat.0 {
+                !info = node_id:info nid;
+                std:push entries ${
+                    kind  = :node,
+                    node  = nid,
+                    name  = nid.0,
+                    label = node_id:label nid,
+                    text  = std:str:to_lowercase ~
+                        std:str:cat info.desc[] "\n" info.help[],
+                };
+            };
+        };
+
+        $data.palette_entries = entries;
+        entries
+    },
+    palette_search = {!(query) = @;
+        !entries = $self.palette_entries[];
+        !query   = std:str:trim query;
+
+        !scored =
+            if query == "" {
+                $@vec iter ent entries { $+ $p(0, ent) }
+            } {
+                !lc_query = std:str:to_lowercase query;
+
+                # The score is a pair of rank and fuzzy score. Any match in
+                # the name or label ranks above a match in the help text:
+                !scored = $@vec iter ent entries {
+                    !label_score = best_score
+                        (hx:fuzzy_score query ent.label)
+                        (hx:fuzzy_score query ent.name);
+
+                    !score =
+                        if is_some[label_score] { $p(1, label_score) } {
+                            if is_some[std:str:find lc_query ent.text]
+                                { $p(0, 0) } { $n }
+                        };
+
+                    if is_some[score] { $+ $p(score, ent) };
+                };
+
+                std:sort {
+                    !rank = std:cmp:num:desc _.0.0 _1.0.0;
+                    if rank == 0 { std:cmp:num:desc _.0.1 _1.0.1 } { rank }
+                } scored;
+                scored
+            };
+
+        !results = $[];
+        iter s scored {
+            if len[results] >= PALETTE_MAX_RESULTS { break[] };
+            std:push results s.1;
+        };
+        results
+    },
+    palette_page = {!(page) = @;
+        !entries = $self.palette_entries[];
+        !pages   = (len[entries] + PALETTE_MAX_RESULTS - 1) / PALETTE_MAX_RESULTS;
+        !page    = (page + pages) % pages;
+        !offs    = page * PALETTE_MAX_RESULTS;
+
+        ${
+            page    = page,
+            pages   = pages,
+            entries = $@vec iter i offs => (offs + PALETTE_MAX_RESULTS) {
+                if i < len[entries] { $+ entries.(i) };
+            },
+        }
+    },
+    palette_execute = {!(entry) = @;
+        match entry.kind
+            :node    => { $self.place_node_near_focus entry.node; }
+            :command => { $self.handle_top_menu_click entry.cmd; };
+    },
+    open_palette = {
+        $self.emit :open_palette;
+    },
+    place_node_near_focus = {!(node_id) = @;
+        if is_none[$data.focus_cell] {
+            $self.set_focus_cell $i(0, 0);
+        };
+
+        !focus = $data.focus_cell;
+
+        if focus.node_id.0 == "nop" {
+            if $self.place_new_instance_at node_id focus.pos {
+                $self.set_focus_cell focus.pos;
+            };
+            return $n;
+        };
+
+        !outs = node_id:out_list focus.node_id;
+        !ins  = (node_id:param_list node_id).inputs;
+
+        # Try to place the new node at one of the output edges of the
+        # focused node, and connect the first output to the first input:
+        iter dir_name $[:TR, :BR, :B] {
+            !dir = hx:dir dir_name;
+            !pos = dir.offs_pos focus.pos;
+            if is_none[pos] { next[] };
+
+            !cell = $data.matrix.get pos;
+            if is_none[cell] &or cell.node_id.0 != "nop" { next[] };
+
+            !src_cell = $data.matrix.get focus.pos;
+            if is_some[src_cell.ports.(dir.as_edge[])] { next[] };
+
+            !ok = $self.matrix_apply_change {!(matrix) = @;
+                cell.node_id = _? ~ matrix.get_unused_instance_node_id node_id;
+
+                if len[outs] > 0 &and len[ins] > 0 {
+                    src_cell.ports.(dir.as_edge[])    = outs.0.1;
+                    cell.ports.(dir.flip[].as_edge[]) = ins.0.name[];
+                    matrix.set focus.pos src_cell;
+                };
+
+                matrix.set pos cell;
+                $F "Place {} next to {}" cell.node_id focus.node_id
+            };
+
+            if ok { $self.set_focus_cell pos };
+            return $n;
+        };
+
+        $self.emit :update_status_help_text
+            ~ format_txt2wichtext
+                "No free cell at the outputs of the selected node."
+                "Place";
+    },
+    focus_cell_is_empty = {
+           is_none[$data.focus_cell]
+        &or $data.focus_cell.node_id.0 == "nop"
+    },
+    delete_focus_cell = {
+        if $self.focus_cell_is_empty[] { return $n };
+
+        !pos = $data.focus_cell.pos;
+        $self.matrix_apply_change {!(matrix) = @;
+            !cell = matrix.get pos;
+            matrix.set pos ${
+                node_id = $p("nop", 0),
+                pos     = pos,
+                ports   = $[$n, $n, $n, $n, $n, $n],
+            };
+            $F "Delete {} at {}" cell.node_id pos
+        };
+
+        $self.set_focus_cell pos;
+    },
+    duplicate_focus_cell = {
+        if $self.focus_cell_is_empty[] { return $n };
+
+        !focus   = $data.focus_cell;
+        !src_nid = focus.node_id;
+
+        iter dir_name $[:BR, :TR, :B, :BL, :TL, :T] {
+            !pos = (hx:dir dir_name).offs_pos focus.pos;
+            if is_none[pos] { next[] };
+
+            !cell = $data.matrix.get pos;
+            if is_none[cell] &or cell.node_id.0 != "nop" { next[] };
+
+            !ok = $self.matrix_apply_change {!(matrix) = @;
+                cell.node_id = _? ~ matrix.get_unused_instance_node_id src_nid;
+                matrix.set pos cell;
+
+                !plist = node_id:param_list src_nid;
+                iter params $[plist.inputs, plist.atoms] {
+                    iter p params {
+                        matrix.set_param
+                            (node_id:inp_param cell.node_id p.name[])
+                            (matrix.get_param p);
+                    };
+                };
+
+                $F "Duplicate {} as {}" src_nid cell.node_id
+            };
+
+            if ok { $self.set_focus_cell pos };
+            return $n;
+        };
     },
     handle_top_menu_click = {!(button_tag) = @;
         match button_tag
             :help       => { $self.emit :show_main_help texts:help; }
             :tracker    => { $self.emit :show_main_help texts:tracker; }
             :about      => { $self.emit :show_main_help texts:about; }
+            :lint       => { $self.toggle_lint[]; }
+            :record     => { $self.toggle_recording[]; }
+            :signal_flow => { $self.toggle_signal_flow[]; }
+            :palette    => { $self.open_palette[]; }
+            :mod_matrix => { $self.open_mod_matrix[]; }
+            :auto_route => { $self.toggle_auto_route[]; }
+            :delete_node    => { $self.delete_focus_cell[]; }
+            :duplicate_node => { $self.duplicate_focus_cell[]; }
     },
     handle_param_trig_btn = {!(param, action) = @;
         match action
             :press   => { $data.matrix.set_param param 1.0 }
             :release => { $data.matrix.set_param param 0.0 }
     },
+    pattern_file_path = {
+        $F "tseq_{}.txt" $data.last_active_tracker_id
+    },
+    export_pattern = {
+        !pat  = $data.matrix.create_pattern_data_model $data.last_active_tracker_id;
+        !path = $self.pattern_file_path[];
+
+        match (std:io:file:write_safe path pat.to_text[])
+            ($error v) => {
+                $self.emit :update_status_help_text
+                    ~ format_txt2wichtext ($F "Pattern export failed\n{}" $\.v) "TSeq";
+                return $n;
+            };
+
+        $self.emit :update_status_help_text
+            ~ format_txt2wichtext ($F "Pattern exported to '{}'." path) "TSeq";
+    },
+    import_pattern = {
+        !pat  = $data.matrix.create_pattern_data_model $data.last_active_tracker_id;
+        !path = $self.pattern_file_path[];
+
+        !fail = {!(msg) = @;
+            $self.emit :update_status_help_text
+                ~ format_txt2wichtext ($F "Pattern import failed\n{}" msg) "TSeq";
+        };
+
+        !text = std:io:file:read_text path;
+        match text
+            ($error v) => { fail $\.v; return $n; };
+
+        match (pat.from_text text)
+            ($error v) => { fail $\.v; return $n; };
+
+        $self.check_pattern_data[];
+        $self.emit :update_status_help_text
+            ~ format_txt2wichtext ($F "Pattern imported from '{}'." path) "TSeq";
+    },
+    open_midi_browser = {
+        $data.sample_browser_atom = $n;
+        $data.sample_browser_mode = :midi;
+        $self.emit :open_sample_browser $n;
+        $self.browse_sample_dir $data.sample_dir;
+    },
+    midi_import_track_label = {
+        if is_none[$data.midi_import_track] { "Trk:auto" }
+            { $F "Trk:{}" $data.midi_import_track }
+    },
+    cycle_midi_import_track = {
+        $data.midi_import_track =
+            if is_none[$data.midi_import_track] { 0 }
+            { if $data.midi_import_track >= 15 { $n } { $data.midi_import_track + 1 } };
+        $self.midi_import_track_label[]
+    },
+    import_midi_pattern = {!(path) = @;
+        !fail = {!(msg) = @;
+            $self.emit :update_status_help_text
+                ~ format_txt2wichtext ($F "MIDI import failed\n{}" msg) "TSeq";
+        };
+
+        !info = hx:midi_file_info path;
+        match info
+            ($error v) => { fail $\.v; return $n; };
+
+        # Without a chosen track the first track with notes is imported,
+        # the first track of a type 1 file usually only has the tempo:
+        !track = $data.midi_import_track;
+        if is_none[track] {
+            iter i 0 => len[info.tracks] {
+                if is_none[track] &and (info.tracks.(i).notes > 0) {
+                    .track = i;
+                };
+            };
+            if is_none[track] { fail "The MIDI file has no notes"; return $n; };
+        };
+
+        !pat = $data.matrix.create_pattern_data_model $data.last_active_tracker_id;
+
+        # The import turns column 0 into a note and column 1 into a gate
+        # column and clears them, so the user has to choose the file twice
+        # if that overwrites something:
+        !used_cells = 0;
+        iter row 0 => pat.rows[] {
+            iter col $[0, 1] {
+                if is_some[pat.get_cell row col] { .used_cells = used_cells + 1 };
+            };
+        };
+        !changes_types =
+            (pat.get_col_type 0) != :note &or (pat.get_col_type 1) != :gate;
+
+        if (used_cells > 0 &or changes_types)
+           &and $data.midi_import_confirm != path {
+            $data.midi_import_confirm = path;
+            $self.emit :update_status_help_text
+                ~ format_txt2wichtext
+                    ($F "MIDI import overwrites columns 0 and 1\nThey contain {} cells and become a note (now {}) and a gate (now {}) column.\nChoose '{}' again to import it."
+                        used_cells (pat.get_col_type 0) (pat.get_col_type 1) path)
+                    "TSeq";
+            return $n;
+        };
+        $data.midi_import_confirm = $n;
+
+        !res = pat.import_midi path track;
+        match res
+            ($error v) => { fail $\.v; return $n; };
+
+        $self.check_pattern_data[];
+        $self.emit :update_status_help_text
+            ~ format_txt2wichtext
+                ($F "MIDI track {} imported from '{}'.\n{} notes in {} rows, {} overlapping notes dropped."
+                    track path res.notes res.rows res.dropped)
+                "TSeq";
+    },
+    pattern_song_info = {
+        $data.matrix.pattern_song $data.last_active_tracker_id
+    },
+    emit_pattern_song = {
+        $self.emit :pattern_song_changed
+            $data.last_active_tracker_id $self.pattern_song_info[];
+    },
+    pattern_song_status = {!(text) = @;
+        $self.emit :update_status_help_text ~ format_txt2wichtext text "TSeq";
+    },
+    select_pattern = {!(idx) = @;
+        match ($data.matrix.select_pattern $data.last_active_tracker_id idx)
+            ($error v) => {
+                $self.pattern_song_status ~ $F "Can't switch the pattern\n{}" $\.v;
+                return $n;
+            };
+        $self.reset_pattern_history[];
+        $self.emit_pattern_song[];
+    },
+    select_next_pattern = {!(delta) = @;
+        !idx = $self.pattern_song_info[].current + delta;
+        if idx >= 0 { $self.select_pattern idx };
+    },
+    toggle_pattern_song_mode = {
+        !song =
+            $data.matrix.set_pattern_song_mode
+                $data.last_active_tracker_id
+                not[$self.pattern_song_info[].song_mode];
+        $self.reset_pattern_history[];
+
+        $self.pattern_song_status
+            ~ if song.song_mode {
+                if len[song.chain] == 0
+                    { "Song mode on, but the chain is empty.\nAdd patterns with 'Chain+'." }
+                    { $F "Song mode on, playing the chain {}" ($@s iter i song.chain { $+ ~ $F " {}" i }) }
+            } { "Song mode off" };
+        $self.emit_pattern_song[];
+    },
+    chain_append_pattern = {
+        !song  = $self.pattern_song_info[];
+        !chain = song.chain;
+        std:push chain song.current;
+        $data.matrix.set_pattern_chain $data.last_active_tracker_id chain;
+        $self.emit_pattern_song[];
+    },
+    chain_clear = {
+        $data.matrix.set_pattern_chain $data.last_active_tracker_id $[];
+        $self.emit_pattern_song[];
+    },
+    # The first output of the focused node chooses the pattern,
+    # a second call removes the control again:
+    toggle_pattern_control = {
+        if is_some[$self.pattern_song_info[].control] {
+            $data.matrix.set_pattern_control $data.last_active_tracker_id $n;
+            $self.pattern_song_status "Pattern control removed";
+            $self.emit_pattern_song[];
+            return $n;
+        };
+
+        !cell = $data.focus_cell;
+        if is_none[cell] &or cell.node_id.0 == "nop" &or cell.node_id.0 == "tseq" {
+            $self.pattern_song_status
+                "Select the node, that should choose the pattern,\nthen click 'Ctl'.";
+            return $n;
+        };
+
+        match ($data.matrix.set_pattern_control $data.last_active_tracker_id cell.node_id)
+            ($error v) => { $self.pattern_song_status $\.v; return $n; };
+
+        $self.pattern_song_status
+            ~ $F "The pattern is chosen by {} {}" cell.node_id.0 cell.node_id.1;
+        $self.emit_pattern_song[];
+    },
+    # The patterns are switched by the song sequencer thread,
+    # this only updates the view if the shown TSeq switched:
+    update_pattern_songs = {
+        !tid  = $data.last_active_tracker_id;
+        !song = $data.matrix.pattern_song tid;
+        !shown = $[tid, song.current, song.chain_pos];
+        !last  = $data.shown_pattern_song;
+
+        if is_some[last] &and last.0 == tid
+           &and (last.1 != shown.1 &or last.2 != shown.2) {
+            $self.reset_pattern_history[];
+            $self.emit_pattern_song[];
+        };
+        $data.shown_pattern_song = shown;
+    },
+    current_pattern = {
+        $data.matrix.create_pattern_data_model $data.last_active_tracker_id
+    },
+    pattern_history = {
+        !tid = $data.last_active_tracker_id;
+        if is_none[$data.pattern_histories.(tid)] {
+            $data.pattern_histories.(tid) = ui:create_pattern_history[];
+        };
+        $data.pattern_histories.(tid)
+    },
+    # Switching patterns in the bank is not an edit, that could be undone:
+    reset_pattern_history = {
+        $self.pattern_history[].reset $self.current_pattern[];
+    },
+    pattern_edit_status = {!(text) = @;
+        $self.emit :update_status_help_text ~ format_txt2wichtext text "TSeq";
+    },
+    pattern_play_row = {!(pat) = @;
+        !phase = $data.matrix.phase_value $p(:tseq, $data.last_active_tracker_id);
+        !rows  = pat.rows[];
+        clamp (int ~ phase * float[rows]) 0 (rows - 1)
+    },
+    toggle_pattern_follow = {
+        $data.pattern_follow = not[$data.pattern_follow];
+        $self.emit :pattern_edit_mode_changed;
+        $data.pattern_follow
+    },
+    toggle_pattern_record = {
+        $data.pattern_record = not[$data.pattern_record];
+        $self.emit :pattern_edit_mode_changed;
+        $data.pattern_record
+    },
+    is_pattern_follow = { $data.pattern_follow },
+    is_pattern_record = { $data.pattern_record },
+    # Called every frame: records the clicked note keys and the notes of
+    # the MIDI input and moves the cursor with the playback in follow mode.
+    # The edits in the pattern editor become an undo step when the cursor
+    # leaves the edited cell.
+    update_pattern_edit = {
+        !pat = $self.current_pattern[];
+        if is_none[pat] { return $n };
+
+        iter key_idx $data.note_keys.pop_keys[] {
+            $self.record_key key_idx;
+        };
+        iter note $data.midi_note_tap.pop_notes[] {
+            if $data.pattern_record { $self.record_note note };
+        };
+
+        if $data.pattern_follow {
+            !cur = pat.get_cursor[];
+            !row = $self.pattern_play_row pat;
+            if row != cur.0 { pat.set_cursor row cur.1 };
+        };
+
+        !cur = pat.get_cursor[];
+        !last = $data.pattern_edit_cursor;
+        if is_none[last] &or last.0 != cur.0 &or last.1 != cur.1 {
+            $self.pattern_history[].checkpoint pat;
+            $data.pattern_edit_cursor = cur;
+        };
+    },
+    get_note_keys_model = { $data.note_keys },
+    pattern_undo = {
+        !pat = $self.current_pattern[];
+        match ($self.pattern_history[].undo pat)
+            ($error v) => { $self.pattern_edit_status ~ $F "Undo failed\n{}" $\.v; }
+            $t         => { $self.check_pattern_data[]; }
+            { $self.pattern_edit_status "Nothing to undo"; };
+    },
+    pattern_redo = {
+        !pat = $self.current_pattern[];
+        match ($self.pattern_history[].redo pat)
+            ($error v) => { $self.pattern_edit_status ~ $F "Redo failed\n{}" $\.v; }
+            $t         => { $self.check_pattern_data[]; }
+            { $self.pattern_edit_status "Nothing to redo"; };
+    },
+    change_record_octave = {!(delta) = @;
+        $data.record_octave = clamp ($data.record_octave + delta) 0 9;
+        $data.record_octave
+    },
+    get_record_octave = { $data.record_octave },
+    # Writes the note into the note column under the cursor. When following
+    # the playback, the note goes into the played row, otherwise the cursor
+    # advances by the edit step.
+    record_note = {!(note) = @;
+        if not[$data.pattern_record] {
+            $self.pattern_edit_status "Switch on 'Rec' to enter notes.";
+            return $n;
+        };
+
+        !pat = $self.current_pattern[];
+        !cur = pat.get_cursor[];
+        !col = cur.1;
+        if (pat.get_col_type col) != :note {
+            $self.pattern_edit_status "Move the cursor to a note column to record notes.";
+            return $n;
+        };
+
+        !row = if $data.pattern_follow { $self.pattern_play_row pat } { cur.0 };
+        pat.set_cell row col (clamp note 0 127);
+
+        if not[$data.pattern_follow] {
+            !step = pat.get_edit_step[];
+            pat.set_cursor ((row + (if step < 1 { 1 } { step })) % pat.rows[]) col;
+        };
+        $self.check_pattern_data[];
+    },
+    record_key = {!(key_idx) = @;
+        $self.record_note (($data.record_octave + 1) * 12 + key_idx);
+    },
+    # The first call marks a corner of the selection at the cursor,
+    # the second call selects the block up to the cursor.
+    pattern_mark = {
+        !cur = $self.current_pattern[].get_cursor[];
+
+        if is_none[$data.pattern_mark] {
+            $data.pattern_mark      = cur;
+            $data.pattern_selection = $n;
+            $self.pattern_edit_status
+                ~ $F "Selection starts at row {:02X}, column {}.\nMove the cursor and click 'Mark' again."
+                    cur.0 cur.1;
+            return $n;
+        };
+
+        !m = $data.pattern_mark;
+        $data.pattern_mark = $n;
+
+        !(row0, row1) = if m.0 < cur.0 { $p(m.0, cur.0) } { $p(cur.0, m.0) };
+        !(col0, col1) = if m.1 < cur.1 { $p(m.1, cur.1) } { $p(cur.1, m.1) };
+        $data.pattern_selection = $[row0, col0, row1 - row0 + 1, col1 - col0 + 1];
+        !sel = $data.pattern_selection;
+        $self.pattern_edit_status
+            ~ $F "Selected rows {:02X}-{:02X}, columns {}-{}"
+                sel.0 (sel.0 + sel.2 - 1) sel.1 (sel.1 + sel.3 - 1);
+    },
+    # Copies the selection, or the cell under the cursor without one:
+    pattern_copy = {
+        !pat = $self.current_pattern[];
+        !sel = $data.pattern_selection;
+        if is_none[sel] {
+            !cur = pat.get_cursor[];
+            .sel = $[cur.0, cur.1, 1, 1];
+        };
+
+        $data.pattern_clipboard = pat.copy_block sel.0 sel.1 sel.2 sel.3;
+        $self.pattern_edit_status
+            ~ $F "Copied {} rows x {} columns" sel.2 sel.3;
+    },
+    pattern_paste = {
+        if is_none[$data.pattern_clipboard] {
+            $self.pattern_edit_status "Nothing copied yet";
+            return $n;
+        };
+
+        !pat = $self.current_pattern[];
+        !cur = pat.get_cursor[];
+        pat.paste_block cur.0 cur.1 $data.pattern_clipboard;
+        $self.check_pattern_data[];
+    },
+    # Called after each edit of the editor, which also makes it an undo step:
     check_pattern_data = {
         $data.matrix.check_pattern_data $data.last_active_tracker_id;
+
+        !pat = $self.current_pattern[];
+        if is_some[pat] { $self.pattern_history[].checkpoint pat };
+    },
+    # The modulated parameters, of the filter node only if one is set:
+    mod_matrix_entries = {
+        !entries = $data.matrix.mod_entries[];
+        !filter  = $data.mod_filter_node;
+        if is_none[filter] { return entries; };
+
+        $@vec iter e entries {
+            if e.node_id.0 == filter.0 &and e.node_id.1 == filter.1 { $+ e };
+        }
+    },
+    open_mod_matrix = {
+        $self.emit :open_mod_matrix;
+        $self.update_mod_matrix[];
+    },
+    update_mod_matrix = {
+        $self.emit :mod_matrix_changed
+            $self.mod_matrix_entries[] $data.mod_filter_node;
+    },
+    set_mod_filter_node = {!(node_id) = @;
+        $data.mod_filter_node = node_id;
+        $self.update_mod_matrix[];
+    },
+    # Calls `f` with the amount of each entry, and sets the returned amount.
+    # A returned `$none` removes the modulation of the parameter.
+    change_mod_amounts = {!(entries, change_text, f) = @;
+        if len[entries] == 0 { return $n; };
+
+        $self.matrix_apply_change {!(matrix) = @;
+            iter e entries {
+                !amt = f e.amount;
+                matrix.set_param_modamt e.param_id
+                    (if is_none[amt] { $n } { clamp amt (-1.0) 1.0 });
+            };
+            $F "{} ({} parameters)" change_text len[entries]
+        };
+
+        $self.update_mod_matrix[];
+        $self.emit :update_param_ui;
+    },
+    scale_mod_amounts = {!(factor) = @;
+        $self.change_mod_amounts $self.mod_matrix_entries[]
+            ($F "Scale modulation by {}" factor) { _ * factor };
+    },
+    invert_mod_amounts = {
+        $self.change_mod_amounts $self.mod_matrix_entries[]
+            "Invert modulation" { 0.0 - _ };
+    },
+    nudge_mod_amount = {!(entry, delta) = @;
+        $self.change_mod_amounts $[entry] "Change modulation" { _ + delta };
+    },
+    clear_mod_amount = {!(entry) = @;
+        $self.change_mod_amounts $[entry] "Remove modulation" {|| $n };
+    },
+    # Removes the modulation of all listed parameters, which are the
+    # parameters of one node if the list is filtered:
+    clear_mod_amounts = {
+        $self.change_mod_amounts $self.mod_matrix_entries[]
+            "Remove modulation" {|| $n };
+    },
+    # Removes the modulation of all parameters of the node, also if
+    # the list is filtered by another node:
+    clear_node_mod_amounts = {!(node_id) = @;
+        !entries = $@vec iter e $data.matrix.mod_entries[] {
+            if e.node_id.0 == node_id.0 &and e.node_id.1 == node_id.1 { $+ e };
+        };
+        $self.change_mod_amounts entries
+            ($F "Remove modulation of {} {}" node_id.0 node_id.1) {|| $n };
     },
 };
 
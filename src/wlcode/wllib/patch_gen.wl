@@ -0,0 +1,219 @@
+!@wlambda;
+!@import std;
+!@import hx;
+!@import node_id;
+
+# Procedural patch generation.
+#
+# All generators work on tree specifications. A tree node is a map:
+#
+#   ${
+#       node   = :amp,                  # node name
+#       out    = :sig,                  # output feeding the parent, $n = first
+#       params = $[:gain => 0.5],       # optional params, like place_chain
+#       inputs = $[                     # optional children
+#           $p(:inp, ${ node = :sin }), # input name => child tree,
+#       ],                              # $n input name = n-th input
+#   }
+#
+# The children are placed on free cells near their parent and connected
+# with matrix.find_route. The whole generation runs inside a
+# matrix.transaction, so a failing generator leaves the matrix untouched.
+
+!INPUT_DIRS = $[:TL, :BL, :T];
+
+!cell_is_free = {!(matrix, pos) = @;
+    if is_none[pos] { return $f };
+    if pos.x < 0 &or pos.y < 0 { return $f };
+
+    !cell = matrix.get pos;
+    is_some[cell] &and cell.node_id.0 == "nop"
+};
+
+# Looks for a free cell for a child of the node at `pos`. The cells
+# adjacent to the input edges come first, because they can be connected
+# directly. Otherwise the search moves further to the left, where the
+# signal can be routed rightwards to the parent.
+!find_free_near = {!(matrix, pos) = @;
+    iter dir INPUT_DIRS {
+        !p = (hx:dir dir).offs_pos pos;
+        if cell_is_free matrix p { return p };
+    };
+
+    iter dist 1 => 16 {
+        iter dx 1 => (dist + 1) {
+            iter dy (-dist) => (dist + 1) {
+                !p = $i(pos.x - dx, pos.y + dy);
+                if cell_is_free matrix p { return p };
+            };
+        };
+    };
+
+    $n
+};
+
+!place_node = {!(matrix, pos, spec) = @;
+    !nid = _? ~ matrix.get_unused_instance_node_id $p(spec.node, 0);
+
+    matrix.set pos ${
+        node_id = nid,
+        pos     = pos,
+        ports   = $[$n, $n, $n, $n, $n, $n],
+    };
+
+    if is_none[spec.params] { return nid };
+
+    iter param spec.params {
+        !pid = node_id:inp_param nid param.0;
+        if is_none[pid] {
+            return $error $F "Unknown parameter '{}' for {}" param.0 spec.node;
+        };
+
+        matrix.set_param pid ~
+            (if is_float[param.1] { pid.norm param.1 } { param.1 });
+    };
+
+    nid
+};
+
+!place_tree = $n;
+.place_tree = {!(matrix, pos, spec) = @;
+    !nid    = _? ~ place_node matrix pos spec;
+    !inputs = (node_id:param_list nid).inputs;
+
+    if is_none[spec.inputs] { return nid };
+
+    !inp_idx = 0;
+    iter inp spec.inputs {
+        !(in_name, child) = inp;
+        !in_name =
+            if is_some[in_name] { in_name }
+                                { inputs.(inp_idx).name[] };
+        .inp_idx += 1;
+
+        !child_pos = find_free_near matrix pos;
+        if is_none[child_pos] {
+            return $error $F "No free cell left for {}" child.node;
+        };
+
+        _? ~ place_tree matrix child_pos child;
+
+        !cells = _? ~ matrix.find_route child_pos child.out pos in_name :auto;
+        iter cell cells { matrix.set cell.pos cell };
+    };
+
+    nid
+};
+
+# Places the tree `spec` with it's root at `pos`.
+# Returns $true or an $error, in which case the matrix is unchanged.
+!tree = {!(matrix, pos, spec) = @;
+    matrix.transaction {!(matrix) = @;
+        place_tree matrix pos spec
+    }
+};
+!@export tree = tree;
+
+# Builds a tree spec that mixes all `sources` with `Mix3` nodes.
+# More than 3 sources are distributed over nested mixers.
+!build_mixer = $n;
+.build_mixer = {!(sources) = @;
+    !groups  = $[$[], $[], $[]];
+    !src_idx = 0;
+    iter src sources {
+        std:push groups.(src_idx % 3) src;
+        .src_idx += 1;
+    };
+
+    !inputs = $[];
+    iter grp groups {
+        if len[grp] == 0 { next[] };
+
+        std:push inputs $p(
+            $F "ch{}" (len[inputs] + 1),
+            (if len[grp] == 1 { grp.0 } { build_mixer grp }));
+    };
+
+    ${ node = :mix3, out = :sig, inputs = inputs }
+};
+!@export mixer = build_mixer;
+
+# Builds a tree spec with `count` copies of `voice` mixed together.
+!@export voices = {!(voice, count) = @;
+    build_mixer ~ $@vec iter i 0 => count { $+ voice };
+};
+
+# Wraps the tree spec `spec` into an `Out` node.
+!@export to_out = {!(spec) = @;
+    ${ node = :out, inputs = $[$p(:ch1, spec)] }
+};
+
+!random_params = {!(nid) = @;
+    $@vec iter atom (node_id:param_list nid).atoms {
+        !min_max = atom.setting_min_max[];
+        if is_some[min_max] &and atom.atom_ui[] == :mode {
+            $+ $p(atom.name[], min_max.0 + (std:rand (1 + min_max.1 - min_max.0)));
+        };
+    }
+};
+
+# Builds a random tree spec of `node_count` nodes, picked from the
+# node categories in `categories` (eg. $[:Osc, :Signal]), that ends
+# in an `Out` node. The optional `seed` makes the result repeatable.
+!random_tree = {|2<3| !(categories, node_count, seed) = @;
+    if is_some[seed] { std:srand seed };
+
+    !cat_map    = node_id:ui_category_node_id_map[];
+    !candidates = $@vec iter cat categories {
+        iter nid cat_map.(cat) {
+            if len[node_id:out_list nid] > 0 { $+ nid };
+        };
+    };
+
+    if len[candidates] == 0 {
+        return $error $F "No nodes with outputs in categories {}" categories;
+    };
+
+    !root = ${ node = :out, inputs = $[] };
+
+    # Each entry holds a tree node and the name of one of it's free inputs:
+    !open = $[$p(root, "ch1")];
+
+    iter i 0 => node_count {
+        if len[open] == 0 { break[] };
+
+        !open_idx = std:rand len[open];
+        !(parent, in_name) = open.(open_idx);
+
+        !idx = 0;
+        .open = $@vec iter o open {
+            if idx != open_idx { $+ o };
+            .idx += 1;
+        };
+
+        !nid  = candidates.(std:rand len[candidates]);
+        !spec = ${
+            node   = nid.0,
+            out    = (node_id:out_list nid).0.1,
+            params = random_params nid,
+            inputs = $[],
+        };
+
+        std:push parent.inputs $p(in_name, spec);
+
+        # Only the first two inputs are offered to keep the trees
+        # from growing too wide to fit on the grid:
+        iter p ($i(0, 2) (node_id:param_list nid).inputs) {
+            std:push open $p(spec, p.name[]);
+        };
+    };
+
+    root
+};
+!@export random_tree = random_tree;
+
+# Places a randomly generated patch with the `Out` node at `pos`.
+!@export random_patch = {|4<5| !(matrix, pos, categories, node_count, seed) = @;
+    !spec = _? ~ random_tree categories node_count seed;
+    tree matrix pos spec
+};
@@ -2,6 +2,7 @@
 !@import std;
 !@import ui;
 !@import hx;
+!@import patch_gen wllib:patch_gen;
 
 !inside_rect = {!(target, test) = @;
          test.x >= target.x
@@ -26,6 +27,13 @@
     _.mouse_release_at pos :left;
 };
 
+!do_click_rmb = {
+    !pos = _1.pos + $f(1.0, 1.0);
+    std:displayln ">>> click(RMB)@" pos;
+    _.mouse_press_at pos :right;
+    _.mouse_release_at pos :right;
+};
+
 !do_drag = {
     !pos = _1.pos + $f(1.0, 1.0);
     std:displayln ">>> pick(LMB)@" pos;
@@ -51,6 +59,7 @@
 };
 
 
+
 !do_hover= {
     !pos = _1.pos + $f(1.0, 1.0);
     std:displayln ">>> hover@" pos;
@@ -258,4 +267,1019 @@
 #        dump_labels td;
     };
     ui:install_test test;
+
+
+    .test = ui:test_script "lint_marks_unconnected_nodes";
+    test.add_step :init {||
+        matrix_init  $i(0, 1) :TR ${chain=$[
+            $[:sin, :sig],
+            $[:inp, :amp, :sig],
+        ]};
+    };
+    test.add_step :click_lint {!(td, labels) = @;
+        !res = $S(*:{path=*.button_float_menu, label=Lint}) labels;
+        do_click td res.0;
+    };
+    test.add_step :check_lint_text {!(td, labels) = @;
+        !res = $S(*:{ctrl=Ctrl\:\:WichText, label=*problem*}) labels;
+        std:assert_str_eq res.0.source "text" "Found the lint summary";
+
+        !res = $S(*:{ctrl=Ctrl\:\:WichText, label=*no\ Out\ node*}) labels;
+        std:assert_str_eq res.0.source "text" "Found missing Out node";
+    };
+    test.add_step :disable_lint {!(td, labels) = @;
+        !res = $S(*:{path=*.button_float_menu, label=Lint}) labels;
+        do_click td res.0;
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "lint_node_in_two_cells";
+    test.add_step :init {||
+        !matrix = hx:get_main_matrix_handle[];
+        matrix.clear[];
+        matrix.place_chain $i(0, 1) :TR ${chain=$[
+            $[:sin, :sig],
+            $[:inp, :amp, :sig],
+        ]};
+        matrix.place_chain $i(4, 1) :TR ${chain=$[
+            $[:amp, :sig],
+            $[:ch1, :out],
+        ]};
+
+        # Both cells are the same Amp, fed in one cell, feeding the Out
+        # from the other one:
+        !cell = matrix.get $i(4, 1);
+        cell.node_id = $p("amp", 0);
+        matrix.set $i(4, 1) cell;
+        matrix.sync[];
+
+        !diags = matrix.lint[];
+        !amp_diags = $@vec iter d diags {
+            if is_some[d.node_id] &and d.node_id.0 == "amp" { $+ d.kind };
+        };
+        std:assert_str_eq amp_diags $[:duplicate-instance, :duplicate-instance]
+            "Amp is connected on both sides";
+
+        matrix.clear[];
+        matrix.place_chain $i(0, 1) :TR ${chain=$[
+            $[:inp, :amp, :sig],
+            $[:ch1, :out],
+        ]};
+        matrix.sync[];
+
+        !input_diags = $@vec iter d matrix.lint[] {
+            if d.kind == :dangling-input &or d.kind == :knob-disabled { $+ d };
+        };
+        std:assert_eq len[input_diags] 1 "The unfed input is reported once";
+        std:assert_str_eq input_diags.0.pos $i(0, 1) "At the cell with the input";
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "signal_type_through_amp";
+    test.add_step :init {||
+        !matrix = hx:get_main_matrix_handle[];
+        matrix.clear[];
+        matrix.place_chain $i(0, 1) :TR ${chain=$[
+            $[:ad, :eoet],
+            $[:inp, :amp, :sig],
+            $[:trig, :ad],
+        ]};
+        matrix.sync[];
+        std:assert_eq len[matrix.signal_mismatches[]] 0
+            "A trigger passed through an Amp is fine";
+
+        matrix.clear[];
+        matrix.place_chain $i(0, 1) :TR ${chain=$[
+            $[:sin, :sig],
+            $[:inp, :amp, :sig],
+            $[:trig, :ad],
+        ]};
+        matrix.sync[];
+        !diags = matrix.signal_mismatches[];
+        std:assert_eq len[diags] 1 "Audio passed through an Amp is reported";
+        std:assert_str_eq diags.0.node_id $p("amp", 0) "At the Amp";
+    };
+    ui:install_test test;
+
+    .test = ui:test_script "patch_gen_parallel_voices";
+    test.add_step :init {||
+        !matrix = hx:get_main_matrix_handle[];
+        matrix.clear[];
+        std:assert ~ patch_gen:tree matrix $i(6, 4) ~
+            patch_gen:to_out ~ patch_gen:voices ${
+                node   = :amp,
+                params = $[:gain => 0.3],
+                inputs = $[$p(:inp, ${ node = :sin })],
+            } 4;
+    };
+    test.add_step :check_cells {!(td, labels) = @;
+        !res = $S(*:{source=cell_name, label=Mix3}) labels;
+        std:assert_eq len[res] 2 "Two mixers for 4 voices";
+
+        !res = $S(*:{source=cell_name, label=Sin}) labels;
+        std:assert_eq len[res] 4 "One oscillator per voice";
+
+        !res = $S(*:{source=cell_name, label=Out}) labels;
+        std:assert_eq len[res] 1 "The Out node";
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "command_palette_places_node";
+    test.add_step :init {||
+        !matrix = hx:get_main_matrix_handle[];
+        matrix.clear[];
+        matrix.sync[];
+    };
+    test.add_step :click_find {!(td, labels) = @;
+        !res = $S(*:{path=*.button_float_menu, label=Find}) labels;
+        do_click td res.0;
+    };
+    test.add_step :next_page {!(td, labels) = @;
+        !res = $S(*:{path=*.palette_page, label=Page\ 1\ /*}) labels;
+        std:assert len[res] > 0 "The palette starts at the first page";
+
+        !res = $S(*:{path=*.palette_item, label=Show\ Help\ \(cmd\)}) labels;
+        std:assert len[res] > 0 "The commands are on the first page";
+
+        !res = $S(*:{path=*.palette_nav_btn, label=Next}) labels;
+        do_click td res.0;
+    };
+    test.add_step :prev_page {!(td, labels) = @;
+        !res = $S(*:{path=*.palette_page, label=Page\ 2\ /*}) labels;
+        std:assert len[res] > 0 "Switched to the next page";
+
+        !res = $S(*:{path=*.palette_nav_btn, label=Prev}) labels;
+        do_click td res.0;
+    };
+    test.add_step :click_node {!(td, labels) = @;
+        !res = $S(*:{path=*.palette_item, label=*\(node\)}) labels;
+        std:assert len[res] > 0 "Found a node on the first page";
+        do_click td res.0;
+    };
+    test.add_step :check_placed {!(td, labels) = @;
+        !matrix = hx:get_main_matrix_handle[];
+        std:assert (matrix.get $i(0, 0)).node_id.0 != "nop"
+            "The node was placed at the focused cell";
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "palette_duplicate_delete_node";
+    test.add_step :init {||
+        matrix_init  $i(0, 1) :TR ${chain=$[
+            $[:sin, :sig],
+            $[:inp, :amp, :sig],
+        ]};
+    };
+    test.add_step :click_sin {!(td, labels) = @;
+        !res = $S(*:{source=cell_name, label=Sin}) labels;
+        do_click td res.0;
+    };
+    test.add_step :click_find {!(td, labels) = @;
+        !res = $S(*:{path=*.button_float_menu, label=Find}) labels;
+        do_click td res.0;
+    };
+    test.add_step :duplicate_sin {!(td, labels) = @;
+        !res = $S(*:{path=*.palette_item, label=Duplicate*}) labels;
+        do_click td res.0;
+    };
+    test.add_step :check_duplicate {!(td, labels) = @;
+        !res = $S(*:{source=cell_name, label=Sin}) labels;
+        std:assert_eq len[res] 2 "Sin was duplicated";
+    };
+    test.add_step :click_amp {!(td, labels) = @;
+        !res = $S(*:{source=cell_name, label=Amp}) labels;
+        do_click td res.0;
+    };
+    test.add_step :click_find_again {!(td, labels) = @;
+        !res = $S(*:{path=*.button_float_menu, label=Find}) labels;
+        do_click td res.0;
+    };
+    test.add_step :delete_amp {!(td, labels) = @;
+        !res = $S(*:{path=*.palette_item, label=Delete*}) labels;
+        do_click td res.0;
+    };
+    test.add_step :check_deleted {!(td, labels) = @;
+        !res = $S(*:{source=cell_name, label=Amp}) labels;
+        std:assert_eq len[res] 0 "Amp was deleted from the palette";
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "inspect_existing_connection";
+    test.add_step :init {||
+        matrix_init  $i(0, 1) :TR ${chain=$[
+            $[:sin, :sig],
+            $[:inp, :amp, :sig],
+        ]};
+    };
+    test.add_step :rmb_click_amp {!(td, labels) = @;
+        !res = $S(*:{source=cell_name, label=Amp}) labels;
+        do_click_rmb td res.0;
+    };
+    test.add_step :check_inspector {!(td, labels) = @;
+        !res = $S(*:{path=*.connector_value, label=Value*}) labels;
+        std:assert len[res] > 0 "Live value of the link is shown";
+    };
+    test.add_step :clear_connection {!(td, labels) = @;
+        !res = $S(*:{path=*.button_label, label=Clear}) labels;
+        do_click td res.0;
+    };
+    test.add_step :check_cleared {!(td, labels) = @;
+        !matrix = hx:get_main_matrix_handle[];
+        !cell = matrix.get $i(0, 1);
+        std:assert_eq cell.ports.0 $n "Sin output was disconnected";
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "node_style_color";
+    test.add_step :init {||
+        matrix_init  $i(0, 1) :TR ${chain=$[
+            $[:sin, :sig],
+            $[:inp, :amp, :sig],
+        ]};
+    };
+    test.add_step :click_amp {!(td, labels) = @;
+        !res = $S(*:{source=cell_name, label=Amp}) labels;
+        do_click td res.0;
+    };
+    test.add_step :open_style {!(td, labels) = @;
+        !res = $S(*:{path=*.button_float_menu, label=_C}) labels;
+        do_click td res.0;
+    };
+    test.add_step :pick_color {!(td, labels) = @;
+        !res = $S(*:{path=*.node_style_label, label=Label:\ \(none\)}) labels;
+        std:assert len[res] > 0 "Node style popup shows the missing label";
+
+        !res = $S(*:{path=*.node_style_color_btn, label=5}) labels;
+        do_click td res.0;
+    };
+    test.add_step :check_closed {!(td, labels) = @;
+        !res = $S(*:{path=*.node_style_label, label=Label*}) labels;
+        std:assert_eq len[res] 0 "Picking a color closes the popup";
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "signal_flow_overlay";
+    test.add_step :init {||
+        matrix_init  $i(0, 1) :TR ${chain=$[
+            $[:sin, :sig],
+            $[:inp, :amp, :sig],
+        ]};
+    };
+    test.add_step :click_sin {!(td, labels) = @;
+        !res = $S(*:{source=cell_name, label=Sin}) labels;
+        do_click td res.0;
+    };
+    test.add_step :enable_flow {!(td, labels) = @;
+        !res = $S(*:{path=*.button_float_menu, label=Flow}) labels;
+        do_click td res.0;
+    };
+    test.add_step :check_enabled {!(td, labels) = @;
+        !res = $S(*:{ctrl=Ctrl\:\:WichText, label=Signal\ flow\ overlay\ enabled}) labels;
+        std:assert len[res] > 0 "Signal flow overlay was enabled by the button";
+
+        !res = $S(*:{path=*.button_float_menu, label=Flow}) labels;
+        do_click td res.0;
+    };
+    test.add_step :check_disabled {!(td, labels) = @;
+        !res = $S(*:{ctrl=Ctrl\:\:WichText, label=Signal\ flow\ overlay\ disabled}) labels;
+        std:assert len[res] > 0 "Signal flow overlay was disabled by the button";
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "scope_add_channel";
+    test.add_step :init {||
+        matrix_init  $i(0, 1) :TR ${chain=$[
+            $[:sin, :sig],
+            $[:inp, :amp, :sig],
+        ]};
+    };
+    test.add_step :check_default {!(td, labels) = @;
+        !res = $S(*:{path=*.scope_status, label=1\ ch\ Out\ L\ |*}) labels;
+        std:assert len[res] > 0 "Scope shows the left output by default";
+
+        !res = $S(*:{path=*.scope_button, label=Add}) labels;
+        do_click td res.0;
+    };
+    test.add_step :check_channels {!(td, labels) = @;
+        !res = $S(*:{path=*.scope_status, label=2\ ch\ Out\ L,\ Out\ R\ |*}) labels;
+        std:assert len[res] > 0 "Right output was added to the scope";
+
+        !res = $S(*:{path=*.scope_button, label=Freeze}) labels;
+        do_click td res.0;
+    };
+    test.add_step :check_frozen {!(td, labels) = @;
+        !res = $S(*:{path=*.scope_status, label=*FROZEN}) labels;
+        std:assert len[res] > 0 "Scope is frozen";
+
+        !matrix = hx:get_main_matrix_handle[];
+        !scope  = matrix.create_scope[];
+        scope.add_channel :out 0;
+        scope.add_channel :mon 3;
+        std:assert_str_eq scope.channel_labels[] $["Out L", "Cell TR"]
+            "Monitor channels follow the device channels";
+        std:assert_str_eq (scope.channel_sources[]).1 $p(:mon, 3) "Monitor source";
+        scope.update[];
+
+        !err = match (scope.add_channel :mon 6) ($error v) => { $\.v } { $n };
+        std:assert is_some[err] "There are only six monitored signals";
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "spectrum_window_select";
+    test.add_step :check_default {!(td, labels) = @;
+        !res = $S(*:{path=*.scope_status, label=hann*}) labels;
+        std:assert len[res] > 0 "Spectrum uses the Hann window by default";
+
+        !res = $S(*:{path=*.scope_button, label=Window}) labels;
+        do_click td res.0;
+    };
+    test.add_step :check_hamming {!(td, labels) = @;
+        !res = $S(*:{path=*.scope_status, label=hamming*}) labels;
+        std:assert len[res] > 0 "Spectrum window was changed to Hamming";
+
+        !res = $S(*:{path=*.scope_button, label=Hold}) labels;
+        do_click td res.0;
+    };
+    test.add_step :check_hold_off {!(td, labels) = @;
+        !res = $S(*:{path=*.scope_status, label=*hold\ off\ |\ Out\ L}) labels;
+        std:assert len[res] > 0 "Peak hold was disabled";
+
+        !res = $S(*:{path=*.spectrum_axis_label, label=20}) labels;
+        std:assert len[res] > 0 "Frequency axis starts at 20 Hz";
+
+        !res = $S(*:{path=*.scope_button, label=Src}) labels;
+        do_click td res.0;
+    };
+    test.add_step :check_source {!(td, labels) = @;
+        !res = $S(*:{path=*.scope_status, label=*\ Hz\ @*hold\ off\ |\ Out\ R}) labels;
+        std:assert len[res] > 0 "Spectrum analyzes the right output";
+
+        !matrix   = hx:get_main_matrix_handle[];
+        !spectrum = matrix.create_spectrum_model :mon 3 256;
+        spectrum.update[];
+        std:assert_str_eq spectrum.get_source[] $p(:mon, 3) "Monitor source";
+        std:assert_eq spectrum.get_source_label[] "Cell TR";
+        std:assert_eq spectrum.get_freq_unit[] "cycles";
+        std:assert_eq (spectrum.x_to_freq 0.0) 1.0 "Monitor axis starts at 1 cycle";
+
+        spectrum.set_source :out 0;
+        std:assert_eq spectrum.get_freq_unit[] "Hz";
+        std:assert_eq (spectrum.x_to_freq 0.0) 20.0 "Device axis starts at 20 Hz";
+
+        !err = match (spectrum.set_source :mon 6) ($error v) => { $\.v } { $n };
+        std:assert is_some[err] "There are only six monitored signals";
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "record_toggle";
+    test.add_step :select_source {!(td, labels) = @;
+        !res = $S(*:{path=*.button_float_menu, label=Out\ L+Out\ R}) labels;
+        std:assert len[res] > 0 "Recorder records the stereo output by default";
+
+        !rec = hx:new_recorder[];
+        !err = match (rec.set_sources $[$p(:mon, 3)]) ($error v) => { $\.v } { $n };
+        std:assert is_some[err] "Monitored cell signals can't be recorded";
+        std:assert_str_eq rec.get_sources[] $[$p(:out, 0), $p(:out, 1)];
+        do_click td res.0;
+    };
+    test.add_step :start {!(td, labels) = @;
+        !res = $S(*:{path=*.button_float_menu, label=Out\ L}) labels;
+        std:assert len[res] > 0 "Recorder source changed to the left output";
+
+        !res = $S(*:{path=*.button_float_menu, label=Rec}) labels;
+        do_click td res.0;
+    };
+    test.add_step :change_source {!(td, labels) = @;
+        !res = $S(*:{path=*.button_float_menu, label=Stop*}) labels;
+        std:assert len[res] > 0 "Rec button shows that it is recording";
+
+        !res = $S(*:{path=*.button_float_menu, label=Out\ L}) labels;
+        do_click td res.0;
+    };
+    test.add_step :stop {!(td, labels) = @;
+        !res = $S(*:{path=*.button_float_menu, label=Out\ L}) labels;
+        std:assert len[res] > 0 "Source can't be changed while recording";
+
+        !res = $S(*:{path=*.button_float_menu, label=Stop*}) labels;
+        do_click td res.0;
+    };
+    test.add_step :check_stopped {!(td, labels) = @;
+        !res = $S(*:{path=*.button_float_menu, label=Rec}) labels;
+        std:assert len[res] > 0 "Rec button shows that the recording stopped";
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "recorder_instances";
+    test.add_step :record {||
+        !rec_a = hx:new_recorder[];
+        !rec_b = hx:new_recorder[];
+
+        std:assert_str_eq rec_a.get_sources[] $[$p(:out, 0), $p(:out, 1)]
+            "Recorders start with the stereo output";
+
+        !is_err = match (rec_b.set_sources $[]) ($error v) => { $t } { $f };
+        std:assert is_err "At least one channel must be selected";
+
+        rec_b.set_sources $[$p(:in, 0)];
+        std:assert_eq (std:str:join "," rec_b.get_source_labels[]) "In L"
+            "Source of the second recorder";
+
+        !path_a = "test_recorder_a.wav";
+        !path_b = "test_recorder_b.wav";
+        rec_a.start path_a;
+        rec_b.start path_b;
+        std:assert rec_a.is_recording[] "First recorder runs";
+        std:assert rec_b.is_recording[] "Second recorder runs";
+
+        !is_err = match (rec_b.set_sources $[$p(:out, 0)]) ($error v) => { $t } { $f };
+        std:assert is_err "Channels can't be changed while recording";
+
+        !info = rec_a.stop[];
+        std:assert_str_eq info.path path_a "Path of the first recording";
+        std:assert not[rec_a.is_recording[]] "First recorder stopped";
+        std:assert rec_b.is_recording[] "Second recorder still runs";
+
+        rec_b.stop[];
+        std:fs:remove_file path_a;
+        std:fs:remove_file path_b;
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "sample_browser_open";
+    test.add_step :init {||
+        matrix_init  $i(0, 1) :TR ${chain=$[
+            $[:sampl, :sig],
+        ]};
+    };
+    test.add_step :click_sampl {!(td, labels) = @;
+        !res = $S(*:{source=cell_name, label=Sampl}) labels;
+        do_click td res.0;
+    };
+    test.add_step :open_browser {!(td, labels) = @;
+        !res = $S(*:{path=*.sample_button, label=(no\ sample)}) labels;
+        std:assert len[res] > 0 "Sampl node shows the sample button";
+        do_click td res.0;
+    };
+    test.add_step :check_listing {!(td, labels) = @;
+        !res = $S(*:{path=*.sample_browser_dir_item, label=../}) labels;
+        std:assert len[res] > 0 "Sample browser lists the parent directory";
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "sample_editor_select_point";
+    test.add_step :init {||
+        matrix_init  $i(0, 1) :TR ${chain=$[
+            $[:sampl, :sig],
+        ]};
+    };
+    test.add_step :click_sampl {!(td, labels) = @;
+        !res = $S(*:{source=cell_name, label=Sampl}) labels;
+        do_click td res.0;
+    };
+    test.add_step :select_point {!(td, labels) = @;
+        !res = $S(*:{path=*.sample_edit_button, label=start}) labels;
+        std:assert len[res] > 0 "Sample editor starts with the start point";
+        do_click td res.0;
+    };
+    test.add_step :check_point {!(td, labels) = @;
+        !res = $S(*:{path=*.sample_edit_button, label=end}) labels;
+        std:assert len[res] > 0 "End point was selected";
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "sample_points_removed_with_node";
+    test.add_step :init {||
+        matrix_init  $i(0, 1) :TR ${chain=$[
+            $[:sampl, :sig],
+        ]};
+        !matrix = hx:get_main_matrix_handle[];
+        !sed    = matrix.create_sample_editor $p(:sampl, 0);
+        sed.set_point :loop_start 0.25;
+        std:assert_eq sed.get_points[].loop_start 0.25 "Loop start stored";
+    };
+    test.add_step :click_sampl {!(td, labels) = @;
+        !res = $S(*:{source=cell_name, label=Sampl}) labels;
+        do_click td res.0;
+    };
+    test.add_step :click_find {!(td, labels) = @;
+        !res = $S(*:{path=*.button_float_menu, label=Find}) labels;
+        do_click td res.0;
+    };
+    test.add_step :delete {!(td, labels) = @;
+        !res = $S(*:{path=*.palette_item, label=Delete*}) labels;
+        do_click td res.0;
+    };
+    test.add_step :check_removed {!(td, labels) = @;
+        !res = $S(*:{source=cell_name, label=Sampl}) labels;
+        std:assert_eq len[res] 0 "Sampl was deleted";
+
+        # Place the node again, it must not get the old loop points:
+        !matrix = hx:get_main_matrix_handle[];
+        matrix.set $i(0, 1) ${
+            node_id = $p(:sampl, 0),
+            pos     = $i(0, 1),
+            ports   = $[$n, $n, $n, $n, $n, $n],
+        };
+        matrix.sync[];
+
+        !sed = matrix.create_sample_editor $p(:sampl, 0);
+        std:assert_eq sed.get_points[].loop_start 0.0
+            "Loop points of the deleted node were removed";
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "sample_storage_round_trip";
+    test.add_step :store {||
+        !matrix = hx:get_main_matrix_handle[];
+        matrix.clear[];
+        matrix.place_chain $i(0, 1) :B ${chain=$[ $[:sampl] ]};
+        matrix.place_chain $i(2, 1) :B ${chain=$[ $[:sampl] ]};
+        matrix.sync[];
+
+        !wav = "test_sample_storage.wav";
+        !buf = hx:new_sample_buf_from ~ $@vec iter i 0 => 441 {
+            $+ ~ (float i) / 441.0;
+        };
+        buf.save_wav wav 44100;
+
+        # Both nodes use the same file:
+        !sample = hx:load_sample_file wav;
+        matrix.set_param (node_id:inp_param $p(:sampl, 0) :sample) sample;
+        matrix.set_param (node_id:inp_param $p(:sampl, 1) :sample) sample;
+        !frames = (matrix.create_sample_editor $p(:sampl, 0)).len[];
+
+        unwrap ~ matrix.save_patch "test_sample_storage_path.hxy" :path;
+        unwrap ~ matrix.save_patch "test_sample_storage_embed.hxy" :embed;
+
+        !text = unwrap ~ std:io:file:read_text "test_sample_storage_embed.hxy";
+        std:assert_eq (len[text $p("deflate:", 0)] - 1) 1
+            "The sample is embedded once and compressed";
+
+        # The loaded sample is stored, not the changed file:
+        (hx:new_sample_buf_from $[0.5, 0.5]).save_wav wav 44100;
+
+        unwrap ~ matrix.save_patch "test_sample_storage_bundle.hxy" :bundle;
+        !bundle_path =
+            (matrix.get_param (node_id:inp_param $p(:sampl, 0) :sample)).s[];
+        std:assert ($i(0, 30) bundle_path) == "test_sample_storage_bundle.hxy"
+            "Sample path refers to the bundle";
+        std:assert_eq
+            (matrix.get_param (node_id:inp_param $p(:sampl, 1) :sample)).s[]
+            bundle_path
+            "Both nodes refer to the same bundled file";
+
+        # A damaged bundled file is written again:
+        (hx:new_sample_buf_from $[0.5, 0.5]).save_wav bundle_path 44100;
+        unwrap ~ matrix.save_patch "test_sample_storage_bundle.hxy" :bundle;
+
+        std:fs:remove_file wav;
+
+        # The path patch reports the missing file once:
+        unwrap ~ matrix.load_patch "test_sample_storage_path.hxy";
+        !missing = matrix.missing_samples[];
+        std:assert_eq len[missing] 1 "Missing sample is reported once";
+        std:assert_eq missing.0.0 wav "Path of the missing sample";
+
+        # The embedded sample is decoded again:
+        unwrap ~ matrix.load_patch "test_sample_storage_embed.hxy";
+        std:assert_eq len[matrix.missing_samples[]] 0 "Embedded sample found";
+        !sed = matrix.create_sample_editor $p(:sampl, 1);
+        std:assert_eq sed.len[] frames "Embedded sample was decoded";
+
+        # The bundled sample is found relative to the patch:
+        unwrap ~ matrix.load_patch "test_sample_storage_bundle.hxy";
+        std:assert_eq len[matrix.missing_samples[]] 0 "Bundled sample found";
+        !sed = matrix.create_sample_editor $p(:sampl, 0);
+        std:assert_eq sed.len[] frames "Bundled sample was loaded";
+
+        std:fs:remove_file "test_sample_storage_path.hxy";
+        std:fs:remove_file "test_sample_storage_embed.hxy";
+        std:fs:remove_file "test_sample_storage_bundle.hxy";
+        std:fs:remove_dir_all "test_sample_storage_bundle.hxy.samples";
+
+        matrix.clear[];
+        matrix.sync[];
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "sample_buf_ops";
+    test.add_step :process {!(td, labels) = @;
+        !buf = hx:new_sample_buf_from $[0.0, 0.0, 0.25, (-0.5), 0.125, 0.0];
+        buf.trim_silence[];
+        std:assert_eq buf.len[] 3 "Silence was trimmed";
+
+        buf.normalize[];
+        std:assert_eq buf.1 (-1.0) "Normalized to the peak";
+
+        buf.reverse[];
+        std:assert_eq buf.0 0.25 "Reversed";
+
+        !parts = buf.slices 3;
+        std:assert_eq len[parts] 3 "Cut into 3 slices";
+        std:assert_eq parts.2.0 0.5 "Last slice";
+
+        buf.mix parts.0 0.0 2.0;
+        std:assert_eq buf.len[] 3 "Mixing keeps the longer length";
+        std:assert_eq buf.0 0.5 "Mixed with gain";
+
+        # A tone at the nyquist frequency of the source must not alias
+        # down to DC, but DC must pass:
+        !tone = hx:new_sample_buf_from ~ $@vec iter i 0 => 1000 {
+            $+ ~ if i % 2 == 0 { 1.0 } { -1.0 };
+        };
+        tone.resample 44100 22050;
+        std:assert_eq tone.len[] 500 "Resampled to half the length";
+        std:assert (std:num:abs tone.250) < 0.01 "Tone above nyquist was filtered";
+
+        !dc = hx:new_sample_buf_from ~ $@vec iter i 0 => 1000 { $+ 0.5 };
+        dc.resample 44100 22050;
+        std:assert (std:num:abs (dc.250 - 0.5)) < 0.001 "DC passes the filter";
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "pattern_text_roundtrip";
+    test.add_step :convert {!(td, labels) = @;
+        !pat = ui:create_pattern_data_unconnected 64;
+        !text = $q°ROWS 4
+TYPES note value step gate value value
+00 | C-4 FFF --- --- --- ---
+01 | --- --- --- 0F0 --- ---
+02 | --- --- --- --- --- ---
+03 | A#2 001 --- --- --- ---
+°;
+        std:assert (pat.from_text text) "Text was imported";
+        std:assert_str_eq pat.to_text[] text "Text round trip";
+
+        !csv = pat.to_csv[];
+        !lines = std:split "\n" csv;
+        std:assert_str_eq lines.0 "rows,4" "CSV row count";
+        std:assert_str_eq lines.1 "types,note,value,step,gate,value,value" "CSV column types";
+        std:assert_str_eq lines.2 "0,03C,FFF,,,," "CSV first row";
+
+        !pat2 = ui:create_pattern_data_unconnected 64;
+        std:assert (pat2.from_csv csv) "CSV was imported";
+        std:assert_str_eq pat2.to_text[] text "CSV round trip";
+
+        # A bad line leaves the whole pattern unchanged:
+        !err = match (pat2.from_csv "rows,2\ntypes,gate\n0,0F0\n1,XYZ")
+            ($error v) => { $\.v } { $n };
+        std:assert is_some[err] "Bad cell value is reported";
+        std:assert_str_eq pat2.to_text[] text "Pattern unchanged after the error";
+
+        # Cells, that are not in the import, are cleared:
+        std:assert (pat2.from_text "ROWS 4\n01 | --- --- --- 0F0 --- ---")
+            "Partial text was imported";
+        std:assert_eq (pat2.get_cell 0 0) $n "Cells of the old pattern were cleared";
+        std:assert_eq (pat2.get_cell 1 3) 0xF0 "Imported cell";
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "pattern_script_ops";
+    test.add_step :generate {!(td, labels) = @;
+        !pat = ui:create_pattern_data_unconnected 64;
+        pat.set_rows 8;
+        std:assert_eq pat.rows[] 8 "Row count was set";
+
+        pat.set_col_type 0 :gate;
+        pat.euclidean 0 3;
+        !hits = $@vec iter row 0 => 8 {
+            if is_some[pat.get_cell row 0] { $+ row };
+        };
+        std:assert_str_eq hits $[0, 3, 6] "Euclidean 3 in 8";
+
+        pat.set_col_type 1 :note;
+        std:assert_str_eq (pat.get_col_type 1) :note "Note column";
+        pat.set_cell 0 1 60;
+        pat.transpose 1 12;
+        std:assert_eq (pat.get_cell 0 1) 72 "Transposed by an octave";
+
+        pat.rotate_rows 1;
+        std:assert_eq (pat.get_cell 1 1) 72 "Rotated down";
+        std:assert is_none[pat.get_cell 0 1] "Rotated cell left row 0";
+
+        pat.randomize 2 16 32 1.0 42;
+        iter row 0 => 8 {
+            !v = pat.get_cell row 2;
+            std:assert (v >= 16 &and v <= 32) "Random value in range";
+        };
+
+        !is_err = match (pat.get_cell 9 0) ($error v) => { $t } { $f };
+        std:assert is_err "Reading beyond the row count is an error";
+    };
+    ui:install_test test;
+
+
+    .test = ui:test_script "pattern_midi_import";
+    test.add_step :import {!(td, labels) = @;
+        # Two notes, 96 ticks per quarter note: C-4 for 2 rows, then E-4
+        # for 1 row, the note off of E-4 uses the running status.
+        !path = "test_pattern_midi_import.mid";
+        std:io:file:write_safe path
+            ($b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x00\x60"
+                $b"MTrk\x00\x00\x00\x13\x00\x90\x3C\x64\x30\x80\x3C\x00\x00\x90\x40\x64\x18\x40\x00\x00\xFF\x2F\x00");
+
+        !info = hx:midi_file_info path;
+        std:assert_eq info.division 96 "Ticks per quarter note";
+        std:assert_eq info.tracks.0.notes 2 "Notes in the track";
+
+        !pat = ui:create_pattern_data_unconnected 64;
+        !res = pat.import_midi path 0;
+        std:fs:remove_file path;
+
+        std:assert_eq res.rows 3 "Pattern resized to the track";
+        std:assert_eq res.notes 2 "All notes imported";
+        std:assert_eq res.dropped 0 "No overlapping notes";
+
+        std:assert_str_eq (pat.get_col_type 0) :note "Note column";
+        std:assert_str_eq (pat.get_col_type 1) :gate "Gate column";
+        std:assert_eq (pat.get_cell 0 0) 60 "C-4";
+        std:assert is_none[pat.get_cell 1 0] "C-4 is held";
+        std:assert_eq (pat.get_cell 2 0) 64 "E-4";
+        std:assert_eq (pat.get_cell 0 1) 0xF0F "Gate held";
+        std:assert_eq (pat.get_cell 1 1) 0x80F "Gate released for the next note";
+        std:assert_eq (pat.get_cell 2 1) 0xF0F "Last note sounds its whole row";
+
+        # C-4 for 2 rows, a rest of 1 row, then E-4 for 1 row. Notes
+        # followed by a rest keep the gate open for their whole length:
+        std:io:file:write_safe path
+            ($b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x00\x60"
+                $b"MTrk\x00\x00\x00\x13\x00\x90\x3C\x64\x30\x80\x3C\x00\x18\x90\x40\x64\x18\x40\x00\x00\xFF\x2F\x00");
+        !res = pat.import_midi path 0;
+        std:fs:remove_file path;
+
+        std:assert_eq res.rows 4 "Pattern includes the rest";
+        !gates = $@vec iter row 0 => 4 { $+ ~ pat.get_cell row 1 };
+        std:assert_str_eq gates $[0xF0F, 0xF0F, $n, 0xF0F]
+            "Gates are open exactly for the length of the notes";
+
+        !is_err = match (pat.import_midi path 0) ($error v) => { $t } { $f };
+        std:assert is_err "Missing MIDI file is an error";
+    };
+    ui:install_test test;
+
+    .test = ui:test_script "pattern_bank_switch";
+    test.add_step :switch {!(td, labels) = @;
+        matrix_init $i(0, 1) :TR ${chain=$[ $[:tseq, :trk1] ]};
+
+        !matrix = hx:get_main_matrix_handle[];
+        !pat = matrix.create_pattern_data_model 0;
+        pat.set_rows 4;
+        pat.set_col_type 0 :value;
+        pat.set_cell 0 0 0x100;
+
+        !song = matrix.select_pattern 0 1;
+        std:assert_eq song.current 1 "Switched to pattern 1";
+        std:assert_eq song.count 2 "New pattern in the bank";
+        std:assert is_none[pat.get_cell 0 0] "New pattern is empty";
+        std:assert_eq pat.rows[] 4 "New pattern has the same row count";
+
+        pat.set_cell 1 0 0x200;
+        matrix.select_pattern 0 0;
+        std:assert_eq (pat.get_cell 0 0) 0x100 "Pattern 0 restored";
+        std:assert is_none[pat.get_cell 1 0] "Pattern 1 not in pattern 0";
+        matrix.select_pattern 0 1;
+        std:assert_eq (pat.get_cell 1 0) 0x200 "Pattern 1 restored";
+
+        !song = matrix.set_pattern_chain 0 $[0, 1, 1];
+        std:assert_str_eq song.chain $[0, 1, 1] "Chain set";
+        !is_err = match (matrix.set_pattern_chain 0 $[5]) ($error v) => { $t } { $f };
+        std:assert is_err "Chain with patterns outside the bank";
+
+        !song = matrix.set_pattern_song_mode 0 $t;
+        std:assert song.song_mode "Song mode on";
+        std:assert_eq song.current 0 "Song starts with the first chain entry";
+        matrix.set_pattern_song_mode 0 $f;
+    };
+    ui:install_test test;
+
+    .test = ui:test_script "pattern_undo_copy_paste";
+    test.add_step :edit {!(td, labels) = @;
+        !pat  = ui:create_pattern_data_unconnected 64;
+        !hist = ui:create_pattern_history[];
+        pat.set_rows 8;
+        hist.reset pat;
+
+        pat.set_cell 0 0 0x10;
+        pat.set_cell 1 1 0x20;
+        std:assert hist.checkpoint[pat] "Change recorded";
+        std:assert not[hist.checkpoint[pat]] "No change, no step";
+
+        pat.set_cell 0 0 0x30;
+        hist.checkpoint pat;
+        std:assert hist.undo[pat] "Undone";
+        std:assert_eq (pat.get_cell 0 0) 0x10 "Value before the change";
+        std:assert hist.redo[pat] "Redone";
+        std:assert_eq (pat.get_cell 0 0) 0x30 "Value after the change";
+
+        !block = pat.copy_block 0 0 2 2;
+        std:assert_str_eq block $[$[0x30, $n], $[$n, 0x20]] "Copied block";
+
+        pat.paste_block 6 4 block;
+        std:assert_eq (pat.get_cell 6 4) 0x30 "Pasted cell";
+        std:assert_eq (pat.get_cell 7 5) 0x20 "Pasted cell";
+        pat.paste_block 7 5 block;
+        std:assert_eq (pat.get_cell 7 5) 0x30 "Block cut off at the pattern end";
+
+        pat.set_cursor 3 2;
+        std:assert_str_eq pat.get_cursor[] $i(3, 2) "Cursor moved";
+    };
+    test.add_step :note_sources {!(td, labels) = @;
+        !keys = ui:create_note_keys_model[];
+        std:assert_str_eq keys.pop_keys[] $[] "No key clicked yet";
+
+        !tap = hx:new_midi_note_tap[];
+        std:assert_str_eq tap.pop_notes[] $[] "No MIDI notes received";
+    };
+    ui:install_test test;
+
+    .test = ui:test_script "tseq_overview_edit_column";
+    test.add_step :init {||
+        matrix_init $i(0, 1) :TR ${chain=$[ $[:tseq, :trk1] ]};
+        !matrix = hx:get_main_matrix_handle[];
+        matrix.place_chain $i(3, 1) :TR ${chain=$[ $[:tseq, :trk1] ]};
+        matrix.sync[];
+        std:assert_str_eq matrix.tracker_ids[] $[0, 1] "Two TSeq instances";
+    };
+    test.add_step :open {!(td, labels) = @;
+        !res = $S(*:{label=All}) labels;
+        do_click td res.0;
+    };
+    test.add_step :click_column {!(td, labels) = @;
+        !res = $S(*:{label=TSeq\ 1*}) labels;
+        std:assert len[res] > 0 "Column header of TSeq 1";
+        do_click td res.0;
+    };
+    test.add_step :check {!(td, labels) = @;
+        !res = $S(*:{label=TSeq\ 1}) labels;
+        std:assert len[res] > 0 "Pattern editor shows TSeq 1";
+    };
+    ui:install_test test;
+
+    .test = ui:test_script "matrix_transaction_rollback";
+    test.add_step :init {||
+        matrix_init $i(0, 1) :TR ${chain=$[ $[:sin, :sig] ]};
+        !matrix   = hx:get_main_matrix_handle[];
+        !freq     = node_id:inp_param $p(:sin, 0) :freq;
+        !new_freq = node_id:inp_param $p(:sin, 1) :freq;
+        !old      = matrix.get_param freq;
+
+        !res = matrix.transaction {!(matrix) = @;
+            matrix.set_param freq 0.9;
+
+            # A new node, it's parameter has no value before the transaction:
+            !cell = matrix.get $i(3, 3);
+            cell.node_id = $p(:sin, 1);
+            matrix.set $i(3, 3) cell;
+            matrix.set_param new_freq 0.9;
+
+            $error :abort
+        };
+        !is_err = match res ($error v) => { $t } { $f };
+        std:assert is_err "Transaction failed";
+        std:assert_str_eq (matrix.get_param freq) old "Param restored";
+        std:assert_str_eq (matrix.get $i(3, 3)).node_id $p("nop", 0) "Cell restored";
+
+        matrix.transaction {!(matrix) = @;
+            !cell = matrix.get $i(3, 3);
+            cell.node_id = $p(:sin, 1);
+            matrix.set $i(3, 3) cell;
+            $t
+        };
+        std:assert_str_eq
+            (matrix.get_param new_freq) new_freq.default_value[]
+            "Param of the new node has the default value";
+
+        !res = matrix.transaction {!(matrix) = @;
+            _? ~ matrix.clear[];
+            $t
+        };
+        !is_err = match res ($error v) => { $t } { $f };
+        std:assert is_err "Clear is rejected inside a transaction";
+        std:assert_str_eq (matrix.get $i(3, 3)).node_id $p("sin", 1) "Nothing cleared";
+    };
+    ui:install_test test;
+
+    .test = ui:test_script "auto_route_find_route";
+    test.add_step :init {||
+        !matrix = hx:get_main_matrix_handle[];
+        matrix.clear[];
+        matrix.place_chain $i(2, 0) :B ${chain=$[ $[:sin] ]};
+        matrix.place_chain $i(2, 3) :B ${chain=$[ $[:amp] ]};
+        matrix.sync[];
+
+        !route = matrix.find_route $i(2, 0) $n $i(2, 3) :inp :pass;
+        std:assert_eq len[route] 4 "Source, two Amp nodes and destination";
+        std:assert_str_eq route.1.node_id $p("amp", 1) "First unused Amp";
+        std:assert_str_eq route.2.node_id $p("amp", 2) "Next unused Amp";
+
+        # Signals only flow to the right or down:
+        !err = match (matrix.find_route $i(2, 3) $n $i(2, 0) :inp :pass)
+            ($error v) => { $\.v } { $n };
+        std:assert_str_eq err :no-route "No route upwards";
+    };
+    test.add_step :drag_unarmed {!(td, labels) = @;
+        !res = $S(*:{source=cell_name, label=Sin}) labels;
+        do_drag td res.0;
+    };
+    test.add_step :drop_unarmed {!(td, labels) = @;
+        !res = $S(*:{source=cell_name, label=Amp}) labels;
+        do_drop td res.0;
+    };
+    test.add_step :arm {!(td, labels) = @;
+        !matrix = hx:get_main_matrix_handle[];
+        std:assert_str_eq (matrix.get $i(2, 1)).node_id $p("nop", 0)
+            "No route without arming";
+
+        !res = $S(*:{path=*.button_float_menu, label=Route}) labels;
+        do_click td res.0;
+    };
+    test.add_step :drag_sin {!(td, labels) = @;
+        !res = $S(*:{source=cell_name, label=Sin}) labels;
+        do_drag td res.0;
+    };
+    test.add_step :drop_amp {!(td, labels) = @;
+        !res = $S(*:{source=cell_name, label=Amp}) labels;
+        do_drop td res.0;
+    };
+    test.add_step :check {!(td, labels) = @;
+        !matrix = hx:get_main_matrix_handle[];
+        std:assert_str_eq (matrix.get $i(2, 1)).node_id $p("amp", 1) "Routed";
+        std:assert_str_eq (matrix.get $i(2, 2)).node_id $p("amp", 2) "Routed";
+
+        !res = $S(*:{path=*.button_float_menu, label=Route}) labels;
+        std:assert len[res] > 0 "Disarmed after the route";
+    };
+    ui:install_test test;
+
+    .test = ui:test_script "mod_matrix_scale_node";
+    test.add_step :init {||
+        matrix_init $i(0, 1) :TR ${chain=$[
+            $[:sin, :sig],
+            $[:inp, :amp, :sig],
+        ]};
+        !matrix = hx:get_main_matrix_handle[];
+        matrix.set_param_modamt (node_id:inp_param $p(:amp, 0) :inp) 0.25;
+        matrix.set_param_modamt (node_id:inp_param $p(:sin, 0) :freq) 0.1;
+
+        !entries = matrix.mod_entries[];
+        std:assert_eq len[entries] 2 "Two modulated parameters";
+        std:assert_str_eq entries.0.node_id $p("amp", 0) "Sorted by node";
+        std:assert_str_eq entries.0.source $p($p("sin", 0), "sig") "Source of amp input";
+        std:assert_str_eq entries.1.source $n "Unconnected sin freq";
+    };
+    test.add_step :open {!(td, labels) = @;
+        !res = $S(*:{label=Mod}) labels;
+        do_click td res.0;
+    };
+    test.add_step :filter_node {!(td, labels) = @;
+        !res = $S(*:{label=amp\ 0}) labels;
+        std:assert len[res] > 0 "Row of amp 0";
+        do_click td res.0;
+    };
+    test.add_step :scale {!(td, labels) = @;
+        !res = $S(*:{label=x2}) labels;
+        do_click td res.0;
+    };
+    test.add_step :check {!(td, labels) = @;
+        !matrix = hx:get_main_matrix_handle[];
+        std:assert_str_eq
+            (matrix.get_param_modamt (node_id:inp_param $p(:amp, 0) :inp))
+            $o(0.5) "Amount of amp scaled";
+        std:assert_str_eq
+            (matrix.get_param_modamt (node_id:inp_param $p(:sin, 0) :freq))
+            $o(0.1) "Other node not changed";
+    };
+    test.add_step :clear_node {!(td, labels) = @;
+        !res = $S(*:{label=xN}) labels;
+        std:assert len[res] > 0 "Clear button of the row";
+        do_click td res.0;
+    };
+    test.add_step :check_cleared {!(td, labels) = @;
+        !matrix = hx:get_main_matrix_handle[];
+        std:assert_str_eq
+            (matrix.get_param_modamt (node_id:inp_param $p(:amp, 0) :inp))
+            $o() "Modulation of amp removed";
+        std:assert_str_eq
+            (matrix.get_param_modamt (node_id:inp_param $p(:sin, 0) :freq))
+            $o(0.1) "Other node not changed";
+    };
+    ui:install_test test;
 };
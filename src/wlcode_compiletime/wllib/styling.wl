@@ -123,7 +123,7 @@ style.button_float_menu = ${
 layout.top_float_menu = ${
     position_type = :self,
     layout_type = :row,
-    width  = :pixels => 320,
+    width  = :pixels => 630,
     height = :pixels => 30,
     left   = :pixels => 0,
     right  = :stretch => 1,
@@ -262,6 +262,222 @@ layout.mode_selector_item = ${
     height = :pixels => 30,
 };
 
+style.palette_popup = ${
+    parent = :panel,
+    bg_color = ui:UI_ACCENT_BG1_CLR,
+};
+layout.palette_popup = ${
+    position_type = :self,
+    layout_type   = :column,
+    height        = :auto,
+    width         = :pixels => 300,
+    visible       = $f,
+};
+layout.palette_nav = ${
+    layout_type = :row,
+    height      = :pixels => 30,
+};
+style.palette_nav_btn = ${ parent = :button };
+layout.palette_nav_btn = ${
+    width = :pixels => 50,
+};
+style.palette_page = ${ parent = :label };
+layout.palette_page = ${
+    width = :stretch => 1,
+};
+layout.palette_results = ${
+    layout_type = :column,
+    height      = :auto,
+};
+style.palette_item = ${ parent = :button };
+layout.palette_item = ${
+    height = :pixels => 26,
+};
+
+style.connector_value = ${ parent = :label };
+layout.connector_value = ${
+    height = :pixels => 30,
+};
+
+layout.connection_select_popup = ${
+    position_type = :self,
+    layout_type   = :column,
+    height        = :auto,
+    width         = :pixels => 200,
+    visible       = $f,
+};
+style.connection_select_item = ${ parent = :button };
+layout.connection_select_item = ${
+    height = :pixels => 30,
+};
+
+style.node_style_popup = ${
+    parent = :panel,
+    bg_color = ui:UI_ACCENT_BG1_CLR,
+};
+layout.node_style_popup = ${
+    position_type = :self,
+    layout_type   = :column,
+    height        = :auto,
+    width         = :pixels => 300,
+    visible       = $f,
+};
+layout.node_style_label_row = ${
+    layout_type = :row,
+    height      = :pixels => 30,
+};
+style.node_style_label = ${ parent = :label };
+layout.node_style_label = ${
+    width = :stretch => 1,
+};
+style.node_style_clear_label = ${ parent = :button };
+layout.node_style_clear_label = ${
+    width = :pixels => 60,
+};
+layout.node_style_color_row = ${
+    layout_type = :row,
+    height      = :pixels => 30,
+};
+style.node_style_color_btn = ${
+    parent = :button,
+    border = 3,
+};
+
+style.sample_button = ${ parent = :button };
+layout.sample_button = ${
+    left   = :pixels => 4,
+    right  = :pixels => 4,
+    top    = :stretch => 1,
+    bottom = :stretch => 1,
+    height = :pixels => 40,
+};
+style.sample_waveform = ${
+    bg_color  = ui:UI_LBL_BG_CLR,
+    font_size = 12,
+};
+layout.sample_waveform = ${
+    height = :pixels => 120,
+    left   = :pixels => 2,
+    right  = :pixels => 2,
+};
+style.sample_markers = ${
+    bg_color = ui:UI_ACCENT_BG1_CLR,
+};
+layout.sample_markers = ${
+    height = :pixels => 30,
+    left   = :pixels => 2,
+    right  = :pixels => 2,
+};
+layout.sample_edit_row = ${
+    layout_type = :row,
+    height      = :pixels => 30,
+};
+style.sample_edit_button = ${ parent = :button };
+layout.sample_edit_button = ${
+    width = :stretch => 1,
+};
+style.sample_browser_popup = ${
+    parent = :panel,
+    bg_color = ui:UI_ACCENT_BG1_CLR,
+};
+layout.sample_browser_popup = ${
+    position_type = :self,
+    layout_type   = :column,
+    height        = :auto,
+    width         = :pixels => 360,
+    visible       = $f,
+};
+style.sample_browser_dir = ${ parent = :label };
+layout.sample_browser_dir = ${
+    height = :pixels => 30,
+};
+layout.sample_browser_list = ${
+    layout_type = :column,
+    height      = :auto,
+};
+style.sample_browser_item = ${ parent = :button };
+layout.sample_browser_item = ${
+    height = :pixels => 26,
+};
+style.sample_browser_dir_item = ${
+    parent = :button,
+    border_color = ui:UI_HLIGHT_CLR,
+};
+layout.sample_browser_dir_item = ${
+    height = :pixels => 26,
+};
+style.tseq_overview_popup = ${
+    parent = :panel,
+    bg_color = ui:UI_ACCENT_BG1_CLR,
+};
+layout.tseq_overview_popup = ${
+    position_type = :self,
+    layout_type   = :column,
+    height        = :auto,
+    width         = :auto,
+    visible       = $f,
+};
+layout.tseq_overview_cols = ${
+    layout_type = :row,
+    height      = :auto,
+    width       = :auto,
+};
+layout.tseq_overview_col = ${
+    layout_type = :column,
+    width       = :pixels => 300,
+    height      = :auto,
+};
+style.tseq_overview_header = ${ parent = :button };
+layout.tseq_overview_header = ${
+    height = :pixels => 30,
+};
+style.tseq_overview_pattern = ${
+    bg_color     = ui:UI_LBL_BG_CLR,
+    border_color = ui:UI_ACCENT_CLR,
+};
+layout.tseq_overview_pattern = ${
+    height = :pixels => 400,
+};
+style.mod_matrix_popup = ${
+    parent = :panel,
+    bg_color = ui:UI_ACCENT_BG1_CLR,
+};
+layout.mod_matrix_popup = ${
+    position_type = :self,
+    layout_type   = :column,
+    height        = :auto,
+    width         = :pixels => 460,
+    visible       = $f,
+};
+style.mod_matrix_title = ${ parent = :label };
+layout.mod_matrix_title = ${
+    height = :pixels => 30,
+};
+layout.mod_matrix_list = ${
+    layout_type = :column,
+    height      = :auto,
+};
+layout.mod_matrix_row = ${
+    layout_type = :row,
+    height      = :pixels => 26,
+};
+style.mod_matrix_button = ${ parent = :button };
+layout.mod_matrix_button = ${
+    width = :pixels => 40,
+};
+style.mod_matrix_node = ${ parent = :button };
+layout.mod_matrix_node = ${
+    width = :pixels => 90,
+};
+style.mod_matrix_param = ${ parent = :label };
+layout.mod_matrix_param = ${
+    width = :stretch => 1,
+};
+style.mod_matrix_amount = ${ parent = :label };
+layout.mod_matrix_amount = ${
+    width = :pixels => 60,
+};
+
 style.wichtext = ${
     bg_color = ui:UI_ACCENT_BG1_CLR,
     border = 0,
@@ -309,6 +525,33 @@ style.pattern_editor_label = ${
 layout.pattern_editor_label = ${
     height = :pixels => 30,
 };
+layout.pattern_io_row = ${
+    layout_type = :row,
+    height      = :pixels => 30,
+};
+style.pattern_io_button = ${ parent = :button };
+layout.pattern_io_button = ${
+    width = :stretch => 1,
+};
+style.pattern_song_label = ${ parent = :label };
+layout.pattern_song_label = ${
+    width = :stretch => 1,
+};
+style.pattern_note_button = ${ parent = :button, font_size = 10 };
+layout.pattern_note_button = ${
+    width = :stretch => 1,
+};
+style.pattern_note_label = ${ parent = :label };
+layout.pattern_note_label = ${
+    width = :stretch => 1,
+};
+layout.pattern_note_keys = ${
+    height = :pixels => 50,
+};
+style.pattern_chain_label = ${ parent = :label };
+layout.pattern_chain_label = ${
+    height = :pixels => 20,
+};
 layout.pattern_editor = ${
     width = :pixels => 300,
 };
@@ -330,6 +573,58 @@ layout.cell_channel_monitor = ${
     width = :pixels => 160,
 };
 
+layout.scope_panel = ${
+    layout_type = :column,
+    height      = :auto,
+};
+layout.scope_button_row = ${
+    layout_type = :row,
+    height      = :pixels => 25,
+};
+style.scope_button = ${
+    parent    = :button_label,
+    font_size = 12,
+};
+layout.scope_button = ${
+    width = :stretch => 1,
+};
+style.scope_status = ${
+    parent    = :label,
+    font_size = 12,
+};
+layout.scope_status = ${
+    height = :pixels => 20,
+};
+layout.scope_graphs = ${
+    layout_type = :column,
+    height      = :auto,
+};
+style.scope_channel = ${
+    bg_color  = ui:UI_LBL_BG_CLR,
+    font_size = 12,
+};
+layout.scope_channel = ${
+    height = :pixels => 50,
+};
+
+style.spectrum_graph = ${
+    bg_color = ui:UI_LBL_BG_CLR,
+};
+layout.spectrum_graph = ${
+    height = :pixels => 60,
+};
+layout.spectrum_axis = ${
+    layout_type = :row,
+    height      = :pixels => 16,
+};
+style.spectrum_axis_label = ${
+    parent    = :label,
+    font_size = 10,
+};
+layout.spectrum_axis_label = ${
+    width = :stretch => 1.0,
+};
+
 layout.monitor_panel = ${
     layout_type = :row,
 };
@@ -438,6 +733,12 @@ layout.monitor_column = ${
     };
 };
 
+!@export apply_color_idx = {!(wid, idx) = @;
+    !st = wid.style[];
+    st.set ${ color = ui:STD_COLORS.(idx) };
+    wid.set_style st;
+};
+
 !@export apply_color_idx_border = {!(wid, idx) = @;
     !st = wid.style[];
     st.set ${ border_color = ui:STD_COLORS.(idx) };
@@ -70,6 +70,19 @@ make a difference as long as the input is in use.
 Combining the fine adjustment areas with the Shift key allows a freedom
 of 4 resolutions to adjust parameters.
 
+The 'Mod' button in the top menu opens the modulation matrix. It lists
+all parameters with a modulation amount, together with the output that
+is connected to their input:
+
+    Node button                     - List only the parameters of that node.
+    All                             - List the parameters of all nodes.
+    x0.5 / x2 / Inv                 - Scale or invert all listed amounts.
+    Clear                           - Remove the modulation of all listed
+                                      parameters (eg. of one node).
+    - / + / x                       - Change or remove a single amount.
+    xN                              - Remove the modulation of all
+                                      parameters of the row's node.
+
 LMB = Left Mouse Button, RMB = Right Mouse Button, MMB = Middle Mouse Button
 Next page: Hex Grid
 
@@ -121,6 +134,9 @@ The most basic actions are:
     RMB click on any cell       - Open context menu for the cell.
     MMB drag grid               - Pan the grid around
     Scrollwheel Down/Up         - Zoom the grid in/out
+    'Route' + LMB drag from a
+    node to a distant node      - Connect both by a chain of Amp nodes
+                                  or a FbWr/FbRd pair.
 
 Apart from these basics, there are multiple differnt mouse drag
 gestures to change the node graph layout of the node matrix in the hex grid.
@@ -254,6 +270,43 @@ RMB (Right Mouse Button) Drag Actions:
         \___/ ^ \        \___/XXX\ drag destination node.
             \___/            \_2_/ (If you want to create a new instance
                                    instead, try dragging with RMB).
+
+
+[c18f22:Hex Grid Actions]
+
+A click on a cell focuses it. The focused cell is edited with
+these mouse shortcuts and buttons:
+
+    RMB click on a cell         - Inspect the connections of the cell: Edit,
+                                  disconnect or watch the live signal value.
+    Find button                 - Open the command palette. It lists the
+                                  editor commands and all nodes, click one
+                                  to run it or to place the node next to the
+                                  focused cell. The commands also delete the
+                                  focused node, or duplicate it with all its
+                                  parameters to a free adjacent cell.
+    Rec button                  - Record the output to a WAV file in the
+                                  current directory. Click again to stop.
+                                  The button next to it selects the
+                                  recorded channels: Output, input or both.
+                                  To record a node, connect it to Out.
+    Sample button               - In the parameters of a Sampl node:
+                                  Browse the directories and click on a
+                                  WAV file to load it into the node.
+                                  Below the waveform, the first button selects
+                                  the start, end or loop point, the arrows
+                                  move it. Zoom in to place it precisely.
+                                  The loop points are markers only, the Sampl
+                                  node loops the region from start to end.
+    Smp button                  - Select how the Save button stores samples:
+                                  As file path, copied into a directory
+                                  next to the patch, or embedded in the patch.
+    _C button                   - Change the color of the focused node,
+                                  or clear the label that a script gave it.
+    Flow button                 - Toggle the signal flow overlay. It shows
+                                  the level on every connection, marks
+                                  clipping signals with CLIP and dims
+                                  nodes with silent outputs.
 °;
 
 !@export tracker = $q°[t18:][f22:]Tracker / Pattern Editor Keyboard Shortcuts
@@ -300,4 +353,56 @@ RMB (Right Mouse Button) Drag Actions:
         [c14:'0'-'9', 'a'-'f'] - Enter value in hex digits
         [c14:'s']              - Set to 000
         [c14:'g']              - Set to FFF
+
+[t18:]* Export / Import
+[t9:]
+    The Export button writes the pattern of the shown TSeq to
+    [c14:tseq_<id>.txt] in the current directory, in a tracker like
+    text format. Import reads it back.
+
+[t18:]* MIDI Import
+[t9:]
+    The MIDI button opens a file browser for Standard MIDI Files.
+    The chosen file is imported into the shown TSeq: the notes go to
+    column 0, the gates to column 1, one row per 16th note.
+    [c14:Trk:auto] imports the first track with notes, click it
+    to choose a track number instead.
+    If columns 0 and 1 contain cells or have other column types,
+    the import asks to choose the file a second time before it
+    overwrites them.
+    Files can't be dropped onto the pattern editor from a file
+    manager, the GUI toolkit only delivers drags inside HexoSynth.
+
+[t18:]* Patterns / Song
+[t9:]
+    [c14:'<' / '>'] switch to the previous or next pattern in the bank of
+    the TSeq, going beyond the last pattern adds an empty one.
+    [c14:Chain+] appends the shown pattern to the chain, [c14:Clr] clears it.
+    [c14:Song] plays the chain, the pattern is switched at the end
+    of each pattern.
+    [c14:Ctl] lets the first output of the selected node choose the
+    pattern, the values 0.0 to 1.0 are spread over the bank.
+
+[t18:]* TSeq Overview
+[t9:]
+    [c14:All] shows the patterns of all TSeq nodes side by side, with
+    their playheads. Click the header of a column to edit that TSeq.
+
+[t18:]* Follow / Record / Undo
+[t9:]
+    [c14:Follow] moves the cursor with the playback of the TSeq.
+    [c14:Rec] writes notes from the note keys below the pattern and
+    the note-ons of the MIDI input (JACK and plugin only) into the note
+    column under the cursor. While following the playback the note goes
+    into the played row, otherwise the cursor advances by the edit step.
+    [c14:'-' / '+'] change the octave of the note keys.
+    [c14:Undo / Redo] revert changes to the pattern, typed values become
+    an undo step when the cursor moves on.
+
+[t18:]* Copy / Paste
+[t9:]
+    [c14:Mark] at the first corner and [c14:Mark] again at the
+    opposite corner selects a block. [c14:Copy] copies the block, or
+    the cell under the cursor without a selection. [c14:Paste] writes
+    it at the cursor, also into patterns of other TSeqs.
 °#;